@@ -1,25 +1,49 @@
 use crate::{
-    BufferDescription, BufferID, BufferWriteInfo, Fence, ImageDescription, ImageDescriptorType, ImageID, ImageViewDescription, ImageViewID, ImageWriteInfo, QueueSubmitInfo, QueueType,
-    SamplerDescription, SamplerID, SamplerWriteInfo, Semaphore, SwapchainDescription,
+    AccelerationStructureID, BlasDescription, BorderColor, BufferDescription, BufferID, BufferUsage, BufferWriteInfo, CommandBufferUsage, CommandPoolFlags, CommandRecorder, CopyAlignments,
+    DEFAULT_NEAREST_SAMPLER_INDEX, DEFAULT_SAMPLER_INDEX, DeviceError, DeviceLimits, DeviceLostCallback, Event, Fence, Filter, Format, FormatSupport, HeapBudget, HeapStats, ImageDescription,
+    ImageDescriptorType, ImageID, ImageLayout, ImageViewDescription, ImageViewID, ImageViewType, ImageWriteInfo, IndexType, MAX_PUSH_DESCRIPTOR_BUFFERS, MemoryStats, MemoryType, QueryPool, QueryType,
+    QueueSubmitInfo, QueueType, ReductionMode, SamplerDescription, SamplerID, SamplerMipmapMode, SamplerWriteInfo, Semaphore, SwapchainDescription, TlasDescription,
     backend::{
-        gpu_resources::{BufferSlot, GpuBindlessDescriptorPool, GpuResourcePool, ImageSlot, ImageViewSlot, SamplerSlot},
-        instance::InnerInstance,
+        gpu_resources::{AccelerationStructureSlot, BufferSlot, GpuBindlessDescriptorPool, GpuResourcePool, ImageSlot, ImageViewSlot, SamplerSlot},
+        instance::{InnerInstance, Surface},
     },
 };
 
 use super::instance::PhysicalDevice;
-use ash::vk::{self};
+use ash::{
+    prelude::VkResult,
+    vk::{self},
+};
 use std::{
     ptr::null_mut,
-    sync::{Arc, RwLock},
+    sync::{
+        Arc, Mutex, OnceLock, RwLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
     u64,
 };
+
+use smallvec::SmallVec;
 use vk_mem::*;
 
+/// Transient command pool, buffers and fence reused by `immediate_submit`.
+/// Raw handles are cached instead of a CommandRecorder, which would keep the device alive
+pub(crate) struct ImmediateCommandData {
+    pub(crate) pool: vk::CommandPool,
+    pub(crate) command_buffers: SmallVec<[vk::CommandBuffer; 2]>,
+    pub(crate) fence: vk::Fence,
+}
+
+/// Resource waiting in the deferred destruction queue
+pub(crate) enum DeferredResource {
+    Buffer(BufferID),
+    Image(ImageID),
+}
+
 pub(crate) struct InnerDevice {
     pub(crate) allocator: Allocator,
     pub(crate) handle: ash::Device,
-    pub(crate) physical_device: PhysicalDevice<'static>,
+    pub(crate) physical_device: PhysicalDevice,
     pub(crate) instance: Arc<InnerInstance>,
 
     //Pools for various gpu resources
@@ -28,23 +52,48 @@ pub(crate) struct InnerDevice {
     pub(crate) image_pool: RwLock<GpuResourcePool<ImageSlot>>,
     pub(crate) image_view_pool: RwLock<GpuResourcePool<ImageViewSlot>>,
     pub(crate) sampler_pool: RwLock<GpuResourcePool<SamplerSlot>>,
+    pub(crate) acceleration_structure_pool: RwLock<GpuResourcePool<AccelerationStructureSlot>>,
+    // Created on first use, written to their reserved bindless indices
+    pub(crate) default_sampler: OnceLock<SamplerID>,
+    pub(crate) default_nearest_sampler: OnceLock<SamplerID>,
 
     //Queues
     pub(crate) graphics_queue: vk::Queue,
     pub(crate) transfer_queue: vk::Queue,
     pub(crate) compute_queue: vk::Queue,
+    // Same handle as graphics_queue unless the device only presents from another family
+    pub(crate) present_queue: vk::Queue,
+
+    // Graphics, Transfer, Compute
+    pub(crate) immediate_command_data: [Mutex<Option<ImmediateCommandData>>; 3],
+
+    // Advanced by every present, deferred resources are destroyed once it reaches their frame
+    pub(crate) frame_counter: AtomicU64,
+    pub(crate) deferred_destroys: Mutex<Vec<(u64, DeferredResource)>>,
 
     // Extensions
     pub(crate) rt: Option<ash::khr::ray_tracing_pipeline::Device>,
+    pub(crate) acceleration_structure: Option<ash::khr::acceleration_structure::Device>,
+    pub(crate) extended_dynamic_state3: Option<ash::ext::extended_dynamic_state3::Device>,
+    pub(crate) mesh_shader: Option<ash::ext::mesh_shader::Device>,
+    pub(crate) push_descriptor: Option<ash::khr::push_descriptor::Device>,
+    // Set 1 of every pipeline layout when push descriptors are supported, null otherwise
+    pub(crate) push_descriptor_layout: vk::DescriptorSetLayout,
+
+    // Device loss
+    pub(crate) on_device_lost: Option<DeviceLostCallback>,
+    pub(crate) device_lost: AtomicBool,
 }
 
 // Swapchain Creation //
 impl InnerDevice {
+    // Falls back to an 8 bit sRGB format so the output is gamma corrected without doing it in the shader
     fn choose_surface_format(available_formats: &[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR {
-        available_formats
-            .iter()
-            .cloned()
-            .find(|f| f.format == vk::Format::R16G16B16A16_SFLOAT && f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR)
+        let find = |format: vk::Format| available_formats.iter().cloned().find(|f| f.format == format && f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR);
+
+        find(vk::Format::R16G16B16A16_SFLOAT)
+            .or_else(|| find(vk::Format::B8G8R8A8_SRGB))
+            .or_else(|| find(vk::Format::R8G8B8A8_SRGB))
             .unwrap_or_else(|| available_formats[0])
     }
 
@@ -67,30 +116,74 @@ impl InnerDevice {
         }
     }
 
+    fn surface(&self) -> &Surface {
+        return self.instance.surface.as_ref().expect("Headless devices have no surface and cannot create swapchains");
+    }
+
+    // The capabilities cached at device creation go stale once the window is resized
+    pub(crate) fn surface_extent(&self, width: u32, height: u32) -> vk::Extent2D {
+        let surface = self.surface();
+        let capabilities = unsafe {
+            surface
+                .loader
+                .get_physical_device_surface_capabilities(self.physical_device.handle, surface.handle)
+                .expect("Failed to get surface capabilities")
+        };
+
+        return InnerDevice::choose_extent(&capabilities, width, height);
+    }
+
     pub(crate) fn create_swapchain_data(
         &self,
         swapchain_description: &SwapchainDescription,
         old_swapchain: vk::SwapchainKHR,
-    ) -> (ash::khr::swapchain::Device, vk::SwapchainKHR, Vec<ImageID>, Vec<ImageViewID>) {
+    ) -> (ash::khr::swapchain::Device, vk::SwapchainKHR, Vec<ImageID>, Vec<ImageViewID>, vk::Format, vk::Extent2D) {
         let swapchain_loader = ash::khr::swapchain::Device::new(&self.instance.handle, &self.handle);
 
-        let support = &self.physical_device.swapchain_support;
+        let surface = self.surface();
+        let support = self.physical_device.swapchain_support.as_ref().unwrap();
+
+        if !support.capabilities.supported_usage_flags.contains(swapchain_description.usage.flags) {
+            panic!(
+                "Swapchain usage {:?} is not supported by the surface, supported usage is {:?}",
+                swapchain_description.usage.flags, support.capabilities.supported_usage_flags
+            );
+        }
+
+        let extent = self.surface_extent(swapchain_description.width, swapchain_description.height);
+
+        if extent.width == 0 || extent.height == 0 {
+            panic!("Cannot create a swapchain with a zero sized extent, skip recreation while the window is minimized");
+        }
 
-        let extent = InnerDevice::choose_extent(&support.capabilities, swapchain_description.width, swapchain_description.height);
         let present_mode = InnerDevice::choose_present_mode(&support.present_modes);
-        let surface_format = InnerDevice::choose_surface_format(&support.formats);
+
+        // A max image count of 0 means there is no upper limit
+        let mut image_count = swapchain_description.image_count.max(support.capabilities.min_image_count);
+        if support.capabilities.max_image_count != 0 {
+            image_count = image_count.min(support.capabilities.max_image_count);
+        }
+
+        // Queried again instead of using the cached formats, moving the window to another monitor can change them
+        let surface_formats = unsafe {
+            surface
+                .loader
+                .get_physical_device_surface_formats(self.physical_device.handle, surface.handle)
+                .expect("Failed to get surface formats")
+        };
+        let surface_format = InnerDevice::choose_surface_format(&surface_formats);
 
         let graphics_family = self.physical_device.queue_families.graphics_family.expect("This shouldnt be possible lol");
         let present_family = self.physical_device.queue_families.presetation_family.expect("This shouldnt be possible lol");
 
         let mut create_info = vk::SwapchainCreateInfoKHR::default()
-            .surface(self.instance.surface.handle)
-            .min_image_count(swapchain_description.image_count)
+            .surface(surface.handle)
+            .min_image_count(image_count)
             .image_format(surface_format.format)
             .image_color_space(surface_format.color_space)
             .image_extent(extent)
             .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT);
+            .image_usage(swapchain_description.usage.flags);
 
         let queue_family_indices = [graphics_family, present_family];
 
@@ -126,6 +219,14 @@ impl InnerDevice {
                         size: 0,
                     },
                     format: surface_format.format,
+                    extent: vk::Extent3D {
+                        width: extent.width,
+                        height: extent.height,
+                        depth: 1,
+                    },
+                    mip_levels: 1,
+                    array_layers: 1,
+                    cube_compatible: false,
                 });
 
                 ImageID { id: id }
@@ -134,28 +235,62 @@ impl InnerDevice {
 
         let image_views: Vec<ImageViewID> = image_ids.iter().map(|&image_id| self.create_image_view(image_id, &ImageViewDescription::default())).collect();
 
-        return (swapchain_loader, swapchain, image_ids, image_views);
+        return (swapchain_loader, swapchain, image_ids, image_views, surface_format.format, extent);
     }
 }
 
 // Buffer //
 impl InnerDevice {
     pub(crate) fn create_buffer(&self, buffer_desc: &BufferDescription) -> BufferID {
+        let (buffer, allocation) = self.allocate_buffer(buffer_desc, buffer_desc.memory_type).expect("Failed to create buffer");
+
+        return self.add_buffer_slot(buffer, allocation, buffer_desc.size);
+    }
+
+    /// Retries in host memory if the device runs out of memory, returns where the buffer ended up
+    pub(crate) fn create_buffer_with_fallback(&self, buffer_desc: &BufferDescription) -> (BufferID, MemoryType) {
+        // On UMA devices host memory is device local as well, so the memory type comes from the path taken
+        let (buffer, allocation, memory_type) = match self.allocate_buffer(buffer_desc, buffer_desc.memory_type) {
+            Ok((buffer, allocation)) => (buffer, allocation, buffer_desc.memory_type),
+            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => {
+                eprintln!("Out of device memory, falling back to host memory for buffer of size {}", buffer_desc.size);
+                let (buffer, allocation) = self.allocate_buffer(buffer_desc, MemoryType::PreferHost).expect("Failed to create buffer");
+                (buffer, allocation, MemoryType::PreferHost)
+            }
+            Err(e) => panic!("Failed to create buffer: {:?}", e),
+        };
+
+        return (self.add_buffer_slot(buffer, allocation, buffer_desc.size), memory_type);
+    }
+
+    fn allocate_buffer(&self, buffer_desc: &BufferDescription, memory_type: MemoryType) -> VkResult<(vk::Buffer, Allocation)> {
         let buffer_create_info = vk::BufferCreateInfo::default()
             .usage(buffer_desc.usage.to_vk_flag() | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS)
             .size(buffer_desc.size);
 
         let mut allocation_create_info = vk_mem::AllocationCreateInfo {
-            usage: buffer_desc.memory_type.to_vk_flag(),
+            usage: memory_type.to_vk_flag(),
             ..Default::default()
         };
 
         if buffer_desc.create_mapped {
-            allocation_create_info.flags = AllocationCreateFlags::MAPPED | AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE;
+            allocation_create_info.flags |= AllocationCreateFlags::MAPPED | buffer_desc.host_access.to_vk_flag();
+        } else if memory_type == MemoryType::PreferHost {
+            // Not mapped up front, but has to stay host visible so map_buffer can map it later
+            allocation_create_info.flags |= buffer_desc.host_access.to_vk_flag();
         }
 
-        let (buffer, allocation) = unsafe { self.allocator.create_buffer(&buffer_create_info, &allocation_create_info).expect("Failed to create buffer") };
+        if buffer_desc.dedicated {
+            allocation_create_info.flags |= AllocationCreateFlags::DEDICATED_MEMORY;
+        }
 
+        return match buffer_desc.min_alignment {
+            Some(alignment) => unsafe { self.allocator.create_buffer_with_alignment(&buffer_create_info, &allocation_create_info, alignment) },
+            None => unsafe { self.allocator.create_buffer(&buffer_create_info, &allocation_create_info) },
+        };
+    }
+
+    fn add_buffer_slot(&self, buffer: vk::Buffer, allocation: Allocation, size: vk::DeviceSize) -> BufferID {
         let alloc_info = self.allocator.get_allocation_info(&allocation);
 
         let buffer_address = unsafe { self.handle.get_buffer_device_address(&vk::BufferDeviceAddressInfo::default().buffer(buffer)) };
@@ -165,6 +300,8 @@ impl InnerDevice {
             address: buffer_address,
             allocation: allocation,
             alloc_info: alloc_info,
+            size: size,
+            map_count: 0,
         });
 
         return BufferID { id: id };
@@ -174,25 +311,201 @@ impl InnerDevice {
         let mut res = self.buffer_pool.write().unwrap().delete(id.id);
 
         unsafe {
+            if res.map_count > 0 {
+                self.allocator.unmap_memory(&mut res.allocation);
+            }
             self.allocator.destroy_buffer(res.handle, &mut res.allocation);
         }
     }
 
     pub(crate) fn write_data_to_buffer<T: Copy>(&self, buffer_id: BufferID, data: &[T]) {
         let buffer_pool = self.buffer_pool.read().unwrap();
-        let buffer = buffer_pool.get_ref(buffer_id.id);
+        let buffer = buffer_pool
+            .try_get_ref(buffer_id.id)
+            .unwrap_or_else(|| panic!("Tried writing data to a buffer that was destroyed or never created (id: {})", buffer_id.id));
+
+        assert!(
+            !buffer.alloc_info.mapped_data.is_null(),
+            "Tried writing data to a buffer that was not created with create_mapped (id: {})",
+            buffer_id.id
+        );
+
+        let data_size = size_of_val(data) as u64;
+        assert!(
+            data_size <= buffer.alloc_info.size,
+            "Tried writing {} bytes ({} elements of {} bytes) to a buffer of {} bytes (id: {})",
+            data_size,
+            data.len(),
+            size_of::<T>(),
+            buffer.alloc_info.size,
+            buffer_id.id
+        );
 
         unsafe {
             let ptr = buffer.alloc_info.mapped_data as *mut T;
             std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
         }
+
+        // Writes to non coherent memory are not visible to the device until flushed
+        let memory_properties = self.allocator.get_allocation_memory_properties(&buffer.allocation);
+        if !memory_properties.contains(vk::MemoryPropertyFlags::HOST_COHERENT) {
+            self.allocator.flush_allocation(&buffer.allocation, 0, data_size).expect("Failed to flush buffer memory");
+        }
+    }
+
+    pub(crate) fn flush_buffer(&self, buffer_id: BufferID, offset: u64, size: u64) {
+        let buffer_pool = self.buffer_pool.read().unwrap();
+        let buffer = buffer_pool
+            .try_get_ref(buffer_id.id)
+            .unwrap_or_else(|| panic!("Tried flushing a buffer that was destroyed or never created (id: {})", buffer_id.id));
+
+        self.allocator.flush_allocation(&buffer.allocation, offset, size).expect("Failed to flush buffer memory");
+    }
+
+    pub(crate) fn invalidate_buffer(&self, buffer_id: BufferID, offset: u64, size: u64) {
+        let buffer_pool = self.buffer_pool.read().unwrap();
+        let buffer = buffer_pool
+            .try_get_ref(buffer_id.id)
+            .unwrap_or_else(|| panic!("Tried invalidating a buffer that was destroyed or never created (id: {})", buffer_id.id));
+
+        self.allocator.invalidate_allocation(&buffer.allocation, offset, size).expect("Failed to invalidate buffer memory");
+    }
+
+    pub(crate) fn mapped_ptr(&self, buffer_id: BufferID) -> Option<*mut u8> {
+        let buffer_pool = self.buffer_pool.read().unwrap();
+        let buffer = buffer_pool
+            .try_get_ref(buffer_id.id)
+            .unwrap_or_else(|| panic!("Tried mapping a buffer that was destroyed or never created (id: {})", buffer_id.id));
+
+        let ptr = buffer.alloc_info.mapped_data as *mut u8;
+
+        return if ptr.is_null() { None } else { Some(ptr) };
+    }
+
+    pub(crate) fn map_buffer(&self, buffer_id: BufferID) -> *mut u8 {
+        let mut buffer_pool = self.buffer_pool.write().unwrap();
+        let buffer = buffer_pool
+            .try_get_mut(buffer_id.id)
+            .unwrap_or_else(|| panic!("Tried mapping a buffer that was destroyed or never created (id: {})", buffer_id.id));
+
+        assert!(buffer.map_count == 0, "Buffer is already mapped, unmap it before mapping again (id: {})", buffer_id.id);
+
+        let memory_properties = self.allocator.get_allocation_memory_properties(&buffer.allocation);
+        assert!(
+            memory_properties.contains(vk::MemoryPropertyFlags::HOST_VISIBLE),
+            "Tried mapping a buffer that is not host visible, create it with MemoryType::PreferHost (id: {})",
+            buffer_id.id
+        );
+
+        let ptr = unsafe { self.allocator.map_memory(&mut buffer.allocation).expect("Failed to map buffer memory") };
+        buffer.map_count += 1;
+
+        return ptr;
+    }
+
+    pub(crate) fn unmap_buffer(&self, buffer_id: BufferID) {
+        let mut buffer_pool = self.buffer_pool.write().unwrap();
+        let buffer = buffer_pool
+            .try_get_mut(buffer_id.id)
+            .unwrap_or_else(|| panic!("Tried unmapping a buffer that was destroyed or never created (id: {})", buffer_id.id));
+
+        assert!(buffer.map_count > 0, "Tried unmapping a buffer that is not mapped (id: {})", buffer_id.id);
+
+        unsafe {
+            self.allocator.unmap_memory(&mut buffer.allocation);
+        }
+        buffer.map_count -= 1;
+    }
+
+    pub(crate) fn buffer_device_address(&self, buffer_id: BufferID) -> u64 {
+        let buffer_pool = self.buffer_pool.read().unwrap();
+        let buffer = buffer_pool
+            .try_get_ref(buffer_id.id)
+            .unwrap_or_else(|| panic!("Tried getting the address of a buffer that was destroyed or never created (id: {})", buffer_id.id));
+
+        return buffer.address;
+    }
+}
+
+// Deferred destruction //
+impl InnerDevice {
+    pub(crate) fn destroy_buffer_deferred(&self, id: BufferID, frames: u32) {
+        let frame = self.frame_counter.load(Ordering::Acquire) + frames as u64;
+        self.deferred_destroys.lock().unwrap().push((frame, DeferredResource::Buffer(id)));
+    }
+
+    pub(crate) fn destroy_image_deferred(&self, id: ImageID, frames: u32) {
+        let frame = self.frame_counter.load(Ordering::Acquire) + frames as u64;
+        self.deferred_destroys.lock().unwrap().push((frame, DeferredResource::Image(id)));
+    }
+
+    pub(crate) fn advance_frame(&self) {
+        let frame = self.frame_counter.fetch_add(1, Ordering::AcqRel) + 1;
+
+        let ready: Vec<DeferredResource> = {
+            let mut deferred = self.deferred_destroys.lock().unwrap();
+            let (ready, waiting) = std::mem::take(&mut *deferred).into_iter().partition(|(f, _)| *f <= frame);
+            *deferred = waiting;
+            ready.into_iter().map(|(_, resource)| resource).collect()
+        };
+
+        for resource in ready {
+            self.destroy_deferred_resource(resource);
+        }
+    }
+
+    fn destroy_deferred_resource(&self, resource: DeferredResource) {
+        match resource {
+            DeferredResource::Buffer(id) => self.destroy_buffer(id),
+            DeferredResource::Image(id) => self.destroy_image(id),
+        }
     }
 }
 
 // Image //
 impl InnerDevice {
     pub(crate) fn create_image(&self, image_desc: &ImageDescription) -> ImageID {
+        let (image, allocation) = self.allocate_image(image_desc, image_desc.memory_type).expect("Failed to create image");
+
+        return self.add_image_slot(image, allocation, image_desc);
+    }
+
+    /// Retries in host memory if the device runs out of memory, returns where the image ended up
+    pub(crate) fn create_image_with_fallback(&self, image_desc: &ImageDescription) -> (ImageID, MemoryType) {
+        let (image, allocation, memory_type) = match self.allocate_image(image_desc, image_desc.memory_type) {
+            Ok((image, allocation)) => (image, allocation, image_desc.memory_type),
+            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => {
+                eprintln!(
+                    "Out of device memory, falling back to host memory for {}x{}x{} image",
+                    image_desc.width, image_desc.height, image_desc.depth
+                );
+                let (image, allocation) = self.allocate_image(image_desc, MemoryType::PreferHost).expect("Failed to create image");
+                (image, allocation, MemoryType::PreferHost)
+            }
+            Err(e) => panic!("Failed to create image: {:?}", e),
+        };
+
+        return (self.add_image_slot(image, allocation, image_desc), memory_type);
+    }
+
+    fn allocate_image(&self, image_desc: &ImageDescription, memory_type: MemoryType) -> VkResult<(vk::Image, Allocation)> {
+        if let Some(usage) = self.format_properties(image_desc.format).unsupported_usage(image_desc.usage) {
+            panic!(
+                "Format {:?} does not support {} usage on this device, check Device::format_properties to pick a fallback",
+                image_desc.format, usage
+            );
+        }
+
+        InnerDevice::validate_cube_compatible(image_desc);
+
+        let flags = if image_desc.cube_compatible {
+            vk::ImageCreateFlags::CUBE_COMPATIBLE
+        } else {
+            vk::ImageCreateFlags::empty()
+        };
+
         let image_create_info = vk::ImageCreateInfo::default()
+            .flags(flags)
             .usage(image_desc.usage.to_vk_flag())
             .extent(vk::Extent3D {
                 height: image_desc.height,
@@ -207,13 +520,28 @@ impl InnerDevice {
             .samples(image_desc.samples.to_vk_flags())
             .tiling(vk::ImageTiling::OPTIMAL);
 
-        let allocation_create_info = vk_mem::AllocationCreateInfo {
-            usage: image_desc.memory_type.to_vk_flag(),
+        let mut allocation_create_info = vk_mem::AllocationCreateInfo {
+            usage: memory_type.to_vk_flag(),
             ..Default::default()
         };
 
-        let (image, allocation) = unsafe { self.allocator.create_image(&image_create_info, &allocation_create_info).expect("Failed to create image") };
+        if image_desc.dedicated {
+            allocation_create_info.flags |= AllocationCreateFlags::DEDICATED_MEMORY;
+        }
 
+        return unsafe { self.allocator.create_image(&image_create_info, &allocation_create_info) };
+    }
+
+    fn validate_cube_compatible(image_desc: &ImageDescription) {
+        if image_desc.cube_compatible && (!image_desc.array_layers.is_multiple_of(6) || image_desc.width != image_desc.height) {
+            panic!(
+                "Cube compatible images need square faces and a multiple of 6 array layers, got {}x{} with {} layers",
+                image_desc.width, image_desc.height, image_desc.array_layers
+            );
+        }
+    }
+
+    fn add_image_slot(&self, image: vk::Image, allocation: Allocation, image_desc: &ImageDescription) -> ImageID {
         let alloc_info = self.allocator.get_allocation_info(&allocation);
 
         let id = self.image_pool.write().unwrap().add(ImageSlot {
@@ -221,6 +549,14 @@ impl InnerDevice {
             allocation: allocation,
             alloc_info: alloc_info,
             format: image_desc.format.to_vk_format(),
+            extent: vk::Extent3D {
+                width: image_desc.width,
+                height: image_desc.height,
+                depth: image_desc.depth,
+            },
+            mip_levels: image_desc.mip_levels,
+            array_layers: image_desc.array_layers,
+            cube_compatible: image_desc.cube_compatible,
         });
 
         return ImageID { id: id };
@@ -237,9 +573,24 @@ impl InnerDevice {
 
 // Image View //
 impl InnerDevice {
+    fn view_subresource_range(image_view_description: &ImageViewDescription) -> vk::ImageSubresourceRange {
+        return vk::ImageSubresourceRange::default()
+            .aspect_mask(image_view_description.aspect.to_vk_aspect())
+            .base_mip_level(image_view_description.base_mip_level)
+            .level_count(image_view_description.level_count)
+            .base_array_layer(image_view_description.base_array_layer)
+            .layer_count(image_view_description.layer_count);
+    }
+
     pub(crate) fn create_image_view(&self, image_id: ImageID, image_view_description: &ImageViewDescription) -> ImageViewID {
         let pool = self.image_pool.read().unwrap();
-        let img = pool.get_ref(image_id.id);
+        let img = pool
+            .try_get_ref(image_id.id)
+            .unwrap_or_else(|| panic!("Tried creating a view of an image that was destroyed or never created (id: {})", image_id.id));
+
+        if matches!(image_view_description.view_type, ImageViewType::Cube | ImageViewType::CubeArray) && !img.cube_compatible {
+            panic!("Cube views need an image created with ImageDescription::cube_compatible set");
+        }
 
         let image_view_create_info = vk::ImageViewCreateInfo::default()
             .image(img.handle)
@@ -251,20 +602,17 @@ impl InnerDevice {
                 b: vk::ComponentSwizzle::IDENTITY,
                 a: vk::ComponentSwizzle::IDENTITY,
             })
-            .subresource_range(
-                vk::ImageSubresourceRange::default()
-                    .aspect_mask(image_view_description.aspect.to_vk_aspect())
-                    .base_mip_level(image_view_description.base_mip_level)
-                    .level_count(image_view_description.layer_count)
-                    .base_array_layer(image_view_description.base_array_layer)
-                    .layer_count(image_view_description.layer_count),
-            );
+            .subresource_range(InnerDevice::view_subresource_range(image_view_description));
 
         let image_view = unsafe { self.handle.create_image_view(&image_view_create_info, None).expect("Failed to create Image view") };
 
         let id = self.image_view_pool.write().unwrap().add(ImageViewSlot {
             handle: image_view,
             parent_image: img.handle,
+            extent: vk::Extent2D {
+                width: (img.extent.width >> image_view_description.base_mip_level).max(1),
+                height: (img.extent.height >> image_view_description.base_mip_level).max(1),
+            },
         });
 
         return ImageViewID { id: id };
@@ -282,7 +630,24 @@ impl InnerDevice {
 // Sampler //
 impl InnerDevice {
     pub(crate) fn create_sampler(&self, sampler_desc: &SamplerDescription) -> SamplerID {
-        let create_info = vk::SamplerCreateInfo::default()
+        let max_anisotropy = sampler_desc.max_anisotropy.and_then(|requested| {
+            if !self.supports_sampler_anisotropy() {
+                eprintln!("Sampler anisotropy is not supported by this device, creating the sampler without it");
+                return None;
+            }
+
+            let limit = self.physical_device.properties.limits.max_sampler_anisotropy;
+            if requested > limit {
+                eprintln!("Sampler anisotropy {} is above the device limit, clamping it to {}", requested, limit);
+            }
+
+            return Some(requested.min(limit));
+        });
+
+        let mut custom_border_color = vk::SamplerCustomBorderColorCreateInfoEXT::default().format(vk::Format::UNDEFINED);
+        let mut reduction_mode = vk::SamplerReductionModeCreateInfo::default();
+
+        let mut create_info = vk::SamplerCreateInfo::default()
             .mag_filter(sampler_desc.mag_filter.to_vk())
             .min_filter(sampler_desc.min_filter.to_vk())
             .mipmap_mode(sampler_desc.mipmap_mode.to_vk())
@@ -290,8 +655,8 @@ impl InnerDevice {
             .address_mode_v(sampler_desc.address_mode_v.to_vk())
             .address_mode_w(sampler_desc.address_mode_w.to_vk())
             .mip_lod_bias(sampler_desc.mip_lod_bias)
-            .anisotropy_enable(sampler_desc.max_anisotropy.is_some())
-            .max_anisotropy(sampler_desc.max_anisotropy.unwrap_or(1.0))
+            .anisotropy_enable(max_anisotropy.is_some())
+            .max_anisotropy(max_anisotropy.unwrap_or(1.0))
             .compare_enable(sampler_desc.compare_op.is_some())
             .compare_op(sampler_desc.compare_op.map(|c| c.to_vk()).unwrap_or(vk::CompareOp::ALWAYS))
             .min_lod(sampler_desc.min_lod)
@@ -299,6 +664,24 @@ impl InnerDevice {
             .border_color(sampler_desc.border_color.to_vk())
             .unnormalized_coordinates(sampler_desc.unnormalized_coordinates);
 
+        if let BorderColor::Custom(color) = sampler_desc.border_color {
+            if !self.supports_custom_border_color() {
+                panic!("BorderColor::Custom needs VK_EXT_custom_border_color, which this device does not support");
+            }
+
+            custom_border_color = custom_border_color.custom_border_color(vk::ClearColorValue { float32: color });
+            create_info = create_info.push_next(&mut custom_border_color);
+        }
+
+        if let Some(mode) = sampler_desc.reduction_mode {
+            if !matches!(mode, ReductionMode::WeightedAverage) && !self.supports_sampler_reduction() {
+                panic!("ReductionMode::{:?} needs VK_EXT_sampler_filter_minmax, which this device does not support", mode);
+            }
+
+            reduction_mode = reduction_mode.reduction_mode(mode.to_vk());
+            create_info = create_info.push_next(&mut reduction_mode);
+        }
+
         let sampler = unsafe { self.handle.create_sampler(&create_info, None).expect("Failed to create sampler") };
 
         let id = self.sampler_pool.write().unwrap().add(SamplerSlot { handle: sampler });
@@ -306,7 +689,37 @@ impl InnerDevice {
         return SamplerID { id: id };
     }
 
+    pub(crate) fn default_sampler(&self) -> SamplerID {
+        return *self
+            .default_sampler
+            .get_or_init(|| self.create_default_sampler(Filter::Linear, SamplerMipmapMode::Linear, DEFAULT_SAMPLER_INDEX));
+    }
+
+    pub(crate) fn default_nearest_sampler(&self) -> SamplerID {
+        return *self
+            .default_nearest_sampler
+            .get_or_init(|| self.create_default_sampler(Filter::Nearest, SamplerMipmapMode::Nearest, DEFAULT_NEAREST_SAMPLER_INDEX));
+    }
+
+    fn create_default_sampler(&self, filter: Filter, mipmap_mode: SamplerMipmapMode, index: u32) -> SamplerID {
+        let sampler = self.create_sampler(&SamplerDescription {
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_mode: mipmap_mode,
+            ..Default::default()
+        });
+
+        self.write_sampler(&SamplerWriteInfo { sampler: sampler, index: index });
+
+        return sampler;
+    }
+
     pub(crate) fn destroy_sampler(&self, sampler_id: SamplerID) {
+        assert!(
+            self.default_sampler.get() != Some(&sampler_id) && self.default_nearest_sampler.get() != Some(&sampler_id),
+            "Tried destroying a default sampler, it is owned by the device"
+        );
+
         let sampler = self.sampler_pool.write().unwrap().delete(sampler_id.id);
 
         unsafe {
@@ -319,43 +732,597 @@ impl InnerDevice {
 impl InnerDevice {
     pub(crate) fn write_buffer(&self, buffer_write_info: &BufferWriteInfo) {
         let buffer_pool = self.buffer_pool.read().unwrap();
-        let buffer = buffer_pool.get_ref(buffer_write_info.buffer.id);
+        let buffer = buffer_pool
+            .try_get_ref(buffer_write_info.buffer.id)
+            .unwrap_or_else(|| panic!("Tried writing a descriptor for a buffer that was destroyed or never created (id: {})", buffer_write_info.buffer.id));
+
+        self.bindless_descriptors
+            .write_buffer(&self.handle, buffer.handle, buffer_write_info.offset, buffer_write_info.range, buffer_write_info.index);
+    }
+
+    pub(crate) fn write_uniform_buffer(&self, buffer_write_info: &BufferWriteInfo) {
+        let buffer_pool = self.buffer_pool.read().unwrap();
+        let buffer = buffer_pool
+            .try_get_ref(buffer_write_info.buffer.id)
+            .unwrap_or_else(|| panic!("Tried writing a descriptor for a buffer that was destroyed or never created (id: {})", buffer_write_info.buffer.id));
+
+        assert!(
+            buffer_write_info.offset <= buffer.size,
+            "Uniform buffer offset {} is past the end of the {} byte buffer (id: {})",
+            buffer_write_info.offset,
+            buffer.size,
+            buffer_write_info.buffer.id
+        );
+
+        let offset_alignment = self.physical_device.properties.limits.min_uniform_buffer_offset_alignment;
+        assert!(
+            buffer_write_info.offset.is_multiple_of(offset_alignment),
+            "Uniform buffer offset {} is not a multiple of the device alignment of {} bytes (id: {})",
+            buffer_write_info.offset,
+            offset_alignment,
+            buffer_write_info.buffer.id
+        );
+
+        let range = if buffer_write_info.range == vk::WHOLE_SIZE {
+            buffer.size - buffer_write_info.offset
+        } else {
+            buffer_write_info.range
+        };
 
-        self.bindless_descriptors.write_buffer(&self.handle, buffer.handle, buffer_write_info.index);
+        let max_range = self.physical_device.properties.limits.max_uniform_buffer_range;
+        assert!(
+            range <= max_range as u64,
+            "Uniform buffer range of {} bytes is larger than the device limit of {} bytes (id: {})",
+            range,
+            max_range,
+            buffer_write_info.buffer.id
+        );
+
+        self.bindless_descriptors
+            .write_uniform_buffer(&self.handle, buffer.handle, buffer_write_info.offset, range, buffer_write_info.index);
     }
 
     pub(crate) fn write_image(&self, image_write_info: &ImageWriteInfo) {
         let img_view_pool = self.image_view_pool.read().unwrap();
-        let img_view = img_view_pool.get_ref(image_write_info.view.id);
+        let img_view = img_view_pool
+            .try_get_ref(image_write_info.view.id)
+            .unwrap_or_else(|| panic!("Tried writing a descriptor for an image view that was destroyed or never created (id: {})", image_write_info.view.id));
 
         match image_write_info.image_descriptor_type {
-            ImageDescriptorType::SampledImage => self.bindless_descriptors.write_sampled_image(&self.handle, img_view.handle, image_write_info.index),
-            ImageDescriptorType::StorageImage => self.bindless_descriptors.write_storage_image(&self.handle, img_view.handle, image_write_info.index),
+            ImageDescriptorType::SampledImage => {
+                let layout = image_write_info.layout.unwrap_or(ImageLayout::ShaderReadOnly).to_vk_layout();
+                self.bindless_descriptors.write_sampled_image(&self.handle, img_view.handle, layout, image_write_info.index);
+            }
+            ImageDescriptorType::StorageImage => {
+                let layout = image_write_info.layout.unwrap_or(ImageLayout::General).to_vk_layout();
+                assert!(layout == vk::ImageLayout::GENERAL, "Storage images have to be accessed in ImageLayout::General");
+                self.bindless_descriptors.write_storage_image(&self.handle, img_view.handle, layout, image_write_info.index);
+            }
         }
     }
 
     pub(crate) fn write_sampler(&self, sampler_write_info: &SamplerWriteInfo) {
         let sampler_pool = self.sampler_pool.read().unwrap();
-        let sampler = sampler_pool.get_ref(sampler_write_info.sampler.id);
+        let sampler = sampler_pool
+            .try_get_ref(sampler_write_info.sampler.id)
+            .unwrap_or_else(|| panic!("Tried writing a descriptor for a sampler that was destroyed or never created (id: {})", sampler_write_info.sampler.id));
 
         self.bindless_descriptors.write_sampler(&self.handle, sampler.handle, sampler_write_info.index);
     }
+
+    pub(crate) fn register_buffer(&self, buffer_id: BufferID) -> u32 {
+        let index = self.bindless_descriptors.buffer_indices.lock().unwrap().allocate();
+
+        self.write_buffer(&BufferWriteInfo {
+            buffer: buffer_id,
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+            index: index,
+        });
+
+        return index;
+    }
+
+    pub(crate) fn register_uniform_buffer(&self, buffer_id: BufferID) -> u32 {
+        let index = self.bindless_descriptors.uniform_buffer_indices.lock().unwrap().allocate();
+
+        self.write_uniform_buffer(&BufferWriteInfo {
+            buffer: buffer_id,
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+            index: index,
+        });
+
+        return index;
+    }
+
+    pub(crate) fn register_image(&self, image_view_id: ImageViewID, image_descriptor_type: ImageDescriptorType) -> u32 {
+        let index = match image_descriptor_type {
+            ImageDescriptorType::SampledImage => self.bindless_descriptors.sampled_image_indices.lock().unwrap().allocate(),
+            ImageDescriptorType::StorageImage => self.bindless_descriptors.storage_image_indices.lock().unwrap().allocate(),
+        };
+
+        self.write_image(&ImageWriteInfo {
+            view: image_view_id,
+            image_descriptor_type: image_descriptor_type,
+            index: index,
+            layout: None,
+        });
+
+        return index;
+    }
+
+    pub(crate) fn register_sampler(&self, sampler_id: SamplerID) -> u32 {
+        let index = self.bindless_descriptors.sampler_indices.lock().unwrap().allocate();
+
+        self.write_sampler(&SamplerWriteInfo { sampler: sampler_id, index: index });
+
+        return index;
+    }
+
+    // The old descriptor stays written, the index is only handed out again
+    pub(crate) fn unregister_buffer(&self, index: u32) {
+        self.bindless_descriptors.buffer_indices.lock().unwrap().free(index);
+    }
+
+    pub(crate) fn unregister_uniform_buffer(&self, index: u32) {
+        self.bindless_descriptors.uniform_buffer_indices.lock().unwrap().free(index);
+    }
+
+    pub(crate) fn unregister_image(&self, index: u32, image_descriptor_type: ImageDescriptorType) {
+        match image_descriptor_type {
+            ImageDescriptorType::SampledImage => self.bindless_descriptors.sampled_image_indices.lock().unwrap().free(index),
+            ImageDescriptorType::StorageImage => self.bindless_descriptors.storage_image_indices.lock().unwrap().free(index),
+        }
+    }
+
+    pub(crate) fn unregister_sampler(&self, index: u32) {
+        self.bindless_descriptors.sampler_indices.lock().unwrap().free(index);
+    }
+}
+
+// Acceleration Structure //
+impl InnerDevice {
+    fn acceleration_structure_loader(&self) -> &ash::khr::acceleration_structure::Device {
+        match &self.acceleration_structure {
+            Some(loader) => loader,
+            None => panic!("Tried building an acceleration structure without enabling ray tracing"),
+        }
+    }
+
+    pub(crate) fn build_blas(self: &Arc<Self>, blas_desc: &BlasDescription) -> AccelerationStructureID {
+        assert!(!blas_desc.geometries.is_empty(), "Tried building a BLAS without any geometry");
+        assert!(
+            blas_desc.geometries.iter().all(|geometry| geometry.index_buffer.is_none() || geometry.index_type != IndexType::Uint8),
+            "Acceleration structure builds only accept 16 or 32 bit indices"
+        );
+
+        // Per geometry transforms are read from device memory during the build
+        let transforms: Vec<vk::TransformMatrixKHR> = blas_desc
+            .geometries
+            .iter()
+            .map(|geometry| vk::TransformMatrixKHR {
+                matrix: bytemuck::cast(geometry.transform),
+            })
+            .collect();
+
+        let transform_buffer = self.create_buffer(&BufferDescription {
+            usage: BufferUsage::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY,
+            size: (transforms.len() * size_of::<vk::TransformMatrixKHR>()) as u64,
+            memory_type: MemoryType::PreferHost,
+            create_mapped: true,
+            ..Default::default()
+        });
+        self.write_data_to_buffer(transform_buffer, &transforms);
+
+        let geometries: Vec<vk::AccelerationStructureGeometryKHR> = {
+            let buffer_pool = self.buffer_pool.read().unwrap();
+            let transform_address = buffer_pool.get_ref(transform_buffer.id).address;
+
+            blas_desc
+                .geometries
+                .iter()
+                .enumerate()
+                .map(|(i, geometry)| {
+                    let (index_type, index_address) = match geometry.index_buffer {
+                        Some(index_buffer) => (geometry.index_type.to_vk_flag(), buffer_pool.get_ref(index_buffer.id).address + geometry.index_offset),
+                        None => (vk::IndexType::NONE_KHR, 0),
+                    };
+
+                    let triangles = vk::AccelerationStructureGeometryTrianglesDataKHR::default()
+                        .vertex_format(geometry.vertex_format.to_vk_format())
+                        .vertex_data(vk::DeviceOrHostAddressConstKHR {
+                            device_address: buffer_pool.get_ref(geometry.vertex_buffer.id).address + geometry.vertex_offset,
+                        })
+                        .vertex_stride(geometry.vertex_stride)
+                        .max_vertex(geometry.max_vertex)
+                        .index_type(index_type)
+                        .index_data(vk::DeviceOrHostAddressConstKHR { device_address: index_address })
+                        .transform_data(vk::DeviceOrHostAddressConstKHR {
+                            device_address: transform_address + (i * size_of::<vk::TransformMatrixKHR>()) as u64,
+                        });
+
+                    vk::AccelerationStructureGeometryKHR::default()
+                        .geometry_type(vk::GeometryTypeKHR::TRIANGLES)
+                        .geometry(vk::AccelerationStructureGeometryDataKHR { triangles: triangles })
+                        .flags(if geometry.opaque { vk::GeometryFlagsKHR::OPAQUE } else { vk::GeometryFlagsKHR::empty() })
+                })
+                .collect()
+        };
+
+        let ranges: Vec<vk::AccelerationStructureBuildRangeInfoKHR> = blas_desc
+            .geometries
+            .iter()
+            .map(|geometry| vk::AccelerationStructureBuildRangeInfoKHR::default().primitive_count(geometry.triangle_count))
+            .collect();
+
+        let id = self.build_acceleration_structure(vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL, blas_desc.build_preference.to_vk(), &geometries, &ranges);
+
+        self.destroy_buffer(transform_buffer);
+
+        return id;
+    }
+
+    pub(crate) fn build_tlas(self: &Arc<Self>, tlas_desc: &TlasDescription) -> AccelerationStructureID {
+        let instances: Vec<vk::AccelerationStructureInstanceKHR> = {
+            let as_pool = self.acceleration_structure_pool.read().unwrap();
+
+            tlas_desc
+                .instances
+                .iter()
+                .map(|instance| {
+                    let mut flags = vk::GeometryInstanceFlagsKHR::empty();
+                    if instance.disable_culling {
+                        flags |= vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE;
+                    }
+                    if instance.force_opaque {
+                        flags |= vk::GeometryInstanceFlagsKHR::FORCE_OPAQUE;
+                    }
+
+                    vk::AccelerationStructureInstanceKHR {
+                        transform: vk::TransformMatrixKHR {
+                            matrix: bytemuck::cast(instance.transform),
+                        },
+                        instance_custom_index_and_mask: vk::Packed24_8::new(instance.custom_index, instance.mask),
+                        instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(instance.sbt_record_offset, flags.as_raw() as u8),
+                        acceleration_structure_reference: vk::AccelerationStructureReferenceKHR {
+                            device_handle: as_pool.get_ref(instance.blas.id).address,
+                        },
+                    }
+                })
+                .collect()
+        };
+
+        let instance_buffer = self.create_buffer(&BufferDescription {
+            usage: BufferUsage::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY,
+            size: (instances.len().max(1) * size_of::<vk::AccelerationStructureInstanceKHR>()) as u64,
+            memory_type: MemoryType::PreferHost,
+            create_mapped: true,
+            ..Default::default()
+        });
+        self.write_data_to_buffer(instance_buffer, &instances);
+
+        let instance_address = self.buffer_pool.read().unwrap().get_ref(instance_buffer.id).address;
+
+        let geometry = vk::AccelerationStructureGeometryKHR::default()
+            .geometry_type(vk::GeometryTypeKHR::INSTANCES)
+            .geometry(vk::AccelerationStructureGeometryDataKHR {
+                instances: vk::AccelerationStructureGeometryInstancesDataKHR::default()
+                    .array_of_pointers(false)
+                    .data(vk::DeviceOrHostAddressConstKHR { device_address: instance_address }),
+            });
+
+        let range = vk::AccelerationStructureBuildRangeInfoKHR::default().primitive_count(instances.len() as u32);
+
+        let id = self.build_acceleration_structure(vk::AccelerationStructureTypeKHR::TOP_LEVEL, tlas_desc.build_preference.to_vk(), &[geometry], &[range]);
+
+        self.destroy_buffer(instance_buffer);
+
+        return id;
+    }
+
+    pub(crate) fn destroy_acceleration_structure(&self, id: AccelerationStructureID) {
+        let slot = self.acceleration_structure_pool.write().unwrap().delete(id.id);
+
+        unsafe {
+            self.acceleration_structure_loader().destroy_acceleration_structure(slot.handle, None);
+        }
+
+        self.destroy_buffer(slot.buffer);
+    }
+
+    // Builds on the graphics queue and waits for completion
+    fn build_acceleration_structure(
+        self: &Arc<Self>,
+        ty: vk::AccelerationStructureTypeKHR,
+        flags: vk::BuildAccelerationStructureFlagsKHR,
+        geometries: &[vk::AccelerationStructureGeometryKHR],
+        ranges: &[vk::AccelerationStructureBuildRangeInfoKHR],
+    ) -> AccelerationStructureID {
+        let loader = self.acceleration_structure_loader();
+
+        let primitive_counts: Vec<u32> = ranges.iter().map(|range| range.primitive_count).collect();
+
+        let mut build_info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
+            .ty(ty)
+            .flags(flags)
+            .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+            .geometries(geometries);
+
+        let mut size_info = vk::AccelerationStructureBuildSizesInfoKHR::default();
+        unsafe {
+            loader.get_acceleration_structure_build_sizes(vk::AccelerationStructureBuildTypeKHR::DEVICE, &build_info, &primitive_counts, &mut size_info);
+        }
+
+        let buffer = self.create_buffer(&BufferDescription {
+            usage: BufferUsage::ACCELERATION_STRUCTURE_STORAGE,
+            size: size_info.acceleration_structure_size,
+            memory_type: MemoryType::DeviceLocal,
+            create_mapped: false,
+            ..Default::default()
+        });
+
+        let handle = {
+            let buffer_pool = self.buffer_pool.read().unwrap();
+
+            let create_info = vk::AccelerationStructureCreateInfoKHR::default()
+                .buffer(buffer_pool.get_ref(buffer.id).handle)
+                .size(size_info.acceleration_structure_size)
+                .ty(ty);
+
+            unsafe { loader.create_acceleration_structure(&create_info, None).expect("Failed to create acceleration structure") }
+        };
+
+        // Scratch memory has its own alignment requirement
+        let scratch_alignment = {
+            let mut as_props = vk::PhysicalDeviceAccelerationStructurePropertiesKHR::default();
+            let mut props = vk::PhysicalDeviceProperties2::default().push_next(&mut as_props);
+            unsafe {
+                self.instance.handle.get_physical_device_properties2(self.physical_device.handle, &mut props);
+            }
+
+            (as_props.min_acceleration_structure_scratch_offset_alignment as u64).max(1)
+        };
+
+        let scratch_buffer = self.create_buffer(&BufferDescription {
+            usage: BufferUsage::STORAGE,
+            size: size_info.build_scratch_size,
+            memory_type: MemoryType::DeviceLocal,
+            create_mapped: false,
+            min_alignment: Some(scratch_alignment),
+            ..Default::default()
+        });
+
+        let scratch_address = self.buffer_pool.read().unwrap().get_ref(scratch_buffer.id).address;
+
+        build_info = build_info
+            .dst_acceleration_structure(handle)
+            .scratch_data(vk::DeviceOrHostAddressKHR { device_address: scratch_address });
+
+        self.immediate_submit(QueueType::Graphics, |recorder| unsafe {
+            loader.cmd_build_acceleration_structures(recorder.current_commad_buffer, &[build_info], &[ranges]);
+        });
+        self.destroy_buffer(scratch_buffer);
+
+        let address = unsafe { loader.get_acceleration_structure_device_address(&vk::AccelerationStructureDeviceAddressInfoKHR::default().acceleration_structure(handle)) };
+
+        let id = self.acceleration_structure_pool.write().unwrap().add(AccelerationStructureSlot {
+            handle: handle,
+            buffer: buffer,
+            address: address,
+        });
+
+        return AccelerationStructureID { id: id };
+    }
+}
+
+//// Device properties ////
+impl InnerDevice {
+    pub(crate) fn copy_alignments(&self) -> CopyAlignments {
+        let limits = &self.physical_device.properties.limits;
+
+        return CopyAlignments {
+            non_coherent_atom_size: limits.non_coherent_atom_size,
+            optimal_buffer_copy_offset_alignment: limits.optimal_buffer_copy_offset_alignment,
+            optimal_buffer_copy_row_pitch_alignment: limits.optimal_buffer_copy_row_pitch_alignment,
+        };
+    }
+
+    pub(crate) fn limits(&self) -> DeviceLimits {
+        return DeviceLimits::from_vk(&self.physical_device.properties.limits);
+    }
+
+    pub(crate) fn format_properties(&self, format: Format) -> FormatSupport {
+        let properties = unsafe { self.instance.handle.get_physical_device_format_properties(self.physical_device.handle, format.to_vk_format()) };
+
+        return FormatSupport::from_vk(&properties);
+    }
+
+    pub(crate) fn device_name(&self) -> String {
+        return self.physical_device.properties.device_name_as_c_str().unwrap_or_default().to_string_lossy().into_owned();
+    }
+
+    pub(crate) fn memory_stats(&self) -> MemoryStats {
+        let memory_properties = unsafe { self.instance.handle.get_physical_device_memory_properties(self.physical_device.handle) };
+        let stats = self.allocator.calculate_statistics().expect("Failed to calculate memory statistics");
+
+        return MemoryStats {
+            heaps: stats.memoryHeap[..memory_properties.memory_heap_count as usize]
+                .iter()
+                .map(|heap| HeapStats::from_vma(&heap.statistics))
+                .collect(),
+            total: HeapStats::from_vma(&stats.total.statistics),
+        };
+    }
+
+    pub(crate) fn memory_budget(&self) -> Vec<HeapBudget> {
+        let memory_properties = unsafe { self.instance.handle.get_physical_device_memory_properties(self.physical_device.handle) };
+        let budgets = self.allocator.get_heap_budgets().expect("Failed to get heap budgets");
+
+        return budgets
+            .iter()
+            .zip(memory_properties.memory_heaps_as_slice())
+            .map(|(budget, heap)| HeapBudget {
+                device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+                size: heap.size,
+                stats: HeapStats::from_vma(&budget.statistics),
+                usage: budget.usage,
+                budget: budget.budget,
+            })
+            .collect();
+    }
+
+    pub(crate) fn has_separate_present_queue(&self) -> bool {
+        let families = &self.physical_device.queue_families;
+        return families.presetation_family.is_some() && families.graphics_family != families.presetation_family;
+    }
+
+    pub(crate) fn supports_dynamic_polygon_mode(&self) -> bool {
+        return self.extended_dynamic_state3.is_some();
+    }
+
+    pub(crate) fn supports_custom_border_color(&self) -> bool {
+        return self.physical_device.custom_border_color;
+    }
+
+    pub(crate) fn supports_sampler_anisotropy(&self) -> bool {
+        return self.physical_device.features.sampler_anisotropy == vk::TRUE;
+    }
+
+    pub(crate) fn supports_sampler_reduction(&self) -> bool {
+        return self.physical_device.sampler_filter_minmax;
+    }
+
+    pub(crate) fn supports_index_type_uint8(&self) -> bool {
+        return self.physical_device.index_type_uint8;
+    }
+
+    pub(crate) fn supports_push_descriptors(&self) -> bool {
+        return self.push_descriptor.is_some();
+    }
+
+    pub(crate) fn supports_ray_tracing(&self) -> bool {
+        return self.rt.is_some();
+    }
+
+    pub(crate) fn supports_mesh_shaders(&self) -> bool {
+        return self.mesh_shader.is_some();
+    }
+
+    // MAX_PUSH_DESCRIPTOR_BUFFERS uniform buffers, visible to every stage
+    pub(crate) fn create_push_descriptor_layout(device: &ash::Device) -> vk::DescriptorSetLayout {
+        let bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..MAX_PUSH_DESCRIPTOR_BUFFERS)
+            .map(|binding| {
+                vk::DescriptorSetLayoutBinding::default()
+                    .binding(binding)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::ALL)
+            })
+            .collect();
+
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::default()
+            .flags(vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR)
+            .bindings(&bindings);
+
+        return unsafe { device.create_descriptor_set_layout(&layout_info, None).expect("Failed to create push descriptor set layout") };
+    }
 }
 
 //// Command buffers ////
 impl InnerDevice {
-    pub(crate) fn createcmd_recorder_data(&self, queue_type: QueueType) -> vk::CommandPool {
-        let cmd_pool_info = vk::CommandPoolCreateInfo::default().flags(vk::CommandPoolCreateFlags::empty()).queue_family_index(match queue_type {
+    pub(crate) fn queue_family_index(&self, queue_type: QueueType) -> u32 {
+        return match queue_type {
             QueueType::Compute => self.physical_device.queue_families.compute_family.unwrap(),
             QueueType::Transfer => self.physical_device.queue_families.transfer_family.unwrap(),
             QueueType::Graphics => self.physical_device.queue_families.graphics_family.unwrap(),
-            QueueType::None => panic!("Please dont pass a None queue for command pool"),
-        });
+            QueueType::None => vk::QUEUE_FAMILY_IGNORED,
+        };
+    }
+
+    // Queue families for a barrier, ignored unless ownership actually moves to another family
+    pub(crate) fn barrier_queue_families(&self, src_queue: QueueType, dst_queue: QueueType) -> (u32, u32) {
+        if src_queue == QueueType::None || dst_queue == QueueType::None {
+            return (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED);
+        }
+
+        let src_family = self.queue_family_index(src_queue);
+        let dst_family = self.queue_family_index(dst_queue);
+
+        if src_family == dst_family {
+            return (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED);
+        }
+
+        return (src_family, dst_family);
+    }
+
+    pub(crate) fn createcmd_recorder_data(&self, queue_type: QueueType, flags: CommandPoolFlags) -> vk::CommandPool {
+        if queue_type == QueueType::None {
+            panic!("Please dont pass a None queue for command pool");
+        }
+
+        let cmd_pool_info = vk::CommandPoolCreateInfo::default().flags(flags.flags).queue_family_index(self.queue_family_index(queue_type));
 
         let pool = unsafe { self.handle.create_command_pool(&cmd_pool_info, None).expect("Failed to create command pool") };
 
         return pool;
     }
+
+    /// Records with a cached recorder, submits and blocks until the work is done
+    pub(crate) fn immediate_submit<F: FnOnce(&mut CommandRecorder)>(self: &Arc<Self>, queue_type: QueueType, f: F) {
+        let slot = match queue_type {
+            QueueType::Graphics => 0,
+            QueueType::Transfer => 1,
+            QueueType::Compute => 2,
+            QueueType::None => panic!("Please dont pass a None queue for immediate submit"),
+        };
+
+        // Not held while the closure records, so it can call immediate_submit again on the same queue
+        let taken = self.immediate_command_data[slot].lock().unwrap().take();
+        // Every buffer is recorded once and reset right after the wait, so the pool is transient
+        let cached = taken.unwrap_or_else(|| ImmediateCommandData {
+            pool: self.createcmd_recorder_data(queue_type, CommandPoolFlags::TRANSIENT),
+            command_buffers: SmallVec::new(),
+            fence: self.create_fence(false),
+        });
+
+        let mut recorder = CommandRecorder::from_raw_parts(self.clone(), queue_type, cached.pool, CommandPoolFlags::TRANSIENT, cached.command_buffers);
+        recorder.begin_recording(CommandBufferUsage::OneTimeSubmit);
+        f(&mut recorder);
+        let cmd = recorder.end_recording();
+
+        let fence = Fence { handle: cached.fence };
+
+        // Held from the submit on, so immediate submits on one queue are still serialized
+        let mut data = self.immediate_command_data[slot].lock().unwrap();
+
+        self.submit(&QueueSubmitInfo {
+            fence: Some(fence),
+            command_buffers: vec![cmd],
+            wait_semaphores: vec![],
+            signal_semaphores: vec![],
+        });
+        self.wait_fence(fence);
+        self.reset_fence(fence);
+
+        recorder.reset();
+        let (pool, command_buffers) = recorder.into_raw_parts();
+
+        // A nested or concurrent submit already cached its own data, only one is kept
+        if data.is_some() {
+            unsafe {
+                self.handle.destroy_command_pool(pool, None);
+                self.handle.destroy_fence(cached.fence, None);
+            }
+            return;
+        }
+
+        *data = Some(ImmediateCommandData {
+            pool: pool,
+            command_buffers: command_buffers,
+            fence: cached.fence,
+        });
+    }
 }
 
 //// Sync ////
@@ -366,6 +1333,13 @@ impl InnerDevice {
         return unsafe { self.handle.create_fence(&create_info, None).expect("Failed to create Fence") };
     }
 
+    pub(crate) fn create_event(&self) -> vk::Event {
+        // Only set and waited on by command buffers, never from the host
+        let create_info = vk::EventCreateInfo::default().flags(vk::EventCreateFlags::DEVICE_ONLY);
+
+        return unsafe { self.handle.create_event(&create_info, None).expect("Failed to create event") };
+    }
+
     pub(crate) fn create_binary_semaphore(&self) -> vk::Semaphore {
         let create_info = vk::SemaphoreCreateInfo::default().flags(vk::SemaphoreCreateFlags::empty());
 
@@ -386,6 +1360,12 @@ impl InnerDevice {
         }
     }
 
+    pub(crate) fn destroy_event(&self, event: Event) {
+        unsafe {
+            self.handle.destroy_event(event.handle, None);
+        }
+    }
+
     pub(crate) fn destroy_semaphore(&self, semaphore: Semaphore) {
         unsafe {
             self.handle.destroy_semaphore(semaphore.handle(), None);
@@ -394,10 +1374,23 @@ impl InnerDevice {
 
     pub(crate) fn wait_fence(&self, fence: Fence) {
         unsafe {
-            self.handle.wait_for_fences(&[fence.handle], true, u64::MAX).expect("Failed to wait for fence");
+            self.handle
+                .wait_for_fences(&[fence.handle], true, u64::MAX)
+                .map_err(|e| self.check_device_lost(e))
+                .expect("Failed to wait for fence");
         }
     }
 
+    pub(crate) fn wait_fences(&self, fences: &[Fence], wait_all: bool, timeout_ns: u64) -> bool {
+        let handles: Vec<vk::Fence> = fences.iter().map(|f| f.handle).collect();
+
+        return match unsafe { self.handle.wait_for_fences(&handles, wait_all, timeout_ns) } {
+            Ok(()) => true,
+            Err(vk::Result::TIMEOUT) => false,
+            Err(e) => panic!("Failed to wait for fences: {:?}", self.check_device_lost(e)),
+        };
+    }
+
     pub(crate) fn reset_fence(&self, fence: Fence) {
         unsafe {
             self.handle.reset_fences(&[fence.handle]).expect("Failed to reset fence");
@@ -405,10 +1398,129 @@ impl InnerDevice {
     }
 }
 
+//// Queries ////
+impl InnerDevice {
+    pub(crate) fn create_query_pool(&self, query_type: QueryType, count: u32) -> QueryPool {
+        let mut create_info = vk::QueryPoolCreateInfo::default().query_type(query_type.to_vk()).query_count(count);
+
+        if let QueryType::PipelineStatistics(statistics) = query_type {
+            if self.physical_device.features.pipeline_statistics_query != vk::TRUE {
+                panic!("Pipeline statistics queries are not supported by this device");
+            }
+
+            create_info = create_info.pipeline_statistics(statistics.flags);
+        }
+
+        let handle = unsafe { self.handle.create_query_pool(&create_info, None).expect("Failed to create query pool") };
+
+        return QueryPool {
+            handle: handle,
+            query_type: query_type,
+            count: count,
+        };
+    }
+
+    pub(crate) fn destroy_query_pool(&self, query_pool: QueryPool) {
+        unsafe {
+            self.handle.destroy_query_pool(query_pool.handle, None);
+        }
+    }
+
+    pub(crate) fn get_query_results(&self, query_pool: QueryPool, first_query: u32, query_count: u32) -> Vec<u64> {
+        assert!(
+            first_query + query_count <= query_pool.count,
+            "Reading queries {}..{} from a pool of {} queries",
+            first_query,
+            first_query + query_count,
+            query_pool.count
+        );
+
+        let values_per_query = query_pool.query_type.values_per_query();
+        let mut results = vec![0u64; (query_count * values_per_query) as usize];
+
+        // ash's wrapper takes the slice length as the query count, which is wrong with several values per query
+        unsafe {
+            (self.handle.fp_v1_0().get_query_pool_results)(
+                self.handle.handle(),
+                query_pool.handle,
+                first_query,
+                query_count,
+                size_of_val(results.as_slice()),
+                results.as_mut_ptr() as *mut std::ffi::c_void,
+                values_per_query as vk::DeviceSize * 8,
+                vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+            )
+            .result()
+            .map_err(|e| self.check_device_lost(e))
+            .expect("Failed to get query results");
+        }
+
+        return results;
+    }
+}
+
 //// Queue submission ////
 impl InnerDevice {
-    // We need to take an array as an input
     pub(crate) fn submit(&self, submit_info: &QueueSubmitInfo) {
+        self.submit_batches(std::slice::from_ref(submit_info));
+    }
+
+    /// All batches go to the same queue in one queue_submit2 call, at most one of them may carry a fence
+    pub(crate) fn submit_batches(&self, submit_infos: &[QueueSubmitInfo]) {
+        assert!(!submit_infos.is_empty(), "Submitted no batches");
+
+        let batches: Vec<_> = submit_infos.iter().map(InnerDevice::submit_batch_infos).collect();
+
+        let cmd_type = batches[0].3;
+        if let Some(i) = batches.iter().position(|batch| batch.3 != cmd_type) {
+            panic!(
+                "Batch {} targets the {:?} queue but batch 0 targets the {:?} queue, all batches of a submission must target the same queue",
+                i, batches[i].3, cmd_type
+            );
+        }
+
+        let mut fences = submit_infos.iter().filter_map(|submit_info| submit_info.fence);
+        let fence_handle = match fences.next() {
+            Some(f) => f.handle,
+            None => vk::Fence::null(),
+        };
+        assert!(fences.next().is_none(), "Only one batch of a submission can signal a fence");
+
+        let submits: Vec<vk::SubmitInfo2> = batches
+            .iter()
+            .map(|(wait_infos, cmd_infos, signal_infos, _)| {
+                vk::SubmitInfo2::default()
+                    .wait_semaphore_infos(wait_infos.as_slice())
+                    .command_buffer_infos(cmd_infos.as_slice())
+                    .signal_semaphore_infos(signal_infos.as_slice())
+                    .flags(vk::SubmitFlags::empty())
+            })
+            .collect();
+
+        let queue = match cmd_type {
+            QueueType::Graphics => self.graphics_queue,
+            QueueType::Compute => self.compute_queue,
+            QueueType::Transfer => self.transfer_queue,
+            _ => panic!("WHY ARE U PASSING NONE QUEUE"),
+        };
+
+        unsafe {
+            self.handle
+                .queue_submit2(queue, &submits, fence_handle)
+                .map_err(|e| self.check_device_lost(e))
+                .expect("Queue submit failed");
+        }
+    }
+
+    // Wait semaphores, command buffers and signal semaphores of one batch, plus the queue it targets
+    fn submit_batch_infos(
+        submit_info: &QueueSubmitInfo,
+    ) -> (
+        Vec<vk::SemaphoreSubmitInfo<'static>>,
+        Vec<vk::CommandBufferSubmitInfo<'static>>,
+        Vec<vk::SemaphoreSubmitInfo<'static>>,
+        QueueType,
+    ) {
         let signal_infos: Vec<vk::SemaphoreSubmitInfo> = submit_info
             .signal_semaphores
             .iter()
@@ -433,43 +1545,53 @@ impl InnerDevice {
 
         let cmd_type = submit_info.command_buffers[0].queue_type;
 
+        // A suspended rendering must be resumed by the very next command buffer in the submission
+        for (i, cb) in submit_info.command_buffers.iter().enumerate() {
+            assert!(!cb.secondary, "Secondary command buffers must be executed through CommandRecorder::execute_secondary");
+
+            if cb.suspends_rendering {
+                assert!(
+                    submit_info.command_buffers.get(i + 1).is_some_and(|next| next.resumes_rendering),
+                    "Command buffer {} suspends a rendering which is not resumed by the next command buffer in the submission",
+                    i
+                );
+            }
+
+            if cb.resumes_rendering {
+                assert!(
+                    i > 0 && submit_info.command_buffers[i - 1].suspends_rendering,
+                    "Command buffer {} resumes a rendering which was not suspended by the previous command buffer in the submission",
+                    i
+                );
+            }
+        }
+
         let cmd_infos: Vec<vk::CommandBufferSubmitInfo> = submit_info
             .command_buffers
             .iter()
-            .map(|cb| {
-                assert!(cb.queue_type == cmd_type);
+            .enumerate()
+            .map(|(i, cb)| {
+                assert!(
+                    cb.queue_type == cmd_type,
+                    "Command buffer {} was recorded for the {:?} queue but command buffer 0 for the {:?} queue, a submission targets a single queue",
+                    i,
+                    cb.queue_type,
+                    cmd_type
+                );
 
                 vk::CommandBufferSubmitInfo::default().command_buffer(cb.handle).device_mask(0)
             })
             .collect();
 
-        let submit = vk::SubmitInfo2::default()
-            .wait_semaphore_infos(wait_infos.as_slice())
-            .command_buffer_infos(cmd_infos.as_slice())
-            .signal_semaphore_infos(signal_infos.as_slice())
-            .flags(vk::SubmitFlags::empty());
-
-        let fence_handle = match &submit_info.fence {
-            Some(f) => f.handle,
-            None => vk::Fence::null(),
-        };
-
-        let queue = match cmd_type {
-            QueueType::Graphics => self.graphics_queue,
-            QueueType::Compute => self.compute_queue,
-            QueueType::Transfer => self.transfer_queue,
-            _ => panic!("WHY ARE U PASSING NONE QUEUE"),
-        };
-
-        unsafe {
-            self.handle.queue_submit2(queue, &[submit], fence_handle).expect("Queue submit failed");
-        }
+        return (wait_infos, cmd_infos, signal_infos, cmd_type);
     }
 
     pub(crate) fn wait_idle(&self) {
-        unsafe {
-            self.handle.device_wait_idle().expect("Failed to wait device idle");
-        }
+        self.try_wait_idle().expect("Failed to wait device idle");
+    }
+
+    pub(crate) fn try_wait_idle(&self) -> Result<(), DeviceError> {
+        return unsafe { self.handle.device_wait_idle().map_err(|e| DeviceError::from_vk(self.check_device_lost(e))) };
     }
 
     pub(crate) fn wait_queue(&self, queue_type: QueueType) {
@@ -481,18 +1603,112 @@ impl InnerDevice {
         };
 
         unsafe {
-            self.handle.queue_wait_idle(queue).expect("Failed to wait for queue");
+            self.handle.queue_wait_idle(queue).map_err(|e| self.check_device_lost(e)).expect("Failed to wait for queue");
+        }
+    }
+
+    /// Marks the device as lost and notifies `DeviceDescription::on_device_lost` the first time it happens.
+    /// Passes the result through so it can sit in front of the panic
+    pub(crate) fn check_device_lost(&self, result: vk::Result) -> vk::Result {
+        if result != vk::Result::ERROR_DEVICE_LOST || self.device_lost.swap(true, Ordering::AcqRel) {
+            return result;
+        }
+
+        if let Some(callback) = &self.on_device_lost {
+            callback();
         }
+
+        return result;
+    }
+
+    pub(crate) fn is_device_lost(&self) -> bool {
+        return self.device_lost.load(Ordering::Acquire);
     }
 }
 
 impl Drop for InnerDevice {
     fn drop(&mut self) {
+        // A lost device is torn down anyway, panicking here would only hide the original error
+        let _ = self.try_wait_idle();
+
+        // Nothing can still be using them once the device is dropped
+        for (_, resource) in std::mem::take(self.deferred_destroys.get_mut().unwrap()) {
+            self.destroy_deferred_resource(resource);
+        }
+
+        for sampler in [self.default_sampler.take(), self.default_nearest_sampler.take()].into_iter().flatten() {
+            self.destroy_sampler(sampler);
+        }
+
         self.bindless_descriptors.cleanup(&self.handle);
 
+        if self.push_descriptor_layout != vk::DescriptorSetLayout::null() {
+            unsafe {
+                self.handle.destroy_descriptor_set_layout(self.push_descriptor_layout, None);
+            }
+        }
+
+        for data in &mut self.immediate_command_data {
+            if let Some(data) = data.get_mut().unwrap().take() {
+                unsafe {
+                    self.handle.destroy_command_pool(data.pool, None);
+                    self.handle.destroy_fence(data.fence, None);
+                }
+            }
+        }
+
         unsafe {
             std::ptr::drop_in_place(&mut self.allocator);
             self.handle.destroy_device(None);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube_desc(width: u32, height: u32, array_layers: u32) -> ImageDescription {
+        return ImageDescription {
+            width: width,
+            height: height,
+            array_layers: array_layers,
+            cube_compatible: true,
+            ..Default::default()
+        };
+    }
+
+    #[test]
+    fn view_range_uses_the_level_count() {
+        let range = InnerDevice::view_subresource_range(&ImageViewDescription {
+            view_type: ImageViewType::Type3D,
+            base_mip_level: 1,
+            level_count: 4,
+            layer_count: 1,
+            ..Default::default()
+        });
+
+        assert_eq!(range.base_mip_level, 1);
+        assert_eq!(range.level_count, 4);
+        assert_eq!(range.base_array_layer, 0);
+        assert_eq!(range.layer_count, 1);
+    }
+
+    #[test]
+    fn cube_with_square_faces_and_six_layers_is_valid() {
+        InnerDevice::validate_cube_compatible(&cube_desc(64, 64, 6));
+        InnerDevice::validate_cube_compatible(&cube_desc(64, 64, 12));
+    }
+
+    #[test]
+    #[should_panic(expected = "multiple of 6 array layers")]
+    fn cube_with_five_layers_is_rejected() {
+        InnerDevice::validate_cube_compatible(&cube_desc(64, 64, 5));
+    }
+
+    #[test]
+    #[should_panic(expected = "square faces")]
+    fn cube_with_non_square_faces_is_rejected() {
+        InnerDevice::validate_cube_compatible(&cube_desc(64, 32, 6));
+    }
+}
@@ -1,14 +1,19 @@
-use std::u64::MAX;
+use std::sync::Mutex;
 
 use ash::vk;
 use vk_mem::*;
 
+use crate::BufferID;
+
 #[derive(Clone)]
 pub(crate) struct BufferSlot {
     pub(crate) handle: vk::Buffer,
     pub(crate) address: vk::DeviceAddress,
     pub(crate) allocation: Allocation,
     pub(crate) alloc_info: AllocationInfo,
+    pub(crate) size: vk::DeviceSize,
+    // Outstanding map_buffer calls, at most one at a time
+    pub(crate) map_count: u32,
 }
 
 #[derive(Clone)]
@@ -17,12 +22,18 @@ pub(crate) struct ImageSlot {
     pub(crate) allocation: Allocation,
     pub(crate) alloc_info: AllocationInfo,
     pub(crate) format: vk::Format,
+    pub(crate) extent: vk::Extent3D,
+    pub(crate) mip_levels: u32,
+    pub(crate) array_layers: u32,
+    pub(crate) cube_compatible: bool,
 }
 
 #[derive(Clone)]
 pub(crate) struct ImageViewSlot {
     pub(crate) handle: vk::ImageView,
     pub(crate) parent_image: vk::Image,
+    // Size of the viewed mip level, used as the default render area
+    pub(crate) extent: vk::Extent2D,
 }
 
 #[derive(Clone)]
@@ -30,6 +41,13 @@ pub(crate) struct SamplerSlot {
     pub(crate) handle: vk::Sampler,
 }
 
+#[derive(Clone)]
+pub(crate) struct AccelerationStructureSlot {
+    pub(crate) handle: vk::AccelerationStructureKHR,
+    pub(crate) buffer: BufferID,
+    pub(crate) address: vk::DeviceAddress,
+}
+
 const MASK: u64 = 0xFFFF;
 
 fn encode(page: u64, index: u64, version: u64) -> u64 {
@@ -71,6 +89,10 @@ impl<Resource> GpuResourcePool<Resource> {
     pub(crate) fn add(&mut self, res: Resource) -> u64 {
         if self.free_indices.is_empty() {
             if self.curr_index == PAGE_SIZE {
+                if self.curr_page as u64 == MASK {
+                    panic!("GpuResourcePool ran out of ids, more than {} resources are alive", (MASK as usize + 1) * PAGE_SIZE);
+                }
+
                 self.data.push(std::array::from_fn(|_| (None, 0)));
                 self.curr_index = 0;
                 self.curr_page += 1;
@@ -103,7 +125,11 @@ impl<Resource> GpuResourcePool<Resource> {
             Some(res) => {
                 if *res_version == version {
                     self.data[page][index] = (None, version);
-                    self.free_indices.push(id);
+
+                    // A slot whose version is used up is retired, wrapping around would make stale ids valid again
+                    if version < MASK {
+                        self.free_indices.push(id);
+                    }
 
                     return res;
                 } else {
@@ -117,38 +143,107 @@ impl<Resource> GpuResourcePool<Resource> {
     }
 
     pub(crate) fn get_ref(&self, id: u64) -> &Resource {
+        return self.try_get_ref(id).expect("Attempted acess with invalid ID");
+    }
+
+    /// None if the id was destroyed, never created or belongs to an older version of the slot
+    pub(crate) fn try_get_ref(&self, id: u64) -> Option<&Resource> {
         let (page, index, version) = decode_as_usize(id);
 
-        let (res_opt, res_version) = &self.data[page][index];
+        let (res_opt, res_version) = self.data.get(page)?.get(index)?;
 
         match res_opt {
-            Some(res) => {
-                if *res_version == version {
-                    return res;
-                } else {
-                    panic!("Attempted acess with invalid ID")
-                }
-            }
-            None => {
-                panic!("Attempted acess with invalid ID")
-            }
+            Some(res) if *res_version == version => return Some(res),
+            _ => return None,
+        }
+    }
+
+    pub(crate) fn try_get_mut(&mut self, id: u64) -> Option<&mut Resource> {
+        let (page, index, version) = decode_as_usize(id);
+
+        let (res_opt, res_version) = self.data.get_mut(page)?.get_mut(index)?;
+
+        match res_opt {
+            Some(res) if *res_version == version => return Some(res),
+            _ => return None,
         }
     }
 }
 
-/// Provides 4 resource types
+/// Tracks which array elements of a bindless binding are taken.
+/// Manual writes mark their index as used too, so auto assigned indices never clobber them
+pub(crate) struct DescriptorIndexAllocator {
+    used: Vec<bool>,
+    name: &'static str,
+}
+
+impl DescriptorIndexAllocator {
+    pub(crate) fn new(capacity: u32, name: &'static str) -> DescriptorIndexAllocator {
+        return DescriptorIndexAllocator {
+            used: vec![false; capacity as usize],
+            name: name,
+        };
+    }
+
+    pub(crate) fn allocate(&mut self) -> u32 {
+        let index = self
+            .used
+            .iter()
+            .position(|used| !used)
+            .unwrap_or_else(|| panic!("Ran out of bindless {} slots, all {} are in use", self.name, self.used.len()));
+
+        self.used[index] = true;
+
+        return index as u32;
+    }
+
+    pub(crate) fn mark_used(&mut self, index: u32) {
+        assert!(
+            (index as usize) < self.used.len(),
+            "Bindless {} index {} is out of range, only {} slots exist",
+            self.name,
+            index,
+            self.used.len()
+        );
+
+        self.used[index as usize] = true;
+    }
+
+    pub(crate) fn free(&mut self, index: u32) {
+        assert!(
+            (index as usize) < self.used.len(),
+            "Bindless {} index {} is out of range, only {} slots exist",
+            self.name,
+            index,
+            self.used.len()
+        );
+        assert!(self.used[index as usize], "Bindless {} index {} was freed twice", self.name, index);
+
+        self.used[index as usize] = false;
+    }
+}
+
+/// Provides 5 resource types
 /// Storage Buffer        -> binding 0
 /// Sampled Image         -> binding 1
 /// Storage image         -> binding 2
 /// Sampler               -> binding 3
+/// Uniform Buffer        -> binding 4
 pub(crate) struct GpuBindlessDescriptorPool {
     pub(crate) pool: vk::DescriptorPool,
     pub(crate) set: vk::DescriptorSet,
     pub(crate) layout: vk::DescriptorSetLayout,
+
+    // Free array elements, one allocator per binding
+    pub(crate) buffer_indices: Mutex<DescriptorIndexAllocator>,
+    pub(crate) sampled_image_indices: Mutex<DescriptorIndexAllocator>,
+    pub(crate) storage_image_indices: Mutex<DescriptorIndexAllocator>,
+    pub(crate) sampler_indices: Mutex<DescriptorIndexAllocator>,
+    pub(crate) uniform_buffer_indices: Mutex<DescriptorIndexAllocator>,
 }
 
 impl GpuBindlessDescriptorPool {
-    pub(crate) fn new(device: &ash::Device, max_buffers: u32, max_storage_images: u32, max_sampled_images: u32, max_samplers: u32) -> GpuBindlessDescriptorPool {
+    pub(crate) fn new(device: &ash::Device, max_buffers: u32, max_storage_images: u32, max_sampled_images: u32, max_samplers: u32, max_uniform_buffers: u32) -> GpuBindlessDescriptorPool {
         let pool_sizes = [
             vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::STORAGE_BUFFER,
@@ -166,6 +261,10 @@ impl GpuBindlessDescriptorPool {
                 ty: vk::DescriptorType::SAMPLER,
                 descriptor_count: max_samplers,
             },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: max_uniform_buffers,
+            },
         ];
 
         let pool_create_info = vk::DescriptorPoolCreateInfo::default()
@@ -196,12 +295,19 @@ impl GpuBindlessDescriptorPool {
                 .descriptor_type(vk::DescriptorType::SAMPLER)
                 .descriptor_count(max_samplers)
                 .stage_flags(vk::ShaderStageFlags::ALL),
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(4)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(max_uniform_buffers)
+                .stage_flags(vk::ShaderStageFlags::ALL),
         ];
 
         let binding_flags = [
             vk::DescriptorBindingFlags::PARTIALLY_BOUND | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND,
             vk::DescriptorBindingFlags::PARTIALLY_BOUND | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND,
             vk::DescriptorBindingFlags::PARTIALLY_BOUND | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND,
+            vk::DescriptorBindingFlags::PARTIALLY_BOUND | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND,
+            // Only the last binding may have a variable count
             vk::DescriptorBindingFlags::PARTIALLY_BOUND | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND | vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT,
         ];
 
@@ -214,7 +320,7 @@ impl GpuBindlessDescriptorPool {
 
         let bindless_set_layout = unsafe { device.create_descriptor_set_layout(&layout_info, None).expect("Failed to create bindless descriptor set layout") };
 
-        let variable_counts = [max_buffers];
+        let variable_counts = [max_uniform_buffers];
         let mut variable_count_info = vk::DescriptorSetVariableDescriptorCountAllocateInfo::default().descriptor_counts(&variable_counts);
 
         let alloc_info = vk::DescriptorSetAllocateInfo::default()
@@ -228,21 +334,28 @@ impl GpuBindlessDescriptorPool {
             pool: descriptor_pool,
             set: bindless_set,
             layout: bindless_set_layout,
+            buffer_indices: Mutex::new(DescriptorIndexAllocator::new(max_buffers, "buffer")),
+            sampled_image_indices: Mutex::new(DescriptorIndexAllocator::new(max_sampled_images, "sampled image")),
+            storage_image_indices: Mutex::new(DescriptorIndexAllocator::new(max_storage_images, "storage image")),
+            sampler_indices: Mutex::new(DescriptorIndexAllocator::new(max_samplers, "sampler")),
+            uniform_buffer_indices: Mutex::new(DescriptorIndexAllocator::new(max_uniform_buffers, "uniform buffer")),
         };
     }
 
-    pub(crate) fn write_buffer(&self, device: &ash::Device, buffer: vk::Buffer, index: u32) {
+    pub(crate) fn write_buffer(&self, device: &ash::Device, buffer: vk::Buffer, offset: u64, range: u64, index: u32) {
+        self.buffer_indices.lock().unwrap().mark_used(index);
+
         let buffer_info = [vk::DescriptorBufferInfo {
             buffer: buffer,
-            offset: 0,
-            range: MAX,
+            offset: offset,
+            range: range,
         }];
 
         let write_info = [vk::WriteDescriptorSet::default()
             .buffer_info(&buffer_info)
             .dst_set(self.set)
-            .dst_binding(index)
-            .dst_array_element(0)
+            .dst_binding(0)
+            .dst_array_element(index)
             .descriptor_count(1)
             .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)];
 
@@ -251,10 +364,34 @@ impl GpuBindlessDescriptorPool {
         }
     }
 
-    pub(crate) fn write_sampled_image(&self, device: &ash::Device, image_view: vk::ImageView, index: u32) {
+    pub(crate) fn write_uniform_buffer(&self, device: &ash::Device, buffer: vk::Buffer, offset: u64, range: u64, index: u32) {
+        self.uniform_buffer_indices.lock().unwrap().mark_used(index);
+
+        let buffer_info = [vk::DescriptorBufferInfo {
+            buffer: buffer,
+            offset: offset,
+            range: range,
+        }];
+
+        let write_info = [vk::WriteDescriptorSet::default()
+            .buffer_info(&buffer_info)
+            .dst_set(self.set)
+            .dst_binding(4)
+            .dst_array_element(index)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)];
+
+        unsafe {
+            device.update_descriptor_sets(&write_info, &[]);
+        }
+    }
+
+    pub(crate) fn write_sampled_image(&self, device: &ash::Device, image_view: vk::ImageView, image_layout: vk::ImageLayout, index: u32) {
+        self.sampled_image_indices.lock().unwrap().mark_used(index);
+
         let sampler_info = [vk::DescriptorImageInfo {
             image_view: image_view,
-            image_layout: vk::ImageLayout::GENERAL,
+            image_layout: image_layout,
             sampler: vk::Sampler::null(),
         }];
 
@@ -273,10 +410,12 @@ impl GpuBindlessDescriptorPool {
         }
     }
 
-    pub(crate) fn write_storage_image(&self, device: &ash::Device, image_view: vk::ImageView, index: u32) {
+    pub(crate) fn write_storage_image(&self, device: &ash::Device, image_view: vk::ImageView, image_layout: vk::ImageLayout, index: u32) {
+        self.storage_image_indices.lock().unwrap().mark_used(index);
+
         let sampler_info = [vk::DescriptorImageInfo {
             image_view: image_view,
-            image_layout: vk::ImageLayout::GENERAL,
+            image_layout: image_layout,
             sampler: vk::Sampler::null(),
         }];
 
@@ -296,6 +435,8 @@ impl GpuBindlessDescriptorPool {
     }
 
     pub(crate) fn write_sampler(&self, device: &ash::Device, sampler: vk::Sampler, index: u32) {
+        self.sampler_indices.lock().unwrap().mark_used(index);
+
         let sampler_info = [vk::DescriptorImageInfo {
             image_view: vk::ImageView::null(),
             image_layout: vk::ImageLayout::UNDEFINED,
@@ -308,7 +449,7 @@ impl GpuBindlessDescriptorPool {
             .dst_binding(3)
             .dst_array_element(index)
             .descriptor_count(1)
-            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)];
+            .descriptor_type(vk::DescriptorType::SAMPLER)];
 
         let copy_sets = [];
 
@@ -324,3 +465,72 @@ impl GpuBindlessDescriptorPool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_id_is_rejected_after_slot_reuse() {
+        let mut pool = GpuResourcePool::<u32>::new();
+
+        let old = pool.add(1);
+        pool.delete(old);
+        let new = pool.add(2);
+
+        assert_eq!(decode_as_usize(old).0, decode_as_usize(new).0);
+        assert_eq!(decode_as_usize(old).1, decode_as_usize(new).1);
+        assert!(pool.try_get_ref(old).is_none());
+        assert_eq!(*pool.get_ref(new), 2);
+    }
+
+    #[test]
+    fn churned_slot_is_retired_past_the_version_limit() {
+        let mut pool = GpuResourcePool::<u32>::new();
+        let mut ids = Vec::new();
+
+        // More create/destroy cycles than a single slot has versions
+        for i in 0..70_000u32 {
+            let id = pool.add(i);
+            assert_eq!(*pool.get_ref(id), i);
+            pool.delete(id);
+            ids.push(id);
+        }
+
+        // No id was handed out twice and none of them is valid after being deleted
+        let unique: std::collections::HashSet<u64> = ids.iter().copied().collect();
+        assert_eq!(unique.len(), ids.len());
+        assert!(ids.iter().all(|&id| pool.try_get_ref(id).is_none()));
+
+        // The first slot went through every version once, then a fresh slot took over
+        let (page, index, _) = decode_as_usize(ids[0]);
+        let first_slot: Vec<u64> = ids
+            .iter()
+            .copied()
+            .filter(|&id| {
+                let (p, i, _) = decode_as_usize(id);
+                p == page && i == index
+            })
+            .collect();
+
+        assert_eq!(first_slot.len(), MASK as usize + 1);
+        assert_eq!(decode_as_usize(*first_slot.last().unwrap()).2, MASK);
+        assert_ne!(decode_as_usize(ids[MASK as usize + 1]).1, index);
+    }
+
+    #[test]
+    #[should_panic(expected = "freed twice")]
+    fn descriptor_index_cannot_be_freed_twice() {
+        let mut indices = DescriptorIndexAllocator::new(4, "buffer");
+        let index = indices.allocate();
+
+        indices.free(index);
+        indices.free(index);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn descriptor_index_out_of_range_is_rejected() {
+        DescriptorIndexAllocator::new(4, "buffer").free(4);
+    }
+}
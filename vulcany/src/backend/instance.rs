@@ -1,4 +1,4 @@
-use crate::{ApiVersion, DeviceDescription, InstanceDescription};
+use crate::{AdapterInfo, AdapterType, ApiVersion, DebugCallback, DebugMessageType, DebugSeverity, DeviceDescription, HeadlessInstanceDescription, InstanceDescription};
 
 use ash::vk;
 //use image::imageops::FilterType::Triangle;
@@ -23,12 +23,26 @@ pub(crate) struct QueueFamilyIndices {
     pub compute_family: Option<u32>,
 }
 
-pub(crate) struct PhysicalDevice<'a> {
+pub(crate) struct PhysicalDevice {
     pub handle: vk::PhysicalDevice,
-    pub swapchain_support: SwapchainSupport,
+    /// None for headless instances
+    pub swapchain_support: Option<SwapchainSupport>,
     pub queue_families: QueueFamilyIndices,
-    pub properties: vk::PhysicalDeviceProperties2<'a>,
-    pub rt_props: vk::PhysicalDeviceRayTracingPipelinePropertiesKHR<'a>,
+    pub properties: vk::PhysicalDeviceProperties,
+    pub rt_props: vk::PhysicalDeviceRayTracingPipelinePropertiesKHR<'static>,
+    pub depth_stencil_resolve_props: vk::PhysicalDeviceDepthStencilResolveProperties<'static>,
+    pub descriptor_indexing_props: vk::PhysicalDeviceDescriptorIndexingProperties<'static>,
+    pub features: vk::PhysicalDeviceFeatures,
+    /// VK_EXT_extended_dynamic_state3 with dynamic polygon mode is available
+    pub dynamic_polygon_mode: bool,
+    /// VK_EXT_custom_border_color usable without a format is available
+    pub custom_border_color: bool,
+    /// VK_KHR_push_descriptor is available
+    pub push_descriptor: bool,
+    /// VK_EXT_sampler_filter_minmax is available
+    pub sampler_filter_minmax: bool,
+    /// VK_EXT_index_type_uint8 is available
+    pub index_type_uint8: bool,
 }
 
 pub(crate) struct InnerInstance {
@@ -36,7 +50,10 @@ pub(crate) struct InnerInstance {
     pub(crate) handle: ash::Instance,
     debug_messenger: Option<vk::DebugUtilsMessengerEXT>,
     debug_loader: Option<ash::ext::debug_utils::Instance>,
-    pub(crate) surface: Surface,
+    // Never read, only kept alive for the messenger. Boxed so its user data pointer stays valid when the instance moves
+    _debug_callback: Option<Box<DebugCallback>>,
+    // None for headless instances
+    pub(crate) surface: Option<Surface>,
     physical_device_extensions: Vec<&'static CStr>,
     api_version: ApiVersion,
 }
@@ -74,23 +91,64 @@ impl InnerInstance {
             _ => {}
         };
 
-        if instance_create_info.enable_validation_layers {
+        let mut instance = InnerInstance::create(
+            entry,
+            required_extensions,
+            instance_create_info.api_version.clone(),
+            instance_create_info.enable_validation_layers,
+            instance_create_info.debug_callback.clone(),
+        );
+
+        instance.surface = Some(unsafe { InnerInstance::create_surface(&instance.entry, &instance.handle, &instance_create_info.window) });
+        instance.physical_device_extensions = vec![ash::khr::swapchain::NAME];
+
+        return instance;
+    }
+
+    pub(crate) fn new_headless(instance_create_info: &HeadlessInstanceDescription) -> InnerInstance {
+        let entry = ash::Entry::linked();
+
+        return InnerInstance::create(
+            entry,
+            Vec::new(),
+            instance_create_info.api_version.clone(),
+            instance_create_info.enable_validation_layers,
+            instance_create_info.debug_callback.clone(),
+        );
+    }
+
+    // Everything but the surface, shared by windowed and headless instances
+    fn create(
+        entry: ash::Entry,
+        mut required_extensions: Vec<*const std::ffi::c_char>,
+        api_version: ApiVersion,
+        enable_validation_layers: bool,
+        debug_callback: Option<DebugCallback>,
+    ) -> InnerInstance {
+        if enable_validation_layers {
             required_extensions.push(ash::ext::debug_utils::NAME.as_ptr());
         }
 
         let app_info = vk::ApplicationInfo {
-            api_version: instance_create_info.api_version.clone() as u32,
+            api_version: api_version.clone() as u32,
             ..Default::default()
         };
 
         let mut create_info = vk::InstanceCreateInfo::default().application_info(&app_info).enabled_extension_names(&required_extensions);
 
+        let debug_callback = debug_callback.map(Box::new);
+        let user_data = match &debug_callback {
+            Some(callback) => &**callback as *const DebugCallback as *mut std::ffi::c_void,
+            None => std::ptr::null_mut(),
+        };
+
         let mut debug_create_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
             .message_severity(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING)
             .message_type(vk::DebugUtilsMessageTypeFlagsEXT::GENERAL | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION)
-            .pfn_user_callback(Some(InnerInstance::vulkan_debug_callback));
+            .pfn_user_callback(Some(InnerInstance::vulkan_debug_callback))
+            .user_data(user_data);
 
-        if instance_create_info.enable_validation_layers {
+        if enable_validation_layers {
             create_info = create_info.push_next(&mut debug_create_info);
         }
 
@@ -99,7 +157,7 @@ impl InnerInstance {
         let mut debug_messenger: Option<vk::DebugUtilsMessengerEXT> = None;
         let mut debug_loader: Option<ash::ext::debug_utils::Instance> = None;
 
-        if instance_create_info.enable_validation_layers {
+        if enable_validation_layers {
             let debug_utils_loader = ash::ext::debug_utils::Instance::new(&entry, &instance);
 
             debug_messenger = Some(unsafe { debug_utils_loader.create_debug_utils_messenger(&debug_create_info, None) }.expect("Debug Utils Messenger creation failed"));
@@ -107,24 +165,26 @@ impl InnerInstance {
             debug_loader = Some(debug_utils_loader);
         }
 
-        let surface = unsafe { InnerInstance::create_surface(&entry, &instance, &instance_create_info.window) };
-
         return InnerInstance {
             entry: entry,
             handle: instance,
             debug_messenger: debug_messenger,
             debug_loader: debug_loader,
-            surface: surface,
-            physical_device_extensions: vec![ash::khr::swapchain::NAME],
-            api_version: instance_create_info.api_version.clone(),
+            _debug_callback: debug_callback,
+            surface: None,
+            physical_device_extensions: Vec::new(),
+            api_version: api_version,
         };
     }
 
     pub(crate) fn create_device_data(&self, device_desc: &DeviceDescription) -> (ash::Device, PhysicalDevice, vk_mem::Allocator) {
         let physical_device = {
-            let dev = self.select_physical_device();
+            let dev = self.select_physical_device(device_desc.adapter_index);
             if dev.is_none() {
-                panic!("Failed to find vulkan compatible device")
+                match device_desc.adapter_index {
+                    Some(index) => panic!("Adapter {} does not exist or is not suitable, check Instance::enumerate_adapters", index),
+                    None => panic!("Failed to find vulkan compatible device"),
+                }
             }
 
             dev.unwrap()
@@ -133,10 +193,11 @@ impl InnerInstance {
         let unique_families: Vec<u32> = {
             let mut v = vec![
                 physical_device.queue_families.graphics_family.unwrap(),
-                physical_device.queue_families.presetation_family.unwrap(),
                 physical_device.queue_families.transfer_family.unwrap(),
                 physical_device.queue_families.compute_family.unwrap(),
             ];
+            // Headless devices have no present queue
+            v.extend(physical_device.queue_families.presetation_family);
             v.sort();
             v.dedup();
             v
@@ -149,11 +210,21 @@ impl InnerInstance {
             .map(|&family| vk::DeviceQueueCreateInfo::default().queue_family_index(family).queue_priorities(&priorities))
             .collect();
 
-        // Required device extensions (swapchain needed for presentation)
-        let mut device_extensions = vec![ash::khr::swapchain::NAME.as_ptr(), ash::khr::synchronization2::NAME.as_ptr()];
+        // Required device extensions, swapchain is only needed when there is a surface to present to
+        let mut device_extensions: Vec<*const std::ffi::c_char> = self.physical_device_extensions.iter().map(|name| name.as_ptr()).collect();
+        device_extensions.push(ash::khr::synchronization2::NAME.as_ptr());
 
         // Existing common features
-        let features = vk::PhysicalDeviceFeatures::default().shader_int64(true);
+        let features = vk::PhysicalDeviceFeatures::default()
+            .shader_int64(true)
+            .fill_mode_non_solid(physical_device.features.fill_mode_non_solid == vk::TRUE)
+            .wide_lines(physical_device.features.wide_lines == vk::TRUE)
+            .depth_bias_clamp(physical_device.features.depth_bias_clamp == vk::TRUE)
+            .depth_clamp(physical_device.features.depth_clamp == vk::TRUE)
+            .pipeline_statistics_query(physical_device.features.pipeline_statistics_query == vk::TRUE)
+            .geometry_shader(physical_device.features.geometry_shader == vk::TRUE)
+            .tessellation_shader(physical_device.features.tessellation_shader == vk::TRUE)
+            .sampler_anisotropy(physical_device.features.sampler_anisotropy == vk::TRUE);
 
         let mut dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures::default().dynamic_rendering(true);
 
@@ -172,7 +243,7 @@ impl InnerInstance {
         let mut sync2 = vk::PhysicalDeviceSynchronization2Features::default().synchronization2(true);
         let mut timeline_sem = vk::PhysicalDeviceTimelineSemaphoreFeatures::default().timeline_semaphore(true);
         let mut buffer_device_address = vk::PhysicalDeviceBufferDeviceAddressFeatures::default().buffer_device_address(true);
-        let mut vk_features_11 = vk::PhysicalDeviceVulkan11Features::default().shader_draw_parameters(true);
+        let mut vk_features_11 = vk::PhysicalDeviceVulkan11Features::default().shader_draw_parameters(true).multiview(true);
 
         // ----> CONDITIONAL RAY TRACING ADDITIONS <----
         let mut accel_struct_features = vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default();
@@ -180,10 +251,20 @@ impl InnerInstance {
         let mut ray_query_features = vk::PhysicalDeviceRayQueryFeaturesKHR::default();
 
         if device_desc.ray_tracing {
+            let rt_extensions = [
+                ash::khr::acceleration_structure::NAME,
+                ash::khr::ray_tracing_pipeline::NAME,
+                ash::khr::deferred_host_operations::NAME,
+                ash::khr::ray_query::NAME,
+            ];
+
             // Add RT extensions
-            device_extensions.push(ash::khr::acceleration_structure::NAME.as_ptr());
-            device_extensions.push(ash::khr::ray_tracing_pipeline::NAME.as_ptr());
-            device_extensions.push(ash::khr::deferred_host_operations::NAME.as_ptr());
+            for name in rt_extensions {
+                if !self.supports_extension(physical_device.handle, name) {
+                    panic!("Ray tracing was requested but the device does not support {:?}", name);
+                }
+                device_extensions.push(name.as_ptr());
+            }
 
             // Enable the Vulkan features
             accel_struct_features = accel_struct_features.acceleration_structure(true);
@@ -193,6 +274,47 @@ impl InnerInstance {
             ray_query_features = ray_query_features.ray_query(true);
         }
 
+        let mut mesh_shader_features = vk::PhysicalDeviceMeshShaderFeaturesEXT::default();
+
+        if device_desc.mesh_shaders {
+            if !self.supports_extension(physical_device.handle, ash::ext::mesh_shader::NAME) {
+                panic!("Mesh shaders were requested but the device does not support VK_EXT_mesh_shader");
+            }
+
+            device_extensions.push(ash::ext::mesh_shader::NAME.as_ptr());
+            mesh_shader_features = mesh_shader_features.mesh_shader(true).task_shader(true);
+        }
+
+        // Dynamic polygon mode, used for wireframe toggles without a second pipeline
+        let mut extended_dynamic_state3_features = vk::PhysicalDeviceExtendedDynamicState3FeaturesEXT::default();
+
+        if physical_device.dynamic_polygon_mode {
+            device_extensions.push(ash::ext::extended_dynamic_state3::NAME.as_ptr());
+            extended_dynamic_state3_features = extended_dynamic_state3_features.extended_dynamic_state3_polygon_mode(true);
+        }
+
+        if physical_device.push_descriptor {
+            device_extensions.push(ash::khr::push_descriptor::NAME.as_ptr());
+        }
+
+        if physical_device.sampler_filter_minmax {
+            device_extensions.push(ash::ext::sampler_filter_minmax::NAME.as_ptr());
+        }
+
+        let mut index_type_uint8_features = vk::PhysicalDeviceIndexTypeUint8FeaturesEXT::default();
+
+        if physical_device.index_type_uint8 {
+            device_extensions.push(ash::ext::index_type_uint8::NAME.as_ptr());
+            index_type_uint8_features = index_type_uint8_features.index_type_uint8(true);
+        }
+
+        let mut custom_border_color_features = vk::PhysicalDeviceCustomBorderColorFeaturesEXT::default();
+
+        if physical_device.custom_border_color {
+            device_extensions.push(ash::ext::custom_border_color::NAME.as_ptr());
+            custom_border_color_features = custom_border_color_features.custom_border_colors(true).custom_border_color_without_format(true);
+        }
+
         // ----> Build final feature2 chain <----
         let mut features2 = vk::PhysicalDeviceFeatures2::default()
             .push_next(&mut indexing_features)
@@ -208,6 +330,22 @@ impl InnerInstance {
             features2 = features2.push_next(&mut accel_struct_features).push_next(&mut rt_pipeline_features).push_next(&mut ray_query_features);
         }
 
+        if device_desc.mesh_shaders {
+            features2 = features2.push_next(&mut mesh_shader_features);
+        }
+
+        if physical_device.dynamic_polygon_mode {
+            features2 = features2.push_next(&mut extended_dynamic_state3_features);
+        }
+
+        if physical_device.index_type_uint8 {
+            features2 = features2.push_next(&mut index_type_uint8_features);
+        }
+
+        if physical_device.custom_border_color {
+            features2 = features2.push_next(&mut custom_border_color_features);
+        }
+
         let create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(&queue_infos)
             .enabled_extension_names(&device_extensions)
@@ -224,12 +362,13 @@ impl InnerInstance {
         return (dev, physical_device, allocator);
     }
 
-    pub(crate) fn create_queues(device: &ash::Device, physical_device: &PhysicalDevice) -> (vk::Queue, vk::Queue, vk::Queue) {
+    pub(crate) fn create_queues(device: &ash::Device, physical_device: &PhysicalDevice) -> (vk::Queue, vk::Queue, vk::Queue, vk::Queue) {
         return unsafe {
             (
                 device.get_device_queue(physical_device.queue_families.graphics_family.unwrap(), 0),
                 device.get_device_queue(physical_device.queue_families.transfer_family.unwrap(), 0),
                 device.get_device_queue(physical_device.queue_families.compute_family.unwrap(), 0),
+                physical_device.queue_families.presetation_family.map_or(vk::Queue::null(), |family| device.get_device_queue(family, 0)),
             )
         };
     }
@@ -316,13 +455,22 @@ impl InnerInstance {
             }
 
             // Presentation
-            let present_support = unsafe { self.surface.loader.get_physical_device_surface_support(physical_device, i as u32, self.surface.handle).unwrap_or(false) };
+            let present_support = match &self.surface {
+                Some(surface) => unsafe { surface.loader.get_physical_device_surface_support(physical_device, i as u32, surface.handle).unwrap_or(false) },
+                None => false,
+            };
             if present_support && indices.presetation_family.is_none() {
                 indices.presetation_family = Some(i as u32);
             }
+
+            // Prefer a single family that can do both, graphics and present then share a queue
+            if present_support && family.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS) && indices.graphics_family != indices.presetation_family {
+                indices.graphics_family = Some(i as u32);
+                indices.presetation_family = Some(i as u32);
+            }
         }
 
-        if indices.graphics_family.is_some() && indices.presetation_family.is_some() {
+        if indices.graphics_family.is_some() && (indices.presetation_family.is_some() || self.surface.is_none()) {
             Some(indices)
         } else {
             None
@@ -330,12 +478,14 @@ impl InnerInstance {
     }
 
     fn get_swapchain_support(&self, physical_device: ash::vk::PhysicalDevice) -> Option<SwapchainSupport> {
+        let surface = self.surface.as_ref()?;
+
         unsafe {
-            let capabilities = self.surface.loader.get_physical_device_surface_capabilities(physical_device, self.surface.handle).ok()?;
+            let capabilities = surface.loader.get_physical_device_surface_capabilities(physical_device, surface.handle).ok()?;
 
-            let formats = self.surface.loader.get_physical_device_surface_formats(physical_device, self.surface.handle).ok()?;
+            let formats = surface.loader.get_physical_device_surface_formats(physical_device, surface.handle).ok()?;
 
-            let present_modes = self.surface.loader.get_physical_device_surface_present_modes(physical_device, self.surface.handle).ok()?;
+            let present_modes = surface.loader.get_physical_device_surface_present_modes(physical_device, surface.handle).ok()?;
 
             if formats.is_empty() || present_modes.is_empty() {
                 return None;
@@ -345,6 +495,12 @@ impl InnerInstance {
         }
     }
 
+    fn supports_extension(&self, device: ash::vk::PhysicalDevice, name: &CStr) -> bool {
+        let available_extensions = unsafe { self.handle.enumerate_device_extension_properties(device).expect("Failed to enumerate device extensions") };
+
+        return available_extensions.iter().any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == name);
+    }
+
     fn check_device_extension_support(&self, device: ash::vk::PhysicalDevice) -> bool {
         let available_extensions = unsafe { self.handle.enumerate_device_extension_properties(device).expect("Failed to enumerate device extensions") };
 
@@ -361,42 +517,123 @@ impl InnerInstance {
         self.physical_device_extensions.iter().all(|&required| available_extension_names.iter().any(|&avail| avail == required))
     }
 
-    fn select_physical_device(&self) -> Option<PhysicalDevice> {
+    pub(crate) fn enumerate_adapters(&self) -> Vec<AdapterInfo> {
+        let devices = unsafe { self.handle.enumerate_physical_devices().expect("Failed to enumerate physical devices") };
+
+        return devices
+            .iter()
+            .enumerate()
+            .map(|(index, &device)| {
+                let properties = unsafe { self.handle.get_physical_device_properties(device) };
+                let memory_properties = unsafe { self.handle.get_physical_device_memory_properties(device) };
+
+                let device_local_memory = memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize]
+                    .iter()
+                    .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+                    .map(|heap| heap.size)
+                    .sum();
+
+                AdapterInfo {
+                    index: index as u32,
+                    name: properties.device_name_as_c_str().map_or(String::new(), |name| name.to_string_lossy().into_owned()),
+                    adapter_type: AdapterType::from_vk(properties.device_type),
+                    device_local_memory: device_local_memory,
+                    suitable: self.is_device_suitable(device),
+                }
+            })
+            .collect();
+    }
+
+    fn is_device_suitable(&self, device: vk::PhysicalDevice) -> bool {
+        let can_present = self.surface.is_none() || self.get_swapchain_support(device).is_some();
+        return can_present && self.get_queue_families(device).is_some() && self.check_device_extension_support(device);
+    }
+
+    // Picks the highest scoring suitable device, or only considers `adapter_index` when given
+    fn select_physical_device(&self, adapter_index: Option<u32>) -> Option<PhysicalDevice> {
         let devices = unsafe { self.handle.enumerate_physical_devices().expect("Failed to enumerate physical devices") };
 
         let mut best_device: Option<(i32, PhysicalDevice)> = None;
 
-        for device in devices {
+        for (index, device) in devices.into_iter().enumerate() {
+            if adapter_index.is_some_and(|i| i as usize != index) {
+                continue;
+            }
+
             let mut rt_props: vk::PhysicalDeviceRayTracingPipelinePropertiesKHR = Default::default();
-            let mut props: vk::PhysicalDeviceProperties2 = vk::PhysicalDeviceProperties2::default().push_next(&mut rt_props);
-            unsafe {
-                self.handle.get_physical_device_properties2(device, &mut props);
+            let mut depth_stencil_resolve_props: vk::PhysicalDeviceDepthStencilResolveProperties = Default::default();
+            let mut descriptor_indexing_props: vk::PhysicalDeviceDescriptorIndexingProperties = Default::default();
+            let properties = {
+                let mut props: vk::PhysicalDeviceProperties2 = vk::PhysicalDeviceProperties2::default()
+                    .push_next(&mut rt_props)
+                    .push_next(&mut depth_stencil_resolve_props)
+                    .push_next(&mut descriptor_indexing_props);
+                unsafe {
+                    self.handle.get_physical_device_properties2(device, &mut props);
+                };
+                props.properties
             };
 
-            if let (Some(sc), Some(qf)) = (self.get_swapchain_support(device), self.get_queue_families(device)) {
+            let mut extended_dynamic_state3_features = vk::PhysicalDeviceExtendedDynamicState3FeaturesEXT::default();
+            let mut custom_border_color_features = vk::PhysicalDeviceCustomBorderColorFeaturesEXT::default();
+            let mut index_type_uint8_features = vk::PhysicalDeviceIndexTypeUint8FeaturesEXT::default();
+            let features = {
+                let mut features2 = vk::PhysicalDeviceFeatures2::default()
+                    .push_next(&mut extended_dynamic_state3_features)
+                    .push_next(&mut custom_border_color_features)
+                    .push_next(&mut index_type_uint8_features);
+                unsafe {
+                    self.handle.get_physical_device_features2(device, &mut features2);
+                };
+                features2.features
+            };
+
+            let dynamic_polygon_mode = features.fill_mode_non_solid == vk::TRUE
+                && extended_dynamic_state3_features.extended_dynamic_state3_polygon_mode == vk::TRUE
+                && self.supports_extension(device, ash::ext::extended_dynamic_state3::NAME);
+
+            let custom_border_color = custom_border_color_features.custom_border_colors == vk::TRUE
+                && custom_border_color_features.custom_border_color_without_format == vk::TRUE
+                && self.supports_extension(device, ash::ext::custom_border_color::NAME);
+
+            let push_descriptor = self.supports_extension(device, ash::khr::push_descriptor::NAME);
+            let sampler_filter_minmax = self.supports_extension(device, ash::ext::sampler_filter_minmax::NAME);
+            let index_type_uint8 = index_type_uint8_features.index_type_uint8 == vk::TRUE && self.supports_extension(device, ash::ext::index_type_uint8::NAME);
+
+            let swapchain_support = self.get_swapchain_support(device);
+            if self.surface.is_some() && swapchain_support.is_none() {
+                continue;
+            }
+
+            if let Some(qf) = self.get_queue_families(device) {
                 if !self.check_device_extension_support(device) {
                     continue;
                 }
 
                 // Score device: discrete = 1000, integrated = 100, others = 10
-                let score = match props.properties.device_type {
+                let score = match properties.device_type {
                     ash::vk::PhysicalDeviceType::DISCRETE_GPU => 1000,
                     ash::vk::PhysicalDeviceType::INTEGRATED_GPU => 100,
                     _ => 10,
                 };
 
                 // Prefer larger max image dimension as tiebreaker
-                let score = score + props.properties.limits.max_image_dimension2_d as i32;
-
-                let owned_props = props; // Copy, no pNext
-                let owned_rt_props = rt_props;
+                let score = score + properties.limits.max_image_dimension2_d as i32;
 
                 let candidate = PhysicalDevice {
                     handle: device,
-                    swapchain_support: sc,
+                    swapchain_support: swapchain_support,
                     queue_families: qf,
-                    properties: owned_props,
-                    rt_props: owned_rt_props,
+                    properties: properties,
+                    rt_props: rt_props,
+                    depth_stencil_resolve_props: depth_stencil_resolve_props,
+                    descriptor_indexing_props: descriptor_indexing_props,
+                    features: features,
+                    dynamic_polygon_mode: dynamic_polygon_mode,
+                    custom_border_color: custom_border_color,
+                    push_descriptor: push_descriptor,
+                    sampler_filter_minmax: sampler_filter_minmax,
+                    index_type_uint8: index_type_uint8,
                 };
 
                 if let Some((best_score, _)) = &best_device {
@@ -415,15 +652,21 @@ impl InnerInstance {
 
 //Debug Messenger
 impl InnerInstance {
-    #[allow(unused)]
+    // `user` points to the boxed DebugCallback, or is null when none was given
     unsafe extern "system" fn vulkan_debug_callback(
         severity: ash::vk::DebugUtilsMessageSeverityFlagsEXT,
         types: ash::vk::DebugUtilsMessageTypeFlagsEXT,
         data: *const ash::vk::DebugUtilsMessengerCallbackDataEXT,
-        _user: *mut std::ffi::c_void,
+        user: *mut std::ffi::c_void,
     ) -> ash::vk::Bool32 {
-        let message = unsafe { std::ffi::CStr::from_ptr((*data).p_message).to_string_lossy().into_owned() };
-        println!("[VULKAN, {:?} {:?}]: {}", severity, types, message);
+        let message = unsafe { std::ffi::CStr::from_ptr((*data).p_message).to_string_lossy() };
+
+        if user.is_null() {
+            println!("[VULKAN, {:?} {:?}]: {}", severity, types, message);
+        } else {
+            let callback = unsafe { &*(user as *const DebugCallback) };
+            callback(DebugSeverity::from_vk(severity), DebugMessageType::from_vk(types), &message);
+        }
 
         ash::vk::FALSE
     }
@@ -433,7 +676,9 @@ impl InnerInstance {
 impl Drop for InnerInstance {
     fn drop(&mut self) {
         unsafe {
-            self.surface.loader.destroy_surface(self.surface.handle, None);
+            if let Some(surface) = &self.surface {
+                surface.loader.destroy_surface(surface.handle, None);
+            }
 
             if !self.debug_messenger.is_none() {
                 if self.debug_loader.is_none() {
@@ -2,4 +2,5 @@ pub(crate) mod device;
 pub(crate) mod gpu_resources;
 pub(crate) mod instance;
 pub(crate) mod pipelines;
+pub(crate) mod spirv;
 pub(crate) mod swapchain;
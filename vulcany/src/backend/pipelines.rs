@@ -1,8 +1,11 @@
 use ahash::{HashMap, HashMapExt};
 use ash::vk;
-use smallvec::smallvec;
 
-use crate::{BufferID, RayTracingPipelineDescription, backend::device::InnerDevice, *};
+use crate::{
+    RayTracingPipelineDescription,
+    backend::{device::InnerDevice, spirv::SpirvReflection},
+    *,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -26,6 +29,9 @@ pub(crate) struct ShaderCacheEntry {
 pub(crate) struct InnerPipelineManager {
     pub(crate) shaders: Mutex<HashMap<String, ShaderCacheEntry>>,
     pub(crate) desc_layout: vk::DescriptorSetLayout,
+    pub(crate) pipeline_cache: vk::PipelineCache,
+    // Keyed by push constant (offset, size, stage flags), every layout shares the bindless set layout
+    pub(crate) pipeline_layouts: Mutex<HashMap<(u32, u32, u32), vk::PipelineLayout>>,
     pub(crate) device: Arc<InnerDevice>,
 }
 
@@ -47,13 +53,56 @@ impl InnerPipelineManager {
             HashMap::new()
         };
 
+        let pipeline_cache = Self::load_pipeline_cache(&device, &cache_dir.join("pipeline_cache.bin"));
+
         InnerPipelineManager {
             shaders: Mutex::new(files),
             desc_layout: device.bindless_descriptors.layout,
+            pipeline_cache: pipeline_cache,
+            pipeline_layouts: Mutex::new(HashMap::new()),
             device,
         }
     }
 
+    // The file starts with the pipelineCacheUUID it was written with, a driver update changes it and the old data is thrown away
+    fn load_pipeline_cache(device: &InnerDevice, path: &Path) -> vk::PipelineCache {
+        let uuid = device.physical_device.properties.pipeline_cache_uuid;
+
+        let initial_data = match fs::read(path) {
+            Ok(data) if data.len() <= vk::UUID_SIZE => {
+                println!("Pipeline cache file is truncated, discarding it");
+                Vec::new()
+            }
+            Ok(data) if data[..vk::UUID_SIZE] == uuid => data[vk::UUID_SIZE..].to_vec(),
+            Ok(_) => {
+                println!("Pipeline cache does not match this device, discarding it");
+                Vec::new()
+            }
+            Err(_) => Vec::new(),
+        };
+
+        let create_info = vk::PipelineCacheCreateInfo::default().initial_data(&initial_data);
+
+        return unsafe { device.handle.create_pipeline_cache(&create_info, None).expect("Failed to create pipeline cache") };
+    }
+
+    fn save_pipeline_cache(&self, path: &Path) {
+        let data = match unsafe { self.device.handle.get_pipeline_cache_data(self.pipeline_cache) } {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Failed to get pipeline cache data: {:?}", e);
+                return;
+            }
+        };
+
+        let mut contents = self.device.physical_device.properties.pipeline_cache_uuid.to_vec();
+        contents.extend_from_slice(&data);
+
+        if let Err(e) = fs::write(path, contents) {
+            eprintln!("Failed to write pipeline cache: {:?}", e);
+        }
+    }
+
     pub(crate) fn get_spv_path(&self, slang_path: &str) -> Option<String> {
         let mut shaders = self.shaders.lock().unwrap();
         let path = Path::new(slang_path);
@@ -119,53 +168,119 @@ impl InnerPipelineManager {
 
 //// Pipeline creation ////
 impl InnerPipelineManager {
+    /// Layouts only differ by their push constant range, so pipelines with the same range share one.
+    /// They are owned by the manager and destroyed with it
+    fn get_pipeline_layout(&self, push_constants: &PushConstantsDescription) -> vk::PipelineLayout {
+        let key = if push_constants.size == 0 {
+            (0, 0, 0)
+        } else {
+            (push_constants.offset, push_constants.size, push_constants.stage_flags.to_vk().as_raw())
+        };
+
+        let mut layouts = self.pipeline_layouts.lock().unwrap();
+
+        if let Some(&layout) = layouts.get(&key) {
+            return layout;
+        }
+
+        let push_constant_ranges = [vk::PushConstantRange::default()
+            .offset(push_constants.offset)
+            .size(push_constants.size)
+            .stage_flags(push_constants.stage_flags.to_vk())];
+        let set_layouts: &[vk::DescriptorSetLayout] = if self.device.supports_push_descriptors() {
+            &[self.desc_layout, self.device.push_descriptor_layout]
+        } else {
+            &[self.desc_layout]
+        };
+        let layout_info = if push_constants.size == 0 {
+            vk::PipelineLayoutCreateInfo::default().set_layouts(set_layouts)
+        } else {
+            vk::PipelineLayoutCreateInfo::default().set_layouts(set_layouts).push_constant_ranges(&push_constant_ranges)
+        };
+
+        let layout = unsafe { self.device.handle.create_pipeline_layout(&layout_info, None).expect("Failed to create pipeline layout") };
+        layouts.insert(key, layout);
+
+        return layout;
+    }
+
     pub(crate) fn create_raster_pipeline_data(&self, raster_pipeline_desc: &RasterizationPipelineDescription) -> (vk::Pipeline, vk::PipelineLayout) {
         let vertex_shader_path = self
             .get_spv_path(raster_pipeline_desc.vertex_shader_path)
             .unwrap_or_else(|| panic!("Wrong vertex shader path provided"));
 
-        let fragment_shader_path = self
-            .get_spv_path(raster_pipeline_desc.fragment_shader_path)
-            .unwrap_or_else(|| panic!("Wrong fragment shader path provided"));
-
         //Shaders
         let vert_code = InnerPipelineManager::read_spv_file(&vertex_shader_path);
-        let frag_code = InnerPipelineManager::read_spv_file(&fragment_shader_path);
 
+        // Only warns about mismatches, skipped in release builds
+        if cfg!(debug_assertions) {
+            let reflection = SpirvReflection::new(&vert_code);
+            let push_constants = &raster_pipeline_desc.push_constants;
+            reflection.check_vertex_input(raster_pipeline_desc.vertex_shader_path, &raster_pipeline_desc.vertex_input);
+            reflection.check_push_constants(raster_pipeline_desc.vertex_shader_path, push_constants.offset, push_constants.size);
+        }
         let vert_module_create_info = vk::ShaderModuleCreateInfo::default().code(&vert_code);
-        let frag_module_create_info = vk::ShaderModuleCreateInfo::default().code(&frag_code);
-
         let vert_module = unsafe { self.device.handle.create_shader_module(&vert_module_create_info, None).expect("Failed to create vertex shader module") };
-        let frag_module = unsafe {
-            self.device
-                .handle
-                .create_shader_module(&frag_module_create_info, None)
-                .expect("Failed to create fragment shader module")
-        };
 
         let entry_point = std::ffi::CString::new("main").unwrap();
 
-        let shader_stages = [
-            vk::PipelineShaderStageCreateInfo::default().stage(vk::ShaderStageFlags::VERTEX).module(vert_module).name(&entry_point),
-            vk::PipelineShaderStageCreateInfo::default()
-                .stage(vk::ShaderStageFlags::FRAGMENT)
-                .module(frag_module)
-                .name(&entry_point),
-        ];
+        let mut shader_stages = vec![vk::PipelineShaderStageCreateInfo::default().stage(vk::ShaderStageFlags::VERTEX).module(vert_module).name(&entry_point)];
 
-        //Pipeline Layout
-        let push_constant_ranges = [vk::PushConstantRange::default()
-            .offset(raster_pipeline_desc.push_constants.offset)
-            .size(raster_pipeline_desc.push_constants.size)
-            .stage_flags(raster_pipeline_desc.push_constants.stage_flags.to_vk())];
-        let layouts = [self.desc_layout];
-        let layout_info = if raster_pipeline_desc.push_constants.size == 0 {
-            vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts)
-        } else {
-            vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts).push_constant_ranges(&push_constant_ranges)
-        };
+        // Optional stages, destroyed along with the vertex module
+        let mut extra_modules: Vec<vk::ShaderModule> = Vec::new();
 
-        let pipeline_layout = unsafe { self.device.handle.create_pipeline_layout(&layout_info, None).expect("Failed to create pipeline layout") };
+        // Depth only pipelines have no fragment stage, the rasterizer still writes depth
+        if let Some(fragment_shader_path) = raster_pipeline_desc.fragment_shader_path {
+            let module = self.create_shader_module(fragment_shader_path);
+            extra_modules.push(module);
+            shader_stages.push(vk::PipelineShaderStageCreateInfo::default().stage(vk::ShaderStageFlags::FRAGMENT).module(module).name(&entry_point));
+        } else if !raster_pipeline_desc.outputs.color.is_empty() {
+            panic!("A pipeline without a fragment shader cannot have color outputs");
+        }
+
+        if let Some(geometry_shader_path) = raster_pipeline_desc.geometry_shader_path {
+            if self.device.physical_device.features.geometry_shader != vk::TRUE {
+                panic!("Geometry shaders are not supported by this device");
+            }
+
+            let module = self.create_shader_module(geometry_shader_path);
+            extra_modules.push(module);
+            shader_stages.push(vk::PipelineShaderStageCreateInfo::default().stage(vk::ShaderStageFlags::GEOMETRY).module(module).name(&entry_point));
+        }
+
+        if let Some(tessellation) = &raster_pipeline_desc.tessellation {
+            if self.device.physical_device.features.tessellation_shader != vk::TRUE {
+                panic!("Tessellation shaders are not supported by this device");
+            }
+
+            let control_module = self.create_shader_module(tessellation.control_shader_path);
+            let evaluation_module = self.create_shader_module(tessellation.evaluation_shader_path);
+            extra_modules.push(control_module);
+            extra_modules.push(evaluation_module);
+
+            shader_stages.push(
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::TESSELLATION_CONTROL)
+                    .module(control_module)
+                    .name(&entry_point),
+            );
+            shader_stages.push(
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::TESSELLATION_EVALUATION)
+                    .module(evaluation_module)
+                    .name(&entry_point),
+            );
+        }
+
+        let (spec_entries, spec_data) = SpecConstantValue::pack(&raster_pipeline_desc.specialization);
+        let spec_info = vk::SpecializationInfo::default().map_entries(&spec_entries).data(&spec_data);
+
+        for stage in shader_stages.iter_mut() {
+            *stage = stage.specialization_info(&spec_info);
+        }
+
+        //Pipeline Layout
+        let pipeline_layout = self.get_pipeline_layout(&raster_pipeline_desc.push_constants);
 
         //Vertex inpput
         let (vertex_input_binding, vertex_input_attributes) = raster_pipeline_desc.vertex_input.to_vk();
@@ -175,18 +290,29 @@ impl InnerPipelineManager {
 
         //Brrr
         let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+            .topology(if raster_pipeline_desc.tessellation.is_some() {
+                vk::PrimitiveTopology::PATCH_LIST
+            } else {
+                vk::PrimitiveTopology::TRIANGLE_LIST
+            })
             .primitive_restart_enable(false);
 
+        let tessellation_state =
+            vk::PipelineTessellationStateCreateInfo::default().patch_control_points(raster_pipeline_desc.tessellation.as_ref().map_or(0, |tessellation| tessellation.patch_control_points));
+
         let viewport_state = vk::PipelineViewportStateCreateInfo::default().viewport_count(1).scissor_count(1);
 
+        if raster_pipeline_desc.depth_clamp_enable && self.device.physical_device.features.depth_clamp != vk::TRUE {
+            panic!("Depth clamp is not supported by this device");
+        }
+
         let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
-            .depth_clamp_enable(false)
-            .rasterizer_discard_enable(false)
+            .depth_clamp_enable(raster_pipeline_desc.depth_clamp_enable)
+            .rasterizer_discard_enable(raster_pipeline_desc.rasterizer_discard_enable)
             .polygon_mode(raster_pipeline_desc.polygon_mode.to_vk_flag())
             .cull_mode(raster_pipeline_desc.cull_mode.to_vk_flag())
             .front_face(raster_pipeline_desc.front_face.to_vk_flag())
-            .depth_bias_enable(false)
+            .depth_bias_enable(raster_pipeline_desc.dynamic_depth_bias)
             .line_width(1.0);
 
         let multisampling = vk::PipelineMultisampleStateCreateInfo::default()
@@ -198,7 +324,9 @@ impl InnerPipelineManager {
             .depth_write_enable(raster_pipeline_desc.depth_stencil.depth_write_enable)
             .depth_compare_op(raster_pipeline_desc.depth_stencil.depth_compare_op.to_vk())
             .depth_bounds_test_enable(false)
-            .stencil_test_enable(raster_pipeline_desc.depth_stencil.stencil_test_enable);
+            .stencil_test_enable(raster_pipeline_desc.depth_stencil.stencil_test_enable)
+            .front(raster_pipeline_desc.depth_stencil.stencil_front.to_vk())
+            .back(raster_pipeline_desc.depth_stencil.stencil_back.to_vk());
 
         let color_blend_attachment = if raster_pipeline_desc.alpha_blend_enable {
             vk::PipelineColorBlendAttachmentState {
@@ -224,18 +352,47 @@ impl InnerPipelineManager {
             }
         };
 
-        let arr = [color_blend_attachment];
+        // One blend state per color output, a depth only pipeline has none
+        let arr = vec![color_blend_attachment; raster_pipeline_desc.outputs.color.len()];
 
         let color_blend_state = vk::PipelineColorBlendStateCreateInfo::default().logic_op_enable(false).attachments(&arr);
 
-        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let mut dynamic_states = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        if raster_pipeline_desc.dynamic_polygon_mode {
+            assert!(
+                self.device.extended_dynamic_state3.is_some(),
+                "dynamic_polygon_mode needs VK_EXT_extended_dynamic_state3, check Device::supports_dynamic_polygon_mode"
+            );
+            dynamic_states.push(vk::DynamicState::POLYGON_MODE_EXT);
+        }
+        if raster_pipeline_desc.dynamic_line_width {
+            dynamic_states.push(vk::DynamicState::LINE_WIDTH);
+        }
+        if raster_pipeline_desc.dynamic_depth_bias {
+            dynamic_states.push(vk::DynamicState::DEPTH_BIAS);
+        }
+        // Extended dynamic state is core in Vulkan 1.3, no feature check needed
+        if raster_pipeline_desc.dynamic_cull_mode {
+            dynamic_states.push(vk::DynamicState::CULL_MODE);
+        }
+        if raster_pipeline_desc.dynamic_front_face {
+            dynamic_states.push(vk::DynamicState::FRONT_FACE);
+        }
+        if raster_pipeline_desc.dynamic_vertex_stride {
+            dynamic_states.push(vk::DynamicState::VERTEX_INPUT_BINDING_STRIDE);
+        }
+        if raster_pipeline_desc.dynamic_stencil_reference {
+            dynamic_states.push(vk::DynamicState::STENCIL_REFERENCE);
+        }
         let dynamic_state = vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
 
         let color_formats = raster_pipeline_desc.outputs.color.iter().map(|f| f.to_vk_format()).collect::<Vec<vk::Format>>();
 
         //Dynamic rendering
         let mut dynamic_rendering_info = {
-            let a = vk::PipelineRenderingCreateInfo::default().color_attachment_formats(color_formats.as_slice());
+            let a = vk::PipelineRenderingCreateInfo::default()
+                .color_attachment_formats(color_formats.as_slice())
+                .view_mask(raster_pipeline_desc.outputs.view_mask);
             let b = if raster_pipeline_desc.outputs.depth.is_some() {
                 a.depth_attachment_format(raster_pipeline_desc.outputs.depth.clone().unwrap().to_vk_format())
             } else {
@@ -252,7 +409,7 @@ impl InnerPipelineManager {
         };
 
         //Pipeline info
-        let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+        let mut pipeline_info = vk::GraphicsPipelineCreateInfo::default()
             .stages(&shader_stages)
             .vertex_input_state(&vertex_input_state)
             .input_assembly_state(&input_assembly)
@@ -265,51 +422,60 @@ impl InnerPipelineManager {
             .layout(pipeline_layout)
             .push_next(&mut dynamic_rendering_info);
 
+        if raster_pipeline_desc.tessellation.is_some() {
+            pipeline_info = pipeline_info.tessellation_state(&tessellation_state);
+        }
+
         let pipeline = unsafe {
             self.device
                 .handle
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
+                .create_graphics_pipelines(self.pipeline_cache, &[pipeline_info], None)
                 .expect("Failed to create graphics pipeline")[0]
         };
 
         unsafe {
             self.device.handle.destroy_shader_module(vert_module, None);
-            self.device.handle.destroy_shader_module(frag_module, None);
+
+            for module in extra_modules {
+                self.device.handle.destroy_shader_module(module, None);
+            }
         }
 
         return (pipeline, pipeline_layout);
     }
 
-    pub(crate) fn create_compute_pipeline(&self, compute_pipeline_desc: &ComputePipelineDescription) -> (vk::Pipeline, vk::PipelineLayout) {
-        let shader_module = self.create_shader_module(compute_pipeline_desc.shader_path);
+    /// Also returns the workgroup size declared by the shader
+    pub(crate) fn create_compute_pipeline(&self, compute_pipeline_desc: &ComputePipelineDescription) -> (vk::Pipeline, vk::PipelineLayout, [u32; 3]) {
+        let shader_code = self.load_spv(compute_pipeline_desc.shader_path);
+        let reflection = SpirvReflection::new(&shader_code);
+        let workgroup_size = reflection.local_size.unwrap_or([1, 1, 1]);
 
-        // pipeline layout
-        let push_constant_ranges = [vk::PushConstantRange::default()
-            .offset(compute_pipeline_desc.push_constants.offset)
-            .size(compute_pipeline_desc.push_constants.size)
-            .stage_flags(compute_pipeline_desc.push_constants.stage_flags.to_vk())];
-        let layouts = [self.desc_layout];
-        let layout_info = if compute_pipeline_desc.push_constants.size == 0 {
-            vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts)
-        } else {
-            vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts).push_constant_ranges(&push_constant_ranges)
-        };
+        if cfg!(debug_assertions) {
+            let push_constants = &compute_pipeline_desc.push_constants;
+            reflection.check_push_constants(compute_pipeline_desc.shader_path, push_constants.offset, push_constants.size);
+        }
+        let shader_module = self.create_module_from_spv(&shader_code);
 
-        let pipeline_layout = unsafe { self.device.handle.create_pipeline_layout(&layout_info, None).expect("Failed to create pipeline layout") };
+        // pipeline layout
+        let pipeline_layout = self.get_pipeline_layout(&compute_pipeline_desc.push_constants);
 
         let entry_point = std::ffi::CString::new("main").unwrap();
 
+        let (spec_entries, spec_data) = SpecConstantValue::pack(&compute_pipeline_desc.specialization);
+        let spec_info = vk::SpecializationInfo::default().map_entries(&spec_entries).data(&spec_data);
+
         let shader_stage_info = vk::PipelineShaderStageCreateInfo::default()
             .stage(vk::ShaderStageFlags::COMPUTE)
             .module(shader_module)
-            .name(&entry_point);
+            .name(&entry_point)
+            .specialization_info(&spec_info);
 
         let pipeline_info = [vk::ComputePipelineCreateInfo::default().layout(pipeline_layout).stage(shader_stage_info)];
 
         let pipeline = unsafe {
             self.device
                 .handle
-                .create_compute_pipelines(vk::PipelineCache::null(), &pipeline_info, None)
+                .create_compute_pipelines(self.pipeline_cache, &pipeline_info, None)
                 .expect("Failed to create compute pipeline")
         }[0];
 
@@ -317,7 +483,7 @@ impl InnerPipelineManager {
             self.device.handle.destroy_shader_module(shader_module, None);
         }
 
-        return (pipeline, pipeline_layout);
+        return (pipeline, pipeline_layout, workgroup_size);
     }
 
     pub(crate) fn create_rt_pipeline(&self, desc: &RayTracingPipelineDescription) -> (vk::Pipeline, vk::PipelineLayout) {
@@ -344,6 +510,18 @@ impl InnerPipelineManager {
         let raygen_index = stage_index;
         stage_index += 1;
 
+        // Raygen and miss shaders are general groups, they come first in the same order as in the SBT
+        let general_group = |index: u32| {
+            vk::RayTracingShaderGroupCreateInfoKHR::default()
+                .ty(vk::RayTracingShaderGroupTypeKHR::GENERAL)
+                .general_shader(index)
+                .closest_hit_shader(vk::SHADER_UNUSED_KHR)
+                .any_hit_shader(vk::SHADER_UNUSED_KHR)
+                .intersection_shader(vk::SHADER_UNUSED_KHR)
+        };
+
+        hit_group_infos.push(general_group(raygen_index));
+
         // -------------------------
         // MISS SHADERS
         // -------------------------
@@ -358,6 +536,10 @@ impl InnerPipelineManager {
             stage_index += 1;
         }
 
+        for &miss_index in &miss_indices {
+            hit_group_infos.push(general_group(miss_index));
+        }
+
         // -------------------------
         // HIT GROUPS
         // -------------------------
@@ -426,27 +608,35 @@ impl InnerPipelineManager {
         }
 
         // -------------------------
-        // Pipeline Layout
+        // CALLABLE SHADERS
         // -------------------------
+        for c in &desc.callable {
+            let module = self.create_shader_module(c);
+            shader_modules.push(module);
 
-        let pc = vk::PushConstantRange::default()
-            .offset(desc.push_constants.offset)
-            .size(desc.push_constants.size)
-            .stage_flags(desc.push_constants.stage_flags.to_vk());
+            shader_stages.push(vk::PipelineShaderStageCreateInfo::default().stage(vk::ShaderStageFlags::CALLABLE_KHR).module(module).name(&cstr_main));
 
-        let layouts = [self.desc_layout];
-        let layout_info = if desc.push_constants.size == 0 {
-            vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts)
-        } else {
-            vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts).push_constant_ranges(std::slice::from_ref(&pc))
-        };
+            hit_group_infos.push(general_group(stage_index));
+            stage_index += 1;
+        }
+
+        // -------------------------
+        // Pipeline Layout
+        // -------------------------
 
-        let pipeline_layout = unsafe { self.device.handle.create_pipeline_layout(&layout_info, None).expect("Failed to create RT pipeline layout") };
+        let pipeline_layout = self.get_pipeline_layout(&desc.push_constants);
 
         // -------------------------
         // Create Pipeline
         // -------------------------
 
+        let (spec_entries, spec_data) = SpecConstantValue::pack(&desc.specialization);
+        let spec_info = vk::SpecializationInfo::default().map_entries(&spec_entries).data(&spec_data);
+
+        for stage in shader_stages.iter_mut() {
+            *stage = stage.specialization_info(&spec_info);
+        }
+
         let rt_pipeline_info = vk::RayTracingPipelineCreateInfoKHR::default()
             .stages(&shader_stages)
             .groups(&hit_group_infos)
@@ -456,7 +646,7 @@ impl InnerPipelineManager {
         let pipeline = unsafe {
             match &self.device.rt {
                 Some(rt) => rt
-                    .create_ray_tracing_pipelines(vk::DeferredOperationKHR::null(), vk::PipelineCache::null(), &[rt_pipeline_info], None)
+                    .create_ray_tracing_pipelines(vk::DeferredOperationKHR::null(), self.pipeline_cache, &[rt_pipeline_info], None)
                     .expect("Failed to create RT pipeline")[0],
                 None => panic!("Tried ray tracing without enabling ray tracing"),
             }
@@ -483,19 +673,21 @@ impl InnerPipelineManager {
         // stride: handle size aligned to handle_alignment
         let handle_stride = align_up(handle_size, handle_alignment);
 
-        // SBT layout: [ rgen(1) | miss(N) | hit(M) ]
+        // SBT layout: [ rgen(1) | miss(N) | hit(M) | callable(K) ]
         let rgen_count = 1usize;
         let miss_count = desc.miss.len();
         let hit_count = desc.hit_grps.len();
+        let callable_count = desc.callable.len();
 
         // each section size must be aligned to base_alignment
         let rgen_size = align_up(rgen_count * handle_stride, base_alignment);
         let miss_size = align_up(miss_count * handle_stride, base_alignment);
         let hit_size = align_up(hit_count * handle_stride, base_alignment);
-        let sbt_size = rgen_size + miss_size + hit_size;
+        let callable_size = align_up(callable_count * handle_stride, base_alignment);
+        let sbt_size = rgen_size + miss_size + hit_size + callable_size;
 
         // --- fetch raw shader group handles from pipeline ---
-        let group_count = (rgen_count + miss_count + hit_count) as u32;
+        let group_count = (rgen_count + miss_count + hit_count + callable_count) as u32;
         let mut handles = unsafe {
             match &self.device.rt {
                 Some(rt) => rt
@@ -533,55 +725,45 @@ impl InnerPipelineManager {
             dst_offset += handle_stride; // advance by stride for next hit record
         }
 
+        // Callable records (last groups)
+        dst_offset = rgen_size + miss_size + hit_size;
+        for _ in 0..callable_count {
+            sbt_data[dst_offset..dst_offset + handle_size].copy_from_slice(&handles[src_index * handle_size..src_index * handle_size + handle_size]);
+            src_index += 1;
+            dst_offset += handle_stride;
+        }
+
         // --- create staging buffer and upload the sbt_data ---
         let staging = self.device.create_buffer(&BufferDescription {
             usage: BufferUsage::TRANSFER_SRC,
             size: sbt_size as u64,
             memory_type: MemoryType::PreferHost,
             create_mapped: true,
+            ..Default::default()
         });
         self.device.write_data_to_buffer(staging, &sbt_data);
 
         // --- create device-local SBT buffer ---
         let sbt_buffer = self.device.create_buffer(&BufferDescription {
-            usage: BufferUsage::TRANSFER_DST
-                | BufferUsage {
-                    flags: vk::BufferUsageFlags::SHADER_BINDING_TABLE_KHR,
-                },
+            usage: BufferUsage::TRANSFER_DST | BufferUsage::SHADER_BINDING_TABLE,
             size: sbt_size as u64,
             memory_type: MemoryType::DeviceLocal,
             create_mapped: false,
+            // Regions start at the buffer's address, which must be base aligned
+            min_alignment: Some(base_alignment as u64),
+            ..Default::default()
         });
 
         // copy staging -> device SBT buffer
-        let mut recorder = CommandRecorder {
-            handle: self.device.createcmd_recorder_data(QueueType::Transfer),
-            commad_buffers: smallvec![],
-            exec_command_buffers: smallvec![],
-            current_commad_buffer: vk::CommandBuffer::null(),
-            queue_type: QueueType::Transfer,
-            remembered_image_ids: HashMap::new(),
-            remembered_buffer_ids: HashMap::new(),
-            remembered_image_view_ids: HashMap::new(),
-            device: self.device.clone(),
-        };
-        recorder.begin_recording(CommandBufferUsage::OneTimeSubmit);
-        recorder.copy_buffer(&BufferCopyInfo {
-            src_buffer: staging,
-            dst_buffer: sbt_buffer,
-            size: sbt_size as u64,
-            src_offset: 0,
-            dst_offset: 0,
+        self.device.immediate_submit(QueueType::Transfer, |recorder| {
+            recorder.copy_buffer(&BufferCopyInfo {
+                src_buffer: staging,
+                dst_buffer: sbt_buffer,
+                size: sbt_size as u64,
+                src_offset: 0,
+                dst_offset: 0,
+            });
         });
-        let cmd = recorder.end_recording();
-
-        self.device.submit(&QueueSubmitInfo {
-            fence: None,
-            command_buffers: vec![cmd],
-            wait_semaphores: vec![],
-            signal_semaphores: vec![],
-        });
-        self.device.wait_queue(QueueType::Transfer);
         self.device.destroy_buffer(staging);
 
         // --- build SBT regions (device addresses) ---
@@ -602,12 +784,23 @@ impl InnerPipelineManager {
             stride: handle_stride as u64,
             size: hit_size as u64,
         };
+        // An empty region has to be all zeros
+        let callable_region = if callable_count == 0 {
+            vk::StridedDeviceAddressRegionKHR::default()
+        } else {
+            vk::StridedDeviceAddressRegionKHR {
+                device_address: base_addr + rgen_size as u64 + miss_size as u64 + hit_size as u64,
+                stride: handle_stride as u64,
+                size: callable_size as u64,
+            }
+        };
 
         ShaderBindingTable {
             buffer: sbt_buffer,
             rgen: rgen_region,
             miss: miss_region,
             hit: hit_region,
+            callable: callable_region,
         }
     }
 }
@@ -615,11 +808,19 @@ impl InnerPipelineManager {
 //// Helpers ////
 impl InnerPipelineManager {
     fn create_shader_module(&self, path: &str) -> vk::ShaderModule {
+        let shader_code = self.load_spv(path);
+
+        return self.create_module_from_spv(&shader_code);
+    }
+
+    fn load_spv(&self, path: &str) -> Vec<u32> {
         let shader = self.get_spv_path(path).unwrap_or_else(|| panic!("Wrong shader provided!!"));
 
-        let shader_code = InnerPipelineManager::read_spv_file(&shader);
+        return InnerPipelineManager::read_spv_file(&shader);
+    }
 
-        let module_create_info = vk::ShaderModuleCreateInfo::default().code(shader_code.as_slice());
+    fn create_module_from_spv(&self, shader_code: &[u32]) -> vk::ShaderModule {
+        let module_create_info = vk::ShaderModuleCreateInfo::default().code(shader_code);
 
         return unsafe { self.device.handle.create_shader_module(&module_create_info, None).expect("Failed to crate shader module") };
     }
@@ -647,11 +848,24 @@ pub(crate) struct InnerRasterizationPipeline {
     pub(crate) manager: Arc<InnerPipelineManager>,
 }
 
+impl Drop for InnerPipelineManager {
+    fn drop(&mut self) {
+        self.save_pipeline_cache(&Path::new(".cache").join("pipeline_cache.bin"));
+
+        unsafe {
+            self.device.handle.destroy_pipeline_cache(self.pipeline_cache, None);
+
+            for (_, layout) in self.pipeline_layouts.lock().unwrap().drain() {
+                self.device.handle.destroy_pipeline_layout(layout, None);
+            }
+        }
+    }
+}
+
 impl Drop for InnerRasterizationPipeline {
     fn drop(&mut self) {
         unsafe {
             self.manager.device.handle.destroy_pipeline(self.handle, None);
-            self.manager.device.handle.destroy_pipeline_layout(self.layout, None);
         }
     }
 }
@@ -659,6 +873,7 @@ impl Drop for InnerRasterizationPipeline {
 pub(crate) struct InnerComputePipeline {
     pub(crate) handle: vk::Pipeline,
     pub(crate) layout: vk::PipelineLayout,
+    pub(crate) workgroup_size: [u32; 3],
     pub(crate) desc: ComputePipelineDescription,
     pub(crate) manager: Arc<InnerPipelineManager>,
 }
@@ -667,14 +882,24 @@ impl Drop for InnerComputePipeline {
     fn drop(&mut self) {
         unsafe {
             self.manager.device.handle.destroy_pipeline(self.handle, None);
-            self.manager.device.handle.destroy_pipeline_layout(self.layout, None);
         }
     }
 }
 
-pub(crate) struct ShaderBindingTable {
-    pub(crate) buffer: BufferID,
-    pub(crate) rgen: vk::StridedDeviceAddressRegionKHR,
-    pub(crate) miss: vk::StridedDeviceAddressRegionKHR,
-    pub(crate) hit: vk::StridedDeviceAddressRegionKHR,
+pub(crate) struct InnerRayTracingPipeline {
+    pub(crate) handle: vk::Pipeline,
+    pub(crate) layout: vk::PipelineLayout,
+    pub(crate) desc: RayTracingPipelineDescription,
+    pub(crate) sbt: ShaderBindingTable,
+    pub(crate) manager: Arc<InnerPipelineManager>,
+}
+
+impl Drop for InnerRayTracingPipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.manager.device.handle.destroy_pipeline(self.handle, None);
+        }
+
+        self.manager.device.destroy_buffer(self.sbt.buffer);
+    }
 }
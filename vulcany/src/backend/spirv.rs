@@ -0,0 +1,331 @@
+use ahash::{HashMap, HashMapExt};
+
+use crate::{Format, VertexInputDescription};
+
+// Just enough of a SPIR-V reader to check descriptions against the compiled shaders
+
+const OP_ENTRY_POINT: u32 = 15;
+const OP_EXECUTION_MODE: u32 = 16;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const EXECUTION_MODEL_VERTEX: u32 = 0;
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+const STORAGE_CLASS_INPUT: u32 = 1;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_OFFSET: u32 = 35;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ScalarKind {
+    Float,
+    Int,
+    Uint,
+}
+
+#[derive(Clone, Copy)]
+enum SpvType {
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Array { element: u32, length: u32 },
+    Struct,
+    Pointer { pointee: u32 },
+}
+
+pub(crate) struct VertexShaderInput {
+    pub(crate) location: u32,
+    pub(crate) kind: ScalarKind,
+    pub(crate) components: u32,
+}
+
+pub(crate) struct SpirvReflection {
+    pub(crate) local_size: Option<[u32; 3]>,
+    pub(crate) inputs: Vec<VertexShaderInput>,
+    pub(crate) push_constant_size: Option<u32>,
+}
+
+impl SpirvReflection {
+    pub(crate) fn new(code: &[u32]) -> SpirvReflection {
+        let mut types: HashMap<u32, SpvType> = HashMap::new();
+        let mut struct_members: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut constants: HashMap<u32, u32> = HashMap::new();
+        let mut locations: HashMap<u32, u32> = HashMap::new();
+        let mut array_strides: HashMap<u32, u32> = HashMap::new();
+        let mut member_offsets: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut built_ins: Vec<u32> = Vec::new();
+        // (result type, id, storage class)
+        let mut variables: Vec<(u32, u32, u32)> = Vec::new();
+
+        let mut is_vertex = false;
+        let mut local_size = None;
+
+        // The first 5 words are the header
+        let mut i = 5;
+        while i < code.len() {
+            let word_count = (code[i] >> 16) as usize;
+            let opcode = code[i] & 0xffff;
+
+            if word_count == 0 || i + word_count > code.len() {
+                break;
+            }
+
+            let ops = &code[i + 1..i + word_count];
+
+            match opcode {
+                OP_ENTRY_POINT if !ops.is_empty() => is_vertex |= ops[0] == EXECUTION_MODEL_VERTEX,
+                OP_EXECUTION_MODE if ops.len() >= 5 && ops[1] == EXECUTION_MODE_LOCAL_SIZE => local_size = Some([ops[2], ops[3], ops[4]]),
+                OP_TYPE_INT if ops.len() >= 3 => {
+                    types.insert(ops[0], SpvType::Int { width: ops[1], signed: ops[2] == 1 });
+                }
+                OP_TYPE_FLOAT if ops.len() >= 2 => {
+                    types.insert(ops[0], SpvType::Float { width: ops[1] });
+                }
+                OP_TYPE_VECTOR if ops.len() >= 3 => {
+                    types.insert(ops[0], SpvType::Vector { component: ops[1], count: ops[2] });
+                }
+                OP_TYPE_MATRIX if ops.len() >= 3 => {
+                    types.insert(ops[0], SpvType::Matrix { column: ops[1], count: ops[2] });
+                }
+                OP_TYPE_ARRAY if ops.len() >= 3 => {
+                    types.insert(ops[0], SpvType::Array { element: ops[1], length: ops[2] });
+                }
+                OP_TYPE_STRUCT if !ops.is_empty() => {
+                    types.insert(ops[0], SpvType::Struct);
+                    struct_members.insert(ops[0], ops[1..].to_vec());
+                }
+                OP_TYPE_POINTER if ops.len() >= 3 => {
+                    types.insert(ops[0], SpvType::Pointer { pointee: ops[2] });
+                }
+                OP_CONSTANT if ops.len() >= 3 => {
+                    constants.insert(ops[1], ops[2]);
+                }
+                OP_VARIABLE if ops.len() >= 3 => variables.push((ops[0], ops[1], ops[2])),
+                OP_DECORATE if ops.len() >= 2 => match ops[1] {
+                    DECORATION_LOCATION if ops.len() >= 3 => {
+                        locations.insert(ops[0], ops[2]);
+                    }
+                    DECORATION_ARRAY_STRIDE if ops.len() >= 3 => {
+                        array_strides.insert(ops[0], ops[2]);
+                    }
+                    DECORATION_BUILT_IN => built_ins.push(ops[0]),
+                    _ => {}
+                },
+                OP_MEMBER_DECORATE if ops.len() >= 4 && ops[2] == DECORATION_OFFSET => {
+                    member_offsets.insert((ops[0], ops[1]), ops[3]);
+                }
+                _ => {}
+            }
+
+            i += word_count;
+        }
+
+        let reflect = Reflector {
+            types: &types,
+            struct_members: &struct_members,
+            constants: &constants,
+            array_strides: &array_strides,
+            member_offsets: &member_offsets,
+        };
+
+        let mut inputs = Vec::new();
+        let mut push_constant_size = None;
+
+        for &(pointer_type, id, storage_class) in &variables {
+            let pointee = match types.get(&pointer_type) {
+                Some(SpvType::Pointer { pointee, .. }) => *pointee,
+                _ => continue,
+            };
+
+            if storage_class == STORAGE_CLASS_INPUT && is_vertex && !built_ins.contains(&id) {
+                let (Some(&location), Some((kind, components))) = (locations.get(&id), reflect.shape(pointee)) else {
+                    continue;
+                };
+
+                inputs.push(VertexShaderInput {
+                    location: location,
+                    kind: kind,
+                    components: components,
+                });
+            } else if storage_class == STORAGE_CLASS_PUSH_CONSTANT {
+                push_constant_size = reflect.size(pointee);
+            }
+        }
+
+        inputs.sort_by_key(|input| input.location);
+
+        return SpirvReflection {
+            local_size: local_size,
+            inputs: inputs,
+            push_constant_size: push_constant_size,
+        };
+    }
+
+    /// Prints a warning for every vertex input of the shader that `vertex_input` does not feed with a matching format
+    pub(crate) fn check_vertex_input(&self, shader: &str, vertex_input: &VertexInputDescription) {
+        for input in &self.inputs {
+            let Some(attribute) = vertex_input.attributes.iter().find(|a| a.location == input.location) else {
+                eprintln!("[{}] vertex input at location {} has no matching vertex attribute", shader, input.location);
+                continue;
+            };
+
+            let Some((kind, components)) = vertex_format_shape(attribute.format) else {
+                eprintln!("[{}] vertex attribute at location {} uses {:?}, which is not a vertex format", shader, input.location, attribute.format);
+                continue;
+            };
+
+            if kind != input.kind || components != input.components {
+                eprintln!(
+                    "[{}] vertex attribute at location {} is {:?} ({} x {:?}) but the shader reads {} x {:?}",
+                    shader, input.location, attribute.format, components, kind, input.components, input.kind
+                );
+            }
+        }
+    }
+
+    /// Prints a warning when the shader uses more push constant bytes than the pipeline declares
+    pub(crate) fn check_push_constants(&self, shader: &str, offset: u32, size: u32) {
+        if let Some(used) = self.push_constant_size.filter(|&used| used > offset + size) {
+            eprintln!("[{}] shader uses {} bytes of push constants but the pipeline only declares {}..{}", shader, used, offset, offset + size);
+        }
+    }
+}
+
+struct Reflector<'a> {
+    types: &'a HashMap<u32, SpvType>,
+    struct_members: &'a HashMap<u32, Vec<u32>>,
+    constants: &'a HashMap<u32, u32>,
+    array_strides: &'a HashMap<u32, u32>,
+    member_offsets: &'a HashMap<(u32, u32), u32>,
+}
+
+impl Reflector<'_> {
+    fn shape(&self, ty: u32) -> Option<(ScalarKind, u32)> {
+        return match *self.types.get(&ty)? {
+            SpvType::Float { .. } => Some((ScalarKind::Float, 1)),
+            SpvType::Int { signed: true, .. } => Some((ScalarKind::Int, 1)),
+            SpvType::Int { signed: false, .. } => Some((ScalarKind::Uint, 1)),
+            SpvType::Vector { component, count } => self.shape(component).map(|(kind, _)| (kind, count)),
+            _ => None,
+        };
+    }
+
+    fn size(&self, ty: u32) -> Option<u32> {
+        return match *self.types.get(&ty)? {
+            SpvType::Int { width, .. } | SpvType::Float { width } => Some(width / 8),
+            SpvType::Vector { component, count } => Some(self.size(component)? * count),
+            SpvType::Matrix { column, count } => Some(self.size(column)? * count),
+            SpvType::Array { element, length } => {
+                let stride = match self.array_strides.get(&ty) {
+                    Some(&stride) => stride,
+                    None => self.size(element)?,
+                };
+                Some(stride * self.constants.get(&length)?)
+            }
+            SpvType::Struct => {
+                let members = self.struct_members.get(&ty)?;
+                let mut end = 0;
+                for (index, &member) in members.iter().enumerate() {
+                    let offset = self.member_offsets.get(&(ty, index as u32)).copied().unwrap_or(end);
+                    end = end.max(offset + self.size(member)?);
+                }
+                Some(end)
+            }
+            SpvType::Pointer { .. } => Some(8),
+        };
+    }
+}
+
+fn vertex_format_shape(format: Format) -> Option<(ScalarKind, u32)> {
+    return match format {
+        Format::R8Unorm | Format::R32Float => Some((ScalarKind::Float, 1)),
+        Format::Rg8Unorm | Format::Rg32Float => Some((ScalarKind::Float, 2)),
+        Format::Rgb565Unorm | Format::Rgb32Float | Format::R11G11B10Float => Some((ScalarKind::Float, 3)),
+        Format::Rgba8Unorm | Format::Bgra8Unorm | Format::A2Bgr10Unorm | Format::Rgba8Srgb | Format::Bgra8Srgb | Format::Rgba16Float | Format::Rgba32Float => Some((ScalarKind::Float, 4)),
+        Format::Rgba8Uint => Some((ScalarKind::Uint, 4)),
+        Format::Rgba32Sint => Some((ScalarKind::Int, 4)),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: [u32; 5] = [0x0723_0203, 0x0001_0500, 0, 100, 0];
+
+    fn module(instructions: &[(u32, &[u32])]) -> Vec<u32> {
+        let mut code = HEADER.to_vec();
+        for &(opcode, ops) in instructions {
+            code.push(((ops.len() as u32 + 1) << 16) | opcode);
+            code.extend_from_slice(ops);
+        }
+        return code;
+    }
+
+    // float3 vertex input at location 2
+    fn vertex_module() -> Vec<u32> {
+        return module(&[
+            (OP_ENTRY_POINT, &[EXECUTION_MODEL_VERTEX, 1]),
+            (OP_DECORATE, &[5, DECORATION_LOCATION, 2]),
+            (OP_TYPE_FLOAT, &[2, 32]),
+            (OP_TYPE_VECTOR, &[3, 2, 3]),
+            (OP_TYPE_POINTER, &[4, STORAGE_CLASS_INPUT, 3]),
+            (OP_VARIABLE, &[4, 5, STORAGE_CLASS_INPUT]),
+        ]);
+    }
+
+    #[test]
+    fn reads_vertex_input_location() {
+        let reflection = SpirvReflection::new(&vertex_module());
+
+        assert_eq!(reflection.inputs.len(), 1);
+        assert_eq!(reflection.inputs[0].location, 2);
+        assert_eq!(reflection.inputs[0].kind, ScalarKind::Float);
+        assert_eq!(reflection.inputs[0].components, 3);
+    }
+
+    #[test]
+    fn sizes_push_constants_with_member_offsets() {
+        // struct { float4 at 0; uint at 16 }
+        let code = module(&[
+            (OP_MEMBER_DECORATE, &[7, 0, DECORATION_OFFSET, 0]),
+            (OP_MEMBER_DECORATE, &[7, 1, DECORATION_OFFSET, 16]),
+            (OP_TYPE_FLOAT, &[2, 32]),
+            (OP_TYPE_VECTOR, &[3, 2, 4]),
+            (OP_TYPE_INT, &[6, 32, 0]),
+            (OP_TYPE_STRUCT, &[7, 3, 6]),
+            (OP_TYPE_POINTER, &[8, STORAGE_CLASS_PUSH_CONSTANT, 7]),
+            (OP_VARIABLE, &[8, 9, STORAGE_CLASS_PUSH_CONSTANT]),
+        ]);
+
+        assert_eq!(SpirvReflection::new(&code).push_constant_size, Some(20));
+    }
+
+    #[test]
+    fn stops_at_a_truncated_instruction() {
+        let mut code = vertex_module();
+        // Claims 4 words but only 2 are left
+        code.push((4 << 16) | OP_DECORATE);
+        code.push(5);
+
+        let reflection = SpirvReflection::new(&code);
+        assert_eq!(reflection.inputs.len(), 1);
+
+        assert!(SpirvReflection::new(&HEADER[..3]).inputs.is_empty());
+    }
+}
@@ -1,9 +1,9 @@
 use ash::vk;
 use crossbeam::queue::ArrayQueue;
 use std::sync::Arc;
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicU32, AtomicUsize};
 
-use crate::{ImageID, ImageViewID, Semaphore};
+use crate::{ImageID, ImageUsage, ImageViewID, Semaphore};
 
 use crate::backend::device::InnerDevice;
 
@@ -16,6 +16,13 @@ pub(crate) struct InnerSwapchain {
     pub(crate) image_semaphore: Vec<Semaphore>,
     pub(crate) preset_semaphore: Vec<Semaphore>,
     pub(crate) timeline: AtomicUsize,
+    pub(crate) format: vk::Format,
+    pub(crate) extent: vk::Extent2D,
+    pub(crate) usage: ImageUsage,
+    // Index of the image returned by the latest acquire_image
+    pub(crate) last_acquired: AtomicU32,
+    // Set by recreate_swapchain when the negotiated format differs from the old swapchain
+    pub(crate) format_changed: bool,
     pub(crate) device: Arc<InnerDevice>,
 }
 
@@ -29,9 +36,15 @@ impl InnerSwapchain {
         let next_timeline_index = (timeline_index + 1) % self.image_semaphore.len();
         self.timeline.store(next_timeline_index, std::sync::atomic::Ordering::Relaxed);
 
-        let (index, _) = unsafe { self.swapchain_loader.acquire_next_image2(&acquire_info).expect("Failed to acquire next image") };
+        let (index, _) = unsafe {
+            self.swapchain_loader
+                .acquire_next_image2(&acquire_info)
+                .map_err(|e| self.device.check_device_lost(e))
+                .expect("Failed to acquire next image")
+        };
 
         self.curr_img_indeices.push(index);
+        self.last_acquired.store(index, std::sync::atomic::Ordering::Relaxed);
 
         //println!("{} {}", timeline_index, index);
 
@@ -52,8 +65,19 @@ impl InnerSwapchain {
         let present_info = vk::PresentInfoKHR::default().swapchains(&handle).image_indices(&index).wait_semaphores(&sem);
 
         unsafe {
-            self.swapchain_loader.queue_present(self.device.graphics_queue, &present_info).expect("Failed to preset image!!");
+            self.swapchain_loader
+                .queue_present(self.device.present_queue, &present_info)
+                .map_err(|e| self.device.check_device_lost(e))
+                .expect("Failed to preset image!!");
         }
+
+        self.device.advance_frame();
+    }
+}
+
+impl InnerSwapchain {
+    pub(crate) fn is_srgb(&self) -> bool {
+        return matches!(self.format, vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB | vk::Format::A8B8G8R8_SRGB_PACK32);
     }
 }
 
@@ -1,17 +1,21 @@
 use std::sync::Arc;
 
-use ahash::HashMap;
+use ahash::{HashMap, HashMapExt};
 use ash::vk;
-use smallvec::SmallVec;
+use delegate::delegate;
+use smallvec::{SmallVec, smallvec};
 
 use crate::{
-    Barrier, BlitInfo, BufferCopyInfo, BufferID, BufferImageCopyInfo, CommandBufferUsage, DispatchIndirectInfo, DispatchInfo, ImageCopyInfo, ImageID, ImageViewID, IndexType, Pipeline, QueueType,
-    RenderingBeginInfo, backend::device::InnerDevice,
+    AccessType, Barrier, BlitInfo, BufferCopyInfo, BufferCopyRegion, BufferID, BufferImageCopyInfo, ClearValue, CommandBufferUsage, CommandPoolFlags, ComputePipeline, CullMode, DispatchIndirectInfo,
+    DispatchInfo, Format, FrontFace, ImageAspect, ImageBarrier, ImageBufferCopyInfo, ImageCopyInfo, ImageID, ImageLayout, ImageViewID, IndexType, LoadOp, MAX_PUSH_DESCRIPTOR_BUFFERS,
+    PUSH_DESCRIPTOR_SET, PingPongComputeInfo, Pipeline, PipelineStage, PolygonMode, QueryCopyInfo, QueryType, QueueType, RenderingAttachment, RenderingBeginInfo, RenderingFlags, ResolveMode,
+    SecondaryInheritanceInfo, ShaderBindingTable, backend::device::InnerDevice,
 };
 
 /// Not thread safe!!
 /// This is because normal vulkan command pools arent hread safe either
 /// Hence it felt unnecessary to have an inner struct
+/// For parallel recording give every thread its own recorder, see `Device::create_command_recorder_for_thread`
 pub struct CommandRecorder {
     pub(crate) handle: vk::CommandPool,
     pub(crate) commad_buffers: SmallVec<[vk::CommandBuffer; 2]>,
@@ -21,6 +25,17 @@ pub struct CommandRecorder {
     pub(crate) remembered_image_ids: HashMap<ImageID, vk::Image>,
     pub(crate) remembered_buffer_ids: HashMap<BufferID, vk::Buffer>,
     pub(crate) remembered_image_view_ids: HashMap<ImageViewID, vk::ImageView>,
+    // Last layout each image was transitioned to by this recorder, used by transition_image
+    pub(crate) tracked_layouts: HashMap<ImageID, ImageLayout>,
+    // Suspend/resume state of the buffer being recorded
+    pub(crate) resumes_rendering: bool,
+    pub(crate) suspends_rendering: bool,
+    pub(crate) has_rendered: bool,
+    // View masks of the current rendering and of the bound rasterization pipeline, which have to match
+    pub(crate) rendering_view_mask: Option<u32>,
+    pub(crate) pipeline_view_mask: Option<u32>,
+    pub(crate) level: vk::CommandBufferLevel,
+    pub(crate) pool_flags: CommandPoolFlags,
     pub(crate) device: Arc<InnerDevice>,
 }
 
@@ -36,9 +51,38 @@ impl CommandRecorder {
         self.commad_buffers.append(&mut self.exec_command_buffers);
     }
 
+    /// Resets a single finished buffer so it can be recorded again, other buffers of the recorder stay untouched.
+    /// The recorder has to be created with `CommandPoolFlags::RESET_INDIVIDUAL` and the buffer must no longer be in flight
+    pub fn reset_buffer(&mut self, command_buffer: ExecutableCommandBuffer) {
+        assert!(
+            self.pool_flags.contains(CommandPoolFlags::RESET_INDIVIDUAL),
+            "Command recorder was not created with CommandPoolFlags::RESET_INDIVIDUAL, use reset instead"
+        );
+
+        let index = self
+            .exec_command_buffers
+            .iter()
+            .position(|cmd| *cmd == command_buffer.handle)
+            .expect("Command buffer was not recorded by this recorder or was already reset");
+
+        unsafe {
+            self.device
+                .handle
+                .reset_command_buffer(command_buffer.handle, vk::CommandBufferResetFlags::empty())
+                .expect("Failed to reset command buffer");
+        }
+
+        self.exec_command_buffers.swap_remove(index);
+        self.commad_buffers.push(command_buffer.handle);
+    }
+
     pub fn begin_recording(&mut self, usage: CommandBufferUsage) {
         let begin_info = vk::CommandBufferBeginInfo::default().flags(usage.to_vk_flags());
 
+        self.begin_command_buffer(&begin_info);
+    }
+
+    pub(crate) fn begin_command_buffer(&mut self, begin_info: &vk::CommandBufferBeginInfo) {
         if self.commad_buffers.is_empty() {
             self.current_commad_buffer = self.new_cmd_buffer();
         } else {
@@ -46,8 +90,14 @@ impl CommandRecorder {
         }
 
         unsafe {
-            self.device.handle.begin_command_buffer(self.current_commad_buffer, &begin_info).expect("Failed to begin cmd buffer!!!");
+            self.device.handle.begin_command_buffer(self.current_commad_buffer, begin_info).expect("Failed to begin cmd buffer!!!");
         }
+
+        self.resumes_rendering = false;
+        self.suspends_rendering = false;
+        self.has_rendered = false;
+        self.rendering_view_mask = None;
+        self.pipeline_view_mask = None;
     }
 
     pub fn end_recording(&mut self) -> ExecutableCommandBuffer {
@@ -62,14 +112,75 @@ impl CommandRecorder {
         return ExecutableCommandBuffer {
             handle: return_buffer,
             queue_type: self.queue_type,
+            resumes_rendering: self.resumes_rendering,
+            suspends_rendering: self.suspends_rendering,
+            secondary: self.level == vk::CommandBufferLevel::SECONDARY,
         };
     }
 
     // Dynamic rendering
+    /// A rendering begun with `RenderingFlags::Suspending` must be resumed with `RenderingFlags::Resuming`,
+    /// either later in the same command buffer or at the start of the next command buffer in the same submission
     pub fn begin_rendering(&mut self, rendering_begin_info: &RenderingBeginInfo) {
+        let render_area = vk::Rect2D {
+            extent: rendering_begin_info.render_area.extent.to_vk(),
+            offset: rendering_begin_info.render_area.offset.to_vk(),
+        };
+
+        self.record_begin_rendering(rendering_begin_info, render_area);
+    }
+
+    /// Same as `begin_rendering`, but a zero sized `render_area` is replaced by the size of the first attachment
+    pub fn begin_rendering_auto(&mut self, rendering_begin_info: &RenderingBeginInfo) {
+        let mut render_area = vk::Rect2D {
+            extent: rendering_begin_info.render_area.extent.to_vk(),
+            offset: rendering_begin_info.render_area.offset.to_vk(),
+        };
+
+        if render_area.extent.width == 0 || render_area.extent.height == 0 {
+            let image_view = rendering_begin_info
+                .color_attachments
+                .first()
+                .or(rendering_begin_info.depth_attachment.as_ref())
+                .or(rendering_begin_info.stencil_attachment.as_ref())
+                .expect("begin_rendering_auto needs at least one attachment to derive the render area from")
+                .image_view;
+
+            let pool = self.device.image_view_pool.read().unwrap();
+            let view = pool
+                .try_get_ref(image_view.id)
+                .unwrap_or_else(|| panic!("Image view used in command recording was destroyed or never created (id: {})", image_view.id));
+
+            render_area.extent = view.extent;
+        }
+
+        self.record_begin_rendering(rendering_begin_info, render_area);
+    }
+
+    fn record_begin_rendering(&mut self, rendering_begin_info: &RenderingBeginInfo, render_area: vk::Rect2D) {
+        match rendering_begin_info.rendering_flags {
+            RenderingFlags::Resuming | RenderingFlags::ResumingAndSuspending => {
+                // Only the first rendering of a buffer resumes from the previous buffer, later ones have to follow a suspended rendering
+                if self.has_rendered {
+                    assert!(self.suspends_rendering, "Resumed a rendering that does not directly follow a suspended rendering");
+                } else {
+                    self.resumes_rendering = true;
+                }
+            }
+            _ => assert!(!self.suspends_rendering, "Began a new rendering while a suspended rendering was not resumed"),
+        }
+
+        self.suspends_rendering = matches!(rendering_begin_info.rendering_flags, RenderingFlags::Suspending | RenderingFlags::ResumingAndSuspending);
+        self.has_rendered = true;
+
+        self.rendering_view_mask = Some(rendering_begin_info.view_mask);
+        self.check_view_mask();
+
         let mut color_attachment_info = SmallVec::<[vk::RenderingAttachmentInfo; 4]>::new();
 
         for color_attachement in &rendering_begin_info.color_attachments {
+            Self::validate_clear_value(color_attachement, false, "color");
+
             let image_view = self.check_and_remeber_image_view_id(color_attachement.image_view);
             let resolve_image_view = if color_attachement.resolve_image_view.is_some() {
                 self.check_and_remeber_image_view_id(color_attachement.resolve_image_view.unwrap())
@@ -95,10 +206,7 @@ impl CommandRecorder {
             .color_attachments(color_attachment_info.as_slice())
             .layer_count(rendering_begin_info.layer_count)
             .view_mask(rendering_begin_info.view_mask)
-            .render_area(vk::Rect2D {
-                extent: rendering_begin_info.render_area.extent.to_vk(),
-                offset: rendering_begin_info.render_area.offset.to_vk(),
-            });
+            .render_area(render_area);
 
         let depth_attachment_info: vk::RenderingAttachmentInfo;
         let stencil_attachment_info: vk::RenderingAttachmentInfo;
@@ -107,6 +215,9 @@ impl CommandRecorder {
         if rendering_begin_info.depth_attachment.is_some() {
             let depth_attachment = rendering_begin_info.depth_attachment.as_ref().unwrap();
 
+            self.validate_depth_stencil_resolve(depth_attachment, self.device.physical_device.depth_stencil_resolve_props.supported_depth_resolve_modes, "depth");
+            Self::validate_clear_value(depth_attachment, true, "depth");
+
             let image_view = self.check_and_remeber_image_view_id(depth_attachment.image_view);
             let resolve_image_view = if depth_attachment.resolve_image_view.is_some() {
                 self.check_and_remeber_image_view_id(depth_attachment.resolve_image_view.unwrap())
@@ -130,6 +241,9 @@ impl CommandRecorder {
         if rendering_begin_info.stencil_attachment.is_some() {
             let stencil_attachment = rendering_begin_info.stencil_attachment.as_ref().unwrap();
 
+            self.validate_depth_stencil_resolve(stencil_attachment, self.device.physical_device.depth_stencil_resolve_props.supported_stencil_resolve_modes, "stencil");
+            Self::validate_clear_value(stencil_attachment, true, "stencil");
+
             let image_view = self.check_and_remeber_image_view_id(stencil_attachment.image_view);
             let resolve_image_view = if stencil_attachment.resolve_image_view.is_some() {
                 self.check_and_remeber_image_view_id(stencil_attachment.resolve_image_view.unwrap())
@@ -150,38 +264,159 @@ impl CommandRecorder {
             rendering_info = rendering_info.stencil_attachment(&stencil_attachment_info);
         }
 
+        // Depth and stencil aspects can only be resolved with different modes if the device allows it
+        if let (Some(depth), Some(stencil)) = (&rendering_begin_info.depth_attachment, &rendering_begin_info.stencil_attachment)
+            && depth.resolve_mode != stencil.resolve_mode
+        {
+            let props = &self.device.physical_device.depth_stencil_resolve_props;
+            let one_is_none = depth.resolve_mode == ResolveMode::None || stencil.resolve_mode == ResolveMode::None;
+
+            assert!(
+                props.independent_resolve == vk::TRUE || (one_is_none && props.independent_resolve_none == vk::TRUE),
+                "Device does not support different depth and stencil resolve modes"
+            );
+        }
+
         unsafe {
             self.device.handle.cmd_begin_rendering(self.current_commad_buffer, &rendering_info);
         }
     }
 
-    pub fn end_rendering(&self) {
+    pub fn end_rendering(&mut self) {
         unsafe {
             self.device.handle.cmd_end_rendering(self.current_commad_buffer);
         }
+
+        // The pipeline bound in this rendering is not checked against the next one, only pipelines bound after this are
+        self.rendering_view_mask = None;
+        self.pipeline_view_mask = None;
+    }
+
+    // A rasterization pipeline can only be used in a rendering with the same view mask
+    fn check_view_mask(&self) {
+        if let (Some(rendering), Some(pipeline)) = (self.rendering_view_mask, self.pipeline_view_mask) {
+            assert!(
+                rendering == pipeline,
+                "Rendering view mask {:#b} does not match the view mask {:#b} of the bound pipeline",
+                rendering,
+                pipeline
+            );
+        }
+    }
+
+    /// Executes secondary command buffers recorded by a `SecondaryCommandRecorder`.
+    /// Inside a rendering, it must have been begun with `RenderingFlags::ContentsSecondaryCommandBuffers`
+    pub fn execute_secondary(&mut self, command_buffers: &[ExecutableCommandBuffer]) {
+        let handles: SmallVec<[vk::CommandBuffer; 4]> = command_buffers
+            .iter()
+            .map(|cb| {
+                assert!(cb.secondary, "Tried executing a primary command buffer as a secondary command buffer");
+                assert!(cb.queue_type == self.queue_type, "Secondary command buffer was recorded for a different queue");
+                cb.handle
+            })
+            .collect();
+
+        unsafe {
+            self.device.handle.cmd_execute_commands(self.current_commad_buffer, &handles);
+        }
     }
 
     //// Bind Commands ////
+    /// Pipeline must be created with `dynamic_line_width`.
+    /// Widths other than 1.0 need the wideLines feature
+    pub fn set_line_width(&self, line_width: f32) {
+        assert!(
+            line_width == 1.0 || self.device.physical_device.features.wide_lines == vk::TRUE,
+            "Line width {} needs the wideLines feature which this device does not support",
+            line_width
+        );
+
+        unsafe {
+            self.device.handle.cmd_set_line_width(self.current_commad_buffer, line_width);
+        }
+    }
+
+    /// Pipeline must be created with `dynamic_depth_bias`.
+    /// A non zero clamp needs the depthBiasClamp feature
+    pub fn set_depth_bias(&self, constant_factor: f32, clamp: f32, slope_factor: f32) {
+        assert!(
+            clamp == 0.0 || self.device.physical_device.features.depth_bias_clamp == vk::TRUE,
+            "Depth bias clamp needs the depthBiasClamp feature which this device does not support"
+        );
+
+        unsafe {
+            self.device.handle.cmd_set_depth_bias(self.current_commad_buffer, constant_factor, clamp, slope_factor);
+        }
+    }
+
+    /// Pipeline must be created with `dynamic_cull_mode`
+    pub fn set_cull_mode(&self, cull_mode: CullMode) {
+        unsafe {
+            self.device.handle.cmd_set_cull_mode(self.current_commad_buffer, cull_mode.to_vk_flag());
+        }
+    }
+
+    /// Pipeline must be created with `dynamic_front_face`
+    pub fn set_front_face(&self, front_face: FrontFace) {
+        unsafe {
+            self.device.handle.cmd_set_front_face(self.current_commad_buffer, front_face.to_vk_flag());
+        }
+    }
+
+    /// Pipeline must be created with `dynamic_stencil_reference`, sets the reference of both faces
+    pub fn set_stencil_reference(&self, reference: u32) {
+        unsafe {
+            self.device
+                .handle
+                .cmd_set_stencil_reference(self.current_commad_buffer, vk::StencilFaceFlags::FRONT_AND_BACK, reference);
+        }
+    }
+
+    /// Pipeline must be created with `dynamic_polygon_mode`, which needs VK_EXT_extended_dynamic_state3.
+    /// Without it create a second pipeline with the other `PolygonMode` and bind that instead
+    pub fn set_polygon_mode(&self, polygon_mode: PolygonMode) {
+        let eds3 = self
+            .device
+            .extended_dynamic_state3
+            .as_ref()
+            .expect("Dynamic polygon mode is not supported on this device, use a separate pipeline instead");
+
+        unsafe {
+            eds3.cmd_set_polygon_mode(self.current_commad_buffer, polygon_mode.to_vk_flag());
+        }
+    }
+
+    /// Viewport and scissor covering `width` x `height` from the origin
     pub fn set_viewport_and_scissor(&self, width: u32, height: u32) {
+        self.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
+        self.set_scissor(0, 0, width, height);
+    }
+
+    /// A negative `height` flips Y to match GL style coordinates, pass the bottom edge as `y` in that case
+    pub fn set_viewport(&self, x: f32, y: f32, width: f32, height: f32, min_depth: f32, max_depth: f32) {
         unsafe {
             self.device.handle.cmd_set_viewport(
                 self.current_commad_buffer,
                 0,
                 &[vk::Viewport {
-                    x: 0.0,
-                    y: 0.0,
-                    width: width as f32,
-                    height: height as f32,
-                    max_depth: 1.0,
-                    min_depth: 0.0,
+                    x: x,
+                    y: y,
+                    width: width,
+                    height: height,
+                    min_depth: min_depth,
+                    max_depth: max_depth,
                 }],
             );
+        }
+    }
 
+    pub fn set_scissor(&self, x: i32, y: i32, width: u32, height: u32) {
+        unsafe {
             self.device.handle.cmd_set_scissor(
                 self.current_commad_buffer,
                 0,
                 &[vk::Rect2D {
-                    offset: vk::Offset2D { x: 0, y: 0 },
+                    offset: vk::Offset2D { x: x, y: y },
                     extent: vk::Extent2D { width: width, height: height },
                 }],
             );
@@ -189,7 +424,10 @@ impl CommandRecorder {
     }
 
     pub fn set_push_constants(&self, push_constants: &impl bytemuck::Pod, pipeline: &impl Pipeline) {
-        let data = bytemuck::bytes_of(push_constants);
+        self.set_push_constant_bytes(bytemuck::bytes_of(push_constants), pipeline);
+    }
+
+    pub fn set_push_constant_bytes(&self, data: &[u8], pipeline: &impl Pipeline) {
         unsafe {
             self.device
                 .handle
@@ -197,7 +435,12 @@ impl CommandRecorder {
         }
     }
 
-    pub fn bind_pipeline(&self, pipeline: &impl Pipeline) {
+    pub fn bind_pipeline(&mut self, pipeline: &impl Pipeline) {
+        if pipeline.get_bind_point() == vk::PipelineBindPoint::GRAPHICS {
+            self.pipeline_view_mask = pipeline.get_view_mask();
+            self.check_view_mask();
+        }
+
         unsafe {
             self.device.handle.cmd_bind_pipeline(self.current_commad_buffer, pipeline.get_bind_point(), pipeline.get_handle());
             self.device.handle.cmd_bind_descriptor_sets(
@@ -211,6 +454,40 @@ impl CommandRecorder {
         }
     }
 
+    /// Binds `range` bytes of `buffer` as the uniform buffer at `binding` of set `PUSH_DESCRIPTOR_SET`.
+    /// Requires `Device::supports_push_descriptors`
+    pub fn push_descriptor_buffer(&mut self, pipeline: &impl Pipeline, binding: u32, buffer_id: BufferID, offset: u64, range: u64) {
+        assert!(
+            binding < MAX_PUSH_DESCRIPTOR_BUFFERS,
+            "Push descriptor binding {} is out of range, only {} are available",
+            binding,
+            MAX_PUSH_DESCRIPTOR_BUFFERS
+        );
+
+        let buffer = self.check_and_remeber_buffer_id(buffer_id);
+
+        let push_descriptor = match &self.device.push_descriptor {
+            Some(p) => p,
+            None => panic!("Push descriptors are not supported on this device"),
+        };
+
+        let buffer_info = [vk::DescriptorBufferInfo::default().buffer(buffer).offset(offset).range(range)];
+        let write = vk::WriteDescriptorSet::default()
+            .dst_binding(binding)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&buffer_info);
+
+        unsafe {
+            push_descriptor.cmd_push_descriptor_set(
+                self.current_commad_buffer,
+                pipeline.get_bind_point(),
+                pipeline.get_layout(),
+                PUSH_DESCRIPTOR_SET,
+                std::slice::from_ref(&write),
+            );
+        }
+    }
+
     pub fn bind_vertex_buffer(&mut self, buffer_id: BufferID, offset: u64) {
         let buffer = [self.check_and_remeber_buffer_id(buffer_id)];
         let offset = [offset];
@@ -220,7 +497,44 @@ impl CommandRecorder {
         }
     }
 
+    /// Binds (buffer, offset) to consecutive bindings starting at `first_binding`
+    pub fn bind_vertex_buffers(&mut self, first_binding: u32, bindings: &[(BufferID, u64)]) {
+        let mut buffers = SmallVec::<[vk::Buffer; 4]>::new();
+        let mut offsets = SmallVec::<[vk::DeviceSize; 4]>::new();
+
+        for &(buffer_id, offset) in bindings {
+            buffers.push(self.check_and_remeber_buffer_id(buffer_id));
+            offsets.push(offset);
+        }
+
+        unsafe {
+            self.device.handle.cmd_bind_vertex_buffers(self.current_commad_buffer, first_binding, &buffers, &offsets);
+        }
+    }
+
+    /// Binds (buffer, offset, stride) to consecutive bindings starting at 0.
+    /// The bound pipeline must be created with `dynamic_vertex_stride`
+    pub fn bind_vertex_buffers_with_stride(&mut self, bindings: &[(BufferID, u64, u64)]) {
+        let mut buffers = SmallVec::<[vk::Buffer; 4]>::new();
+        let mut offsets = SmallVec::<[vk::DeviceSize; 4]>::new();
+        let mut strides = SmallVec::<[vk::DeviceSize; 4]>::new();
+
+        for &(buffer_id, offset, stride) in bindings {
+            buffers.push(self.check_and_remeber_buffer_id(buffer_id));
+            offsets.push(offset);
+            strides.push(stride);
+        }
+
+        unsafe {
+            self.device.handle.cmd_bind_vertex_buffers2(self.current_commad_buffer, 0, &buffers, &offsets, None, Some(&strides));
+        }
+    }
+
     pub fn bind_index_buffer(&mut self, buffer_id: BufferID, offset: u64, index_type: IndexType) {
+        if matches!(index_type, IndexType::Uint8) && !self.device.supports_index_type_uint8() {
+            panic!("IndexType::Uint8 needs VK_EXT_index_type_uint8, which this device does not support");
+        }
+
         let buffer = self.check_and_remeber_buffer_id(buffer_id);
 
         unsafe {
@@ -243,6 +557,15 @@ impl CommandRecorder {
         }
     }
 
+    /// Launches task (or mesh) workgroups, requires `Device::supports_mesh_shaders`
+    pub fn draw_mesh_tasks(&self, group_count_x: u32, group_count_y: u32, group_count_z: u32) {
+        let mesh_shader = self.device.mesh_shader.as_ref().expect("Mesh shaders were not enabled, set DeviceDescription::mesh_shaders");
+
+        unsafe {
+            mesh_shader.cmd_draw_mesh_tasks(self.current_commad_buffer, group_count_x, group_count_y, group_count_z);
+        }
+    }
+
     //// Compute commands ////
     pub fn dispatch(&self, info: &DispatchInfo) {
         unsafe {
@@ -250,6 +573,18 @@ impl CommandRecorder {
         }
     }
 
+    /// Dispatches enough workgroups of `pipeline` to cover `width` x `height` x `depth` invocations.
+    /// Partial groups are rounded up, the shader has to skip invocations outside the range
+    pub fn dispatch_for(&self, pipeline: &ComputePipeline, width: u32, height: u32, depth: u32) {
+        let [x, y, z] = pipeline.workgroup_size();
+
+        self.dispatch(&DispatchInfo {
+            group_count_x: width.div_ceil(x),
+            group_count_y: height.div_ceil(y),
+            group_count_z: depth.div_ceil(z),
+        });
+    }
+
     pub fn dispatch_indirect(&mut self, info: &DispatchIndirectInfo) {
         let buffer = self.check_and_remeber_buffer_id(info.buffer);
         unsafe {
@@ -257,8 +592,153 @@ impl CommandRecorder {
         }
     }
 
+    /// Iteration `i` reads `images[i % 2]` and writes `images[(i + 1) % 2]`. The pipeline's push constants
+    /// must start with two u32s, which receive the storage indices of the read and the written image.
+    /// Returns the index into `images` of the image holding the final result
+    pub fn ping_pong_compute(&mut self, pipeline: &ComputePipeline, info: &PingPongComputeInfo) -> usize {
+        self.bind_pipeline(pipeline);
+
+        for i in 0..info.iterations as usize {
+            let (read, write) = (i % 2, (i + 1) % 2);
+
+            self.set_push_constants(&[info.storage_indices[read], info.storage_indices[write]], pipeline);
+            self.dispatch(&info.dispatch);
+
+            // The next iteration reads what was just written, and writing the other image only needs the execution dependency
+            self.pipeline_barrier(&[Barrier::Image(ImageBarrier {
+                image: info.images[write],
+                old_layout: ImageLayout::General,
+                new_layout: ImageLayout::General,
+                src_stage: PipelineStage::ComputeShader,
+                dst_stage: PipelineStage::ComputeShader,
+                src_access: AccessType::ShaderWrite,
+                dst_access: AccessType::ShaderRead,
+                ..Default::default()
+            })]);
+        }
+
+        return info.iterations as usize % 2;
+    }
+
+    //// Queries ////
+    /// Resets every query in the pool, must be recorded outside of rendering
+    pub fn reset_query_pool(&self, query_pool: QueryPool) {
+        unsafe {
+            self.device.handle.cmd_reset_query_pool(self.current_commad_buffer, query_pool.handle, 0, query_pool.count);
+        }
+    }
+
+    /// Starts an occlusion or pipeline statistics query
+    pub fn begin_query(&self, query_pool: QueryPool, query: u32) {
+        assert!(query_pool.query_type != QueryType::Timestamp, "Timestamp queries are written with write_timestamp");
+        assert!(query < query_pool.count, "Began query {} in a pool of {} queries", query, query_pool.count);
+
+        unsafe {
+            self.device.handle.cmd_begin_query(self.current_commad_buffer, query_pool.handle, query, vk::QueryControlFlags::empty());
+        }
+    }
+
+    pub fn end_query(&self, query_pool: QueryPool, query: u32) {
+        assert!(query < query_pool.count, "Ended query {} in a pool of {} queries", query, query_pool.count);
+
+        unsafe {
+            self.device.handle.cmd_end_query(self.current_commad_buffer, query_pool.handle, query);
+        }
+    }
+
+    /// Writes the timestamp once every previous command has reached `stage`
+    pub fn write_timestamp(&self, query_pool: QueryPool, query: u32, stage: PipelineStage) {
+        assert!(query_pool.query_type == QueryType::Timestamp, "write_timestamp needs a timestamp query pool");
+        assert!(query < query_pool.count, "Wrote timestamp {} in a pool of {} queries", query, query_pool.count);
+
+        unsafe {
+            self.device.handle.cmd_write_timestamp2(self.current_commad_buffer, stage.to_vk(), query_pool.handle, query);
+        }
+    }
+
+    /// Copies results into a buffer without a cpu round trip, so a later pass can consume them directly.
+    /// The buffer needs `BufferUsage::TRANSFER_DST`, make the copy visible with a transfer write barrier
+    pub fn copy_query_results(&mut self, info: &QueryCopyInfo) {
+        assert!(
+            info.first_query + info.query_count <= info.query_pool.count,
+            "Copying queries {}..{} from a pool of {} queries",
+            info.first_query,
+            info.first_query + info.query_count,
+            info.query_pool.count
+        );
+
+        let buffer = self.check_and_remeber_buffer_id(info.dst_buffer);
+        let stride = info.size() / info.query_count.max(1) as u64;
+        let flags = if info.with_availability {
+            vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WITH_AVAILABILITY
+        } else {
+            vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT
+        };
+
+        unsafe {
+            self.device.handle.cmd_copy_query_pool_results(
+                self.current_commad_buffer,
+                info.query_pool.handle,
+                info.first_query,
+                info.query_count,
+                buffer,
+                info.dst_offset,
+                stride,
+                flags,
+            );
+        }
+    }
+
+    //// Ray tracing commands ////
+    pub fn trace_rays(&self, sbt: &ShaderBindingTable, width: u32, height: u32, depth: u32) {
+        let rt = match &self.device.rt {
+            Some(rt) => rt,
+            None => panic!("Tried ray tracing without enabling ray tracing"),
+        };
+
+        unsafe {
+            rt.cmd_trace_rays(self.current_commad_buffer, &sbt.rgen, &sbt.miss, &sbt.hit, &sbt.callable, width, height, depth);
+        }
+    }
+
     //// Pipeline barriers and sync ////
     pub fn pipeline_barrier(&mut self, barriers: &[Barrier]) {
+        self.with_dependency_info(barriers, |device, cmd_buffer, dep_info| unsafe {
+            device.cmd_pipeline_barrier2(cmd_buffer, dep_info);
+        });
+    }
+
+    /// Signals the event once the source half of `barriers` is done, the matching `wait_events` does the rest.
+    /// Work recorded in between can overlap with both halves
+    pub fn set_event(&mut self, event: Event, barriers: &[Barrier]) {
+        self.with_dependency_info(barriers, |device, cmd_buffer, dep_info| unsafe {
+            device.cmd_set_event2(cmd_buffer, event.handle, dep_info);
+        });
+    }
+
+    /// Unsignals the event after `stage` is done, so it can be set again
+    pub fn reset_event(&self, event: Event, stage: PipelineStage) {
+        unsafe {
+            self.device.handle.cmd_reset_event2(self.current_commad_buffer, event.handle, stage.to_vk());
+        }
+    }
+
+    /// Waits for every event and executes the destination half of `barriers`.
+    /// Vulkan requires `barriers` to be the same as the ones each event was set with
+    pub fn wait_events(&mut self, events: &[Event], barriers: &[Barrier]) {
+        let handles: SmallVec<[vk::Event; 4]> = events.iter().map(|event| event.handle).collect();
+
+        self.with_dependency_info(barriers, |device, cmd_buffer, dep_info| {
+            let dep_infos: SmallVec<[vk::DependencyInfo; 4]> = handles.iter().map(|_| *dep_info).collect();
+
+            unsafe {
+                device.cmd_wait_events2(cmd_buffer, &handles, &dep_infos);
+            }
+        });
+    }
+
+    // Translates the barriers and hands the dependency info to `record`
+    fn with_dependency_info(&mut self, barriers: &[Barrier], record: impl FnOnce(&ash::Device, vk::CommandBuffer, &vk::DependencyInfo)) {
         let mut mem_barriers = SmallVec::<[vk::MemoryBarrier2; 2]>::new();
         let mut image_barriers = SmallVec::<[vk::ImageMemoryBarrier2; 2]>::new();
         let mut buffer_barriers = SmallVec::<[vk::BufferMemoryBarrier2; 2]>::new();
@@ -277,14 +757,37 @@ impl CommandRecorder {
                 Barrier::Image(img_barrier) => {
                     let img = self.check_and_remeber_image_id(img_barrier.image);
 
+                    let (aspect, whole_image) = {
+                        let pool = self.device.image_pool.read().unwrap();
+                        let slot = pool.get_ref(img_barrier.image.id);
+                        let format = Format::from_vk_format(slot.format);
+
+                        // Without separateDepthStencilLayouts both aspects of a depth stencil image have to transition together
+                        let aspect = match (img_barrier.aspect, format.map(|format| format.aspect())) {
+                            (ImageAspect::Depth | ImageAspect::Stencil, Some(ImageAspect::DepthStencil)) => ImageAspect::DepthStencil,
+                            (aspect, _) => aspect,
+                        };
+
+                        (aspect, img_barrier.covers_whole_image(slot.mip_levels, slot.array_layers))
+                    };
+
+                    // After a partial barrier the image has no single layout left to track
+                    if whole_image {
+                        self.tracked_layouts.insert(img_barrier.image, img_barrier.new_layout);
+                    } else {
+                        self.tracked_layouts.remove(&img_barrier.image);
+                    }
+
                     let subresource_range = vk::ImageSubresourceRange {
-                        aspect_mask: img_barrier.aspect.to_vk_aspect(),
+                        aspect_mask: aspect.to_vk_aspect(),
                         base_mip_level: img_barrier.base_mip,
                         level_count: img_barrier.level_count,
                         base_array_layer: img_barrier.base_layer,
                         layer_count: img_barrier.layer_count,
                     };
 
+                    let (src_family, dst_family) = self.device.barrier_queue_families(img_barrier.src_queue, img_barrier.dst_queue);
+
                     image_barriers.push(
                         vk::ImageMemoryBarrier2::default()
                             .src_stage_mask(img_barrier.src_stage.to_vk())
@@ -293,18 +796,24 @@ impl CommandRecorder {
                             .dst_access_mask(img_barrier.dst_access.to_vk())
                             .old_layout(img_barrier.old_layout.to_vk_layout())
                             .new_layout(img_barrier.new_layout.to_vk_layout())
+                            .src_queue_family_index(src_family)
+                            .dst_queue_family_index(dst_family)
                             .image(img)
                             .subresource_range(subresource_range),
                     );
                 }
                 Barrier::Buffer(buffer_barrier) => {
                     let buf = self.check_and_remeber_buffer_id(buffer_barrier.buffer);
+                    let (src_family, dst_family) = self.device.barrier_queue_families(buffer_barrier.src_queue, buffer_barrier.dst_queue);
+
                     buffer_barriers.push(
                         vk::BufferMemoryBarrier2::default()
                             .src_stage_mask(buffer_barrier.src_stage.to_vk())
                             .src_access_mask(buffer_barrier.src_access.to_vk())
                             .dst_stage_mask(buffer_barrier.dst_stage.to_vk())
                             .dst_access_mask(buffer_barrier.dst_access.to_vk())
+                            .src_queue_family_index(src_family)
+                            .dst_queue_family_index(dst_family)
                             .buffer(buf)
                             .offset(buffer_barrier.offset)
                             .size(buffer_barrier.size),
@@ -318,19 +827,83 @@ impl CommandRecorder {
             .image_memory_barriers(image_barriers.as_slice())
             .buffer_memory_barriers(buffer_barriers.as_slice());
 
+        record(&self.device.handle, self.current_commad_buffer, &dep_info);
+    }
+
+    /// Tells `transition_image` which layout an image is in, eg. after it was transitioned by another recorder
+    pub fn track_image_layout(&mut self, image: ImageID, layout: ImageLayout) {
+        self.tracked_layouts.insert(image, layout);
+    }
+
+    /// Last layout this recorder transitioned the whole image to, None if it was never transitioned or tracked.
+    /// A barrier over only some mips or layers clears it, track the layout again once the image is uniform
+    pub fn tracked_image_layout(&self, image: ImageID) -> Option<ImageLayout> {
+        return self.tracked_layouts.get(&image).copied();
+    }
+
+    /// Transitions every mip and layer of the image from its tracked layout, or `Undefined` if it has none.
+    /// Access masks are derived from the two layouts
+    pub fn transition_image(&mut self, image: ImageID, new_layout: ImageLayout, src_stage: PipelineStage, dst_stage: PipelineStage) {
+        let old_layout = self.tracked_layouts.get(&image).copied().unwrap_or(ImageLayout::Undefined);
+        let img = self.check_and_remeber_image_id(image);
+
+        let aspect = {
+            let pool = self.device.image_pool.read().unwrap();
+            let format = pool.get_ref(image.id).format;
+            Format::from_vk_format(format).map_or(ImageAspect::Color, |format| format.aspect())
+        };
+
+        let image_barrier = vk::ImageMemoryBarrier2::default()
+            .src_stage_mask(src_stage.to_vk())
+            .src_access_mask(old_layout.to_vk_access())
+            .dst_stage_mask(dst_stage.to_vk())
+            .dst_access_mask(new_layout.to_vk_access())
+            .old_layout(old_layout.to_vk_layout())
+            .new_layout(new_layout.to_vk_layout())
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(img)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: aspect.to_vk_aspect(),
+                base_mip_level: 0,
+                level_count: vk::REMAINING_MIP_LEVELS,
+                base_array_layer: 0,
+                layer_count: vk::REMAINING_ARRAY_LAYERS,
+            });
+
+        let dep_info = vk::DependencyInfo::default().image_memory_barriers(std::slice::from_ref(&image_barrier));
+
         unsafe {
             self.device.handle.cmd_pipeline_barrier2(self.current_commad_buffer, &dep_info);
         }
+
+        self.tracked_layouts.insert(image, new_layout);
     }
 
     //// Copy commands ////
     pub fn copy_buffer(&mut self, buffer_copy_info: &BufferCopyInfo) {
-        let src_buffer = self.check_and_remeber_buffer_id(buffer_copy_info.src_buffer);
-        let dst_buffer = self.check_and_remeber_buffer_id(buffer_copy_info.dst_buffer);
+        self.copy_buffer_regions(
+            buffer_copy_info.src_buffer,
+            buffer_copy_info.dst_buffer,
+            &[BufferCopyRegion {
+                src_offset: buffer_copy_info.src_offset,
+                dst_offset: buffer_copy_info.dst_offset,
+                size: buffer_copy_info.size,
+            }],
+        );
+    }
 
-        let copy_region = vk::BufferCopy2::default().src_offset(0).dst_offset(0).size(buffer_copy_info.size);
+    /// Copies several ranges between the same two buffers in one command
+    pub fn copy_buffer_regions(&mut self, src_buffer: BufferID, dst_buffer: BufferID, regions: &[BufferCopyRegion]) {
+        let src_buffer = self.check_and_remeber_buffer_id(src_buffer);
+        let dst_buffer = self.check_and_remeber_buffer_id(dst_buffer);
 
-        let copy_info = vk::CopyBufferInfo2::default().src_buffer(src_buffer).dst_buffer(dst_buffer).regions(std::slice::from_ref(&copy_region));
+        let copy_regions: SmallVec<[vk::BufferCopy2; 4]> = regions
+            .iter()
+            .map(|region| vk::BufferCopy2::default().src_offset(region.src_offset).dst_offset(region.dst_offset).size(region.size))
+            .collect();
+
+        let copy_info = vk::CopyBufferInfo2::default().src_buffer(src_buffer).dst_buffer(dst_buffer).regions(&copy_regions);
 
         unsafe {
             self.device.handle.cmd_copy_buffer2(self.current_commad_buffer, &copy_info);
@@ -367,10 +940,9 @@ impl CommandRecorder {
         }
     }
 
-    pub fn copy_image_to_buffer(&mut self, info: &BufferImageCopyInfo) {
-        // same struct is symmetric
-        let src = self.check_and_remeber_image_id(info.dst_image); // swap
-        let dst = self.check_and_remeber_buffer_id(info.src_buffer);
+    pub fn copy_image_to_buffer(&mut self, info: &ImageBufferCopyInfo) {
+        let src = self.check_and_remeber_image_id(info.src_image);
+        let dst = self.check_and_remeber_buffer_id(info.dst_buffer);
 
         let subresource = vk::ImageSubresourceLayers {
             aspect_mask: info.region.image_subresource.aspect.to_vk_aspect(),
@@ -389,7 +961,7 @@ impl CommandRecorder {
 
         let copy_info = vk::CopyImageToBufferInfo2::default()
             .src_image(src)
-            .src_image_layout(info.dst_image_layout.to_vk_layout())
+            .src_image_layout(info.src_image_layout.to_vk_layout())
             .dst_buffer(dst)
             .regions(std::slice::from_ref(&region));
 
@@ -475,12 +1047,94 @@ impl CommandRecorder {
 }
 
 impl CommandRecorder {
+    pub(crate) fn new(device: Arc<InnerDevice>, queue_type: QueueType, pool_flags: CommandPoolFlags) -> CommandRecorder {
+        let pool = device.createcmd_recorder_data(queue_type, pool_flags);
+
+        return CommandRecorder::from_raw_parts(device, queue_type, pool, pool_flags, smallvec![]);
+    }
+
+    /// Wraps an existing pool and its already allocated, reset command buffers
+    pub(crate) fn from_raw_parts(
+        device: Arc<InnerDevice>,
+        queue_type: QueueType,
+        pool: vk::CommandPool,
+        pool_flags: CommandPoolFlags,
+        command_buffers: SmallVec<[vk::CommandBuffer; 2]>,
+    ) -> CommandRecorder {
+        return CommandRecorder {
+            handle: pool,
+            commad_buffers: command_buffers,
+            exec_command_buffers: smallvec![],
+            current_commad_buffer: vk::CommandBuffer::null(),
+            queue_type: queue_type,
+            remembered_image_ids: HashMap::new(),
+            remembered_buffer_ids: HashMap::new(),
+            remembered_image_view_ids: HashMap::new(),
+            tracked_layouts: HashMap::new(),
+            resumes_rendering: false,
+            suspends_rendering: false,
+            has_rendered: false,
+            rendering_view_mask: None,
+            pipeline_view_mask: None,
+            level: vk::CommandBufferLevel::PRIMARY,
+            pool_flags: pool_flags,
+            device: device,
+        };
+    }
+
+    /// Gives back the pool and its command buffers without destroying them.
+    /// The recorder should be reset first so every buffer is reusable
+    pub(crate) fn into_raw_parts(mut self) -> (vk::CommandPool, SmallVec<[vk::CommandBuffer; 2]>) {
+        let pool = std::mem::replace(&mut self.handle, vk::CommandPool::null());
+        let mut command_buffers = std::mem::take(&mut self.commad_buffers);
+        command_buffers.append(&mut self.exec_command_buffers);
+
+        // Destroying a null pool in drop is a no-op
+        return (pool, command_buffers);
+    }
+
+    fn validate_depth_stencil_resolve(&self, attachment: &RenderingAttachment, supported_modes: vk::ResolveModeFlags, aspect: &str) {
+        if attachment.resolve_mode == ResolveMode::None {
+            return;
+        }
+
+        assert!(
+            attachment.resolve_mode != ResolveMode::Average,
+            "Average resolve is not allowed for {} attachments, use SampleZero, Min or Max",
+            aspect
+        );
+        assert!(
+            supported_modes.contains(attachment.resolve_mode.to_vk()),
+            "Resolve mode {:?} is not supported for {} attachments on this device",
+            attachment.resolve_mode,
+            aspect
+        );
+        assert!(attachment.resolve_image_view.is_some(), "A {} attachment with a resolve mode needs a resolve image view", aspect);
+    }
+
+    // The clear value is a union, the wrong variant would be reinterpreted as garbage
+    fn validate_clear_value(attachment: &RenderingAttachment, depth_stencil: bool, aspect: &str) {
+        if attachment.load_op != LoadOp::Clear {
+            return;
+        }
+
+        let is_depth_stencil = matches!(attachment.clear_value, ClearValue::DepthStencil { .. });
+
+        if depth_stencil && !is_depth_stencil {
+            panic!("A {} attachment cleared with a color value, use ClearValue::DepthStencil", aspect);
+        } else if !depth_stencil && is_depth_stencil {
+            panic!("A {} attachment cleared with ClearValue::DepthStencil, use a color variant", aspect);
+        }
+    }
+
     fn check_and_remeber_image_id(&mut self, id: ImageID) -> vk::Image {
         match self.remembered_image_ids.get(&id) {
             Some(img) => img.clone(),
             None => {
                 let img_pool = self.device.image_pool.read().unwrap();
-                let img = img_pool.get_ref(id.id);
+                let img = img_pool
+                    .try_get_ref(id.id)
+                    .unwrap_or_else(|| panic!("Image used in command recording was destroyed or never created (id: {})", id.id));
                 self.remembered_image_ids.insert(id, img.handle);
                 img.handle
             }
@@ -492,7 +1146,9 @@ impl CommandRecorder {
             Some(buff) => buff.clone(),
             None => {
                 let buffer_pool = self.device.buffer_pool.read().unwrap();
-                let buffer = buffer_pool.get_ref(id.id);
+                let buffer = buffer_pool
+                    .try_get_ref(id.id)
+                    .unwrap_or_else(|| panic!("Buffer used in command recording was destroyed or never created (id: {})", id.id));
                 self.remembered_buffer_ids.insert(id, buffer.handle);
                 buffer.handle
             }
@@ -504,7 +1160,9 @@ impl CommandRecorder {
             Some(img_view) => img_view.clone(),
             None => {
                 let pool = self.device.image_view_pool.read().unwrap();
-                let img_view = pool.get_ref(id.id);
+                let img_view = pool
+                    .try_get_ref(id.id)
+                    .unwrap_or_else(|| panic!("Image view used in command recording was destroyed or never created (id: {})", id.id));
                 self.remembered_image_view_ids.insert(id, img_view.handle);
                 img_view.handle
             }
@@ -512,10 +1170,7 @@ impl CommandRecorder {
     }
 
     pub(crate) fn new_cmd_buffer(&self) -> vk::CommandBuffer {
-        let alloc_info = vk::CommandBufferAllocateInfo::default()
-            .command_buffer_count(1)
-            .command_pool(self.handle)
-            .level(vk::CommandBufferLevel::PRIMARY);
+        let alloc_info = vk::CommandBufferAllocateInfo::default().command_buffer_count(1).command_pool(self.handle).level(self.level);
 
         let cmd_buffer = unsafe { self.device.handle.allocate_command_buffers(&alloc_info).expect("Failed to allocate command buffer") }[0];
 
@@ -523,6 +1178,64 @@ impl CommandRecorder {
     }
 }
 
+/// Records secondary command buffers which are replayed inside a rendering with `CommandRecorder::execute_secondary`.
+/// Each thread can own its own secondary recorder, letting a single render pass be recorded in parallel
+pub struct SecondaryCommandRecorder {
+    pub(crate) recorder: CommandRecorder,
+}
+
+impl SecondaryCommandRecorder {
+    /// `inheritance_info` must match the rendering the command buffer will be executed in
+    pub fn begin_recording(&mut self, usage: CommandBufferUsage, inheritance_info: &SecondaryInheritanceInfo) {
+        let color_formats: SmallVec<[vk::Format; 4]> = inheritance_info.color_formats.iter().map(|f| f.to_vk_format()).collect();
+
+        let mut rendering_info = vk::CommandBufferInheritanceRenderingInfo::default()
+            .color_attachment_formats(&color_formats)
+            .depth_attachment_format(inheritance_info.depth_format.map_or(vk::Format::UNDEFINED, |f| f.to_vk_format()))
+            .stencil_attachment_format(inheritance_info.stencil_format.map_or(vk::Format::UNDEFINED, |f| f.to_vk_format()))
+            .rasterization_samples(inheritance_info.samples.to_vk_flags())
+            .view_mask(inheritance_info.view_mask);
+
+        let inheritance = vk::CommandBufferInheritanceInfo::default().push_next(&mut rendering_info);
+
+        let begin_info = vk::CommandBufferBeginInfo::default()
+            .flags(usage.to_vk_flags() | vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
+            .inheritance_info(&inheritance);
+
+        self.recorder.begin_command_buffer(&begin_info);
+        self.recorder.rendering_view_mask = Some(inheritance_info.view_mask);
+    }
+
+    delegate! {
+        to self.recorder {
+            pub fn reset(&mut self);
+            pub fn reset_buffer(&mut self, command_buffer: ExecutableCommandBuffer);
+            pub fn end_recording(&mut self) -> ExecutableCommandBuffer;
+            // Bind commands
+            pub fn set_viewport_and_scissor(&self, width: u32, height: u32);
+            pub fn set_viewport(&self, x: f32, y: f32, width: f32, height: f32, min_depth: f32, max_depth: f32);
+            pub fn set_scissor(&self, x: i32, y: i32, width: u32, height: u32);
+            pub fn set_polygon_mode(&self, polygon_mode: PolygonMode);
+            pub fn set_line_width(&self, line_width: f32);
+            pub fn set_depth_bias(&self, constant_factor: f32, clamp: f32, slope_factor: f32);
+            pub fn set_cull_mode(&self, cull_mode: CullMode);
+            pub fn set_front_face(&self, front_face: FrontFace);
+            pub fn set_stencil_reference(&self, reference: u32);
+            pub fn set_push_constants(&self, push_constants: &impl bytemuck::Pod, pipeline: &impl Pipeline);
+            pub fn set_push_constant_bytes(&self, data: &[u8], pipeline: &impl Pipeline);
+            pub fn bind_pipeline(&mut self, pipeline: &impl Pipeline);
+            pub fn bind_vertex_buffer(&mut self, buffer_id: BufferID, offset: u64);
+            pub fn bind_vertex_buffers(&mut self, first_binding: u32, bindings: &[(BufferID, u64)]);
+            pub fn bind_vertex_buffers_with_stride(&mut self, bindings: &[(BufferID, u64, u64)]);
+            pub fn bind_index_buffer(&mut self, buffer_id: BufferID, offset: u64, index_type: IndexType);
+            // Draw commands
+            pub fn draw(&self, vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32);
+            pub fn draw_indexed(&self, index_count: u32, instance_count: u32, first_index: u32, vertex_offset: i32, first_instance: u32);
+            pub fn draw_mesh_tasks(&self, group_count_x: u32, group_count_y: u32, group_count_z: u32);
+        }
+    }
+}
+
 impl Drop for CommandRecorder {
     fn drop(&mut self) {
         unsafe {
@@ -534,6 +1247,9 @@ impl Drop for CommandRecorder {
 pub struct ExecutableCommandBuffer {
     pub(crate) handle: vk::CommandBuffer,
     pub(crate) queue_type: QueueType,
+    pub(crate) resumes_rendering: bool,
+    pub(crate) suspends_rendering: bool,
+    pub(crate) secondary: bool,
 }
 
 #[derive(Clone, Copy)]
@@ -541,6 +1257,30 @@ pub struct Fence {
     pub(crate) handle: vk::Fence,
 }
 
+/// Split barrier, set with `CommandRecorder::set_event` and waited on later with `CommandRecorder::wait_events`
+#[derive(Clone, Copy)]
+pub struct Event {
+    pub(crate) handle: vk::Event,
+}
+
+/// Pool of queries of a single type, queries must be reset before every use
+#[derive(Clone, Copy)]
+pub struct QueryPool {
+    pub(crate) handle: vk::QueryPool,
+    pub(crate) query_type: QueryType,
+    pub(crate) count: u32,
+}
+
+impl QueryPool {
+    pub fn query_type(&self) -> QueryType {
+        return self.query_type;
+    }
+
+    pub fn count(&self) -> u32 {
+        return self.count;
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct BinarySemaphore {
     pub(crate) handle: vk::Semaphore,
@@ -1,14 +1,16 @@
-use ahash::{HashMap, HashMapExt};
-use ash::vk;
 use crossbeam::queue::ArrayQueue;
-use smallvec::smallvec;
 
 use crate::{
-    BinarySemaphore, BufferDescription, BufferID, BufferWriteInfo, CommandRecorder, Fence, ImageDescription, ImageID, ImageViewDescription, ImageViewID, ImageWriteInfo, PipelineManager,
-    QueueSubmitInfo, QueueType, SamplerDescription, SamplerID, SamplerWriteInfo, Semaphore, Swapchain, SwapchainDescription, TimelineSemaphore,
+    AccelerationStructureID, AccessType, Barrier, BinarySemaphore, BlasDescription, BufferBarrier, BufferCopyInfo, BufferDescription, BufferID, BufferUsage, BufferWriteInfo, CommandPoolFlags,
+    CommandRecorder, CopyAlignments, DeviceError, DeviceLimits, Event, Fence, Format, FormatSupport, HeapBudget, ImageAspect, ImageDescription, ImageDescriptorType, ImageID, ImageUsage,
+    ImageViewDescription, ImageViewID, ImageWriteInfo, MemoryStats, MemoryType, PipelineManager, PipelineStage, QueryPool, QueryType, QueueSubmitInfo, QueueType, SamplerDescription, SamplerID,
+    SamplerWriteInfo, SecondaryCommandRecorder, Semaphore, SemaphoreInfo, Swapchain, SwapchainDescription, TimelineSemaphore, TlasDescription,
     backend::{device::InnerDevice, pipelines::InnerPipelineManager, swapchain::InnerSwapchain},
 };
-use std::sync::{Arc, atomic::AtomicUsize};
+use std::sync::{
+    Arc,
+    atomic::{AtomicU32, AtomicUsize},
+};
 
 #[derive(Clone)]
 pub struct Device {
@@ -18,13 +20,14 @@ pub struct Device {
 //Swapchain Impl//
 impl Device {
     pub fn create_swapchain(&self, swapchain_desc: &SwapchainDescription) -> Swapchain {
-        let (loader, swapchain, images, image_views) = self.inner.create_swapchain_data(swapchain_desc, ash::vk::SwapchainKHR::null());
+        let (loader, swapchain, images, image_views, format, extent) = self.inner.create_swapchain_data(swapchain_desc, ash::vk::SwapchainKHR::null());
 
         let (image_semapgores, present_semaphore) = {
             let mut t: Vec<Semaphore> = vec![];
             let mut n: Vec<Semaphore> = vec![];
 
-            for _ in 0..swapchain_desc.image_count {
+            // The driver can create more images than requested
+            for _ in 0..images.len() {
                 t.push(self.create_binary_semaphore());
                 n.push(self.create_binary_semaphore());
             }
@@ -36,25 +39,33 @@ impl Device {
             inner: Arc::new(InnerSwapchain {
                 handle: swapchain,
                 swapchain_loader: loader,
-                curr_img_indeices: ArrayQueue::new(swapchain_desc.image_count as usize),
+                curr_img_indeices: ArrayQueue::new(images.len()),
                 image_views: image_views,
                 images: images,
                 image_semaphore: image_semapgores,
                 preset_semaphore: present_semaphore,
                 timeline: AtomicUsize::new(0),
+                format: format,
+                extent: extent,
+                usage: swapchain_desc.usage,
+                last_acquired: AtomicU32::new(0),
+                format_changed: false,
                 device: self.inner.clone(),
             }),
         };
     }
 
+    /// The surface format is negotiated again, check `Swapchain::format_changed` afterwards
+    /// and rebuild pipelines that render to the swapchain if it did
     pub fn recreate_swapchain(&self, swapchain_desc: &SwapchainDescription, old_swapchain: &Swapchain) -> Swapchain {
-        let (loader, swapchain, images, image_views) = self.inner.create_swapchain_data(swapchain_desc, old_swapchain.inner.handle);
+        let (loader, swapchain, images, image_views, format, extent) = self.inner.create_swapchain_data(swapchain_desc, old_swapchain.inner.handle);
 
         let (image_semapgores, present_semaphore) = {
             let mut t: Vec<Semaphore> = vec![];
             let mut n: Vec<Semaphore> = vec![];
 
-            for _ in 0..swapchain_desc.image_count {
+            // The driver can create more images than requested
+            for _ in 0..images.len() {
                 t.push(self.create_binary_semaphore());
                 n.push(self.create_binary_semaphore());
             }
@@ -66,12 +77,17 @@ impl Device {
             inner: Arc::new(InnerSwapchain {
                 handle: swapchain,
                 swapchain_loader: loader,
-                curr_img_indeices: ArrayQueue::new(swapchain_desc.image_count as usize),
+                curr_img_indeices: ArrayQueue::new(images.len()),
                 image_views: image_views,
                 images: images,
                 image_semaphore: image_semapgores,
                 preset_semaphore: present_semaphore,
                 timeline: AtomicUsize::new(0),
+                format: format,
+                extent: extent,
+                usage: swapchain_desc.usage,
+                last_acquired: AtomicU32::new(0),
+                format_changed: format != old_swapchain.inner.format,
                 device: self.inner.clone(),
             }),
         };
@@ -84,13 +100,128 @@ impl Device {
         return self.inner.create_buffer(buffer_desc);
     }
 
+    /// Falls back to host memory when the device is out of memory.
+    /// Returns the requested memory type, or `MemoryType::PreferHost` if it fell back
+    pub fn create_buffer_with_fallback(&self, buffer_desc: &BufferDescription) -> (BufferID, MemoryType) {
+        return self.inner.create_buffer_with_fallback(buffer_desc);
+    }
+
     pub fn destroy_buffer(&self, id: BufferID) {
         self.inner.destroy_buffer(id);
     }
 
+    /// Destroys the buffer after `frames` more presents, so command buffers still in flight can finish using it.
+    /// Pass the number of frames in flight
+    pub fn destroy_buffer_deferred(&self, id: BufferID, frames: u32) {
+        self.inner.destroy_buffer_deferred(id, frames);
+    }
+
+    /// Copies `data` to the start of a buffer created with `create_mapped`, panics if it does not fit
     pub fn write_data_to_buffer<T: Copy>(&self, buffer_id: BufferID, data: &[T]) {
         self.inner.write_data_to_buffer(buffer_id, data);
     }
+
+    /// Creates a device local buffer with `data` copied in through a staging buffer on the transfer queue.
+    /// Ownership is handed to the graphics queue when they are different families. Blocks until the upload is done
+    pub fn upload_buffer<T: Copy>(&self, data: &[T], usage: BufferUsage) -> BufferID {
+        let size = std::mem::size_of_val(data) as u64;
+
+        let staging_buffer = self.create_buffer(&BufferDescription {
+            usage: BufferUsage::TRANSFER_SRC,
+            size: size,
+            memory_type: MemoryType::PreferHost,
+            create_mapped: true,
+            ..Default::default()
+        });
+        self.write_data_to_buffer(staging_buffer, data);
+
+        let buffer = self.create_buffer(&BufferDescription {
+            usage: usage | BufferUsage::TRANSFER_DST,
+            size: size,
+            memory_type: MemoryType::DeviceLocal,
+            ..Default::default()
+        });
+
+        let separate_families = self.inner.queue_family_index(QueueType::Transfer) != self.inner.queue_family_index(QueueType::Graphics);
+
+        // Release on the transfer queue, the acquire below completes the ownership transfer
+        let ownership_barrier = BufferBarrier {
+            buffer: buffer,
+            src_stage: PipelineStage::Transfer,
+            dst_stage: PipelineStage::AllCommands,
+            src_access: AccessType::TransferWrite,
+            dst_access: usage.read_access(),
+            src_queue: QueueType::Transfer,
+            dst_queue: QueueType::Graphics,
+            offset: 0,
+            size: size,
+        };
+
+        self.immediate_submit(QueueType::Transfer, |recorder| {
+            recorder.copy_buffer(&BufferCopyInfo {
+                src_buffer: staging_buffer,
+                dst_buffer: buffer,
+                src_offset: 0,
+                dst_offset: 0,
+                size: size,
+            });
+
+            if separate_families {
+                recorder.pipeline_barrier(&[Barrier::Buffer(BufferBarrier {
+                    dst_access: AccessType::None,
+                    ..ownership_barrier.clone()
+                })]);
+            }
+        });
+
+        if separate_families {
+            self.immediate_submit(QueueType::Graphics, |recorder| {
+                recorder.pipeline_barrier(&[Barrier::Buffer(BufferBarrier {
+                    src_stage: PipelineStage::TopOfPipe,
+                    src_access: AccessType::None,
+                    ..ownership_barrier
+                })]);
+            });
+        }
+
+        self.destroy_buffer(staging_buffer);
+
+        return buffer;
+    }
+
+    /// Makes host writes to a mapped buffer visible to the device.
+    /// Only needed for non coherent memory, `write_data_to_buffer` already flushes for you
+    pub fn flush_buffer(&self, buffer_id: BufferID, offset: u64, size: u64) {
+        self.inner.flush_buffer(buffer_id, offset, size);
+    }
+
+    /// Makes device writes to a mapped buffer visible to the host, call before reading back non coherent memory
+    pub fn invalidate_buffer(&self, buffer_id: BufferID, offset: u64, size: u64) {
+        self.inner.invalidate_buffer(buffer_id, offset, size);
+    }
+
+    /// Persistent pointer to the memory of a buffer created with `create_mapped`, None otherwise.
+    /// Writing while the gpu may still read the buffer is a race, wait on the fence of the submission first.
+    /// Non coherent memory also needs `flush_buffer` after writing and `invalidate_buffer` before reading
+    pub fn mapped_ptr(&self, buffer_id: BufferID) -> Option<*mut u8> {
+        return self.inner.mapped_ptr(buffer_id);
+    }
+
+    /// Maps a `MemoryType::PreferHost` buffer on demand, the pointer is valid until `unmap_buffer`.
+    /// Unlike `create_mapped` the memory only stays mapped while needed. Mapping twice without unmapping panics
+    pub fn map_buffer(&self, buffer_id: BufferID) -> *mut u8 {
+        return self.inner.map_buffer(buffer_id);
+    }
+
+    /// Unmaps a buffer mapped with `map_buffer`, flush non coherent memory before unmapping
+    pub fn unmap_buffer(&self, buffer_id: BufferID) {
+        self.inner.unmap_buffer(buffer_id);
+    }
+
+    /// Device address of the buffer, for buffer references passed through push constants or other buffers
+    pub fn buffer_device_address(&self, buffer_id: BufferID) -> u64 {
+        return self.inner.buffer_device_address(buffer_id);
+    }
 }
 
 // Image //
@@ -99,9 +230,20 @@ impl Device {
         return self.inner.create_image(image_desc);
     }
 
+    /// Falls back to host memory when the device is out of memory.
+    /// Returns the requested memory type, or `MemoryType::PreferHost` if it fell back
+    pub fn create_image_with_fallback(&self, image_desc: &ImageDescription) -> (ImageID, MemoryType) {
+        return self.inner.create_image_with_fallback(image_desc);
+    }
+
     pub fn destroy_image(&self, image_id: ImageID) {
         self.inner.destroy_image(image_id);
     }
+
+    /// Same as `destroy_buffer_deferred`, views of the image have to be destroyed separately
+    pub fn destroy_image_deferred(&self, image_id: ImageID, frames: u32) {
+        self.inner.destroy_image_deferred(image_id, frames);
+    }
 }
 
 // Image View //
@@ -113,6 +255,32 @@ impl Device {
     pub fn destroy_image_view(&self, image_view_id: ImageViewID) {
         self.inner.destroy_image_view(image_view_id);
     }
+
+    /// Creates a device local depth attachment and a view covering every aspect of the format.
+    /// Returns the image and the view, ready to be used in a `RenderingAttachment`
+    pub fn create_depth_target(&self, width: u32, height: u32, format: Format) -> (ImageID, ImageViewID) {
+        assert!(format.is_depth_stencil(), "Depth target format {:?} is not a depth stencil format", format);
+
+        let image = self.create_image(&ImageDescription {
+            usage: ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+            format: format,
+            width: width,
+            height: height,
+            memory_type: MemoryType::DeviceLocal,
+            dedicated: true,
+            ..Default::default()
+        });
+
+        let view = self.create_image_view(
+            image,
+            &ImageViewDescription {
+                aspect: format.aspect(),
+                ..Default::default()
+            },
+        );
+
+        return (image, view);
+    }
 }
 
 // Sampler //
@@ -121,17 +289,50 @@ impl Device {
         return self.inner.create_sampler(sampler_desc);
     }
 
+    /// Linear filtering with repeat addressing, created on first use and always bound at `DEFAULT_SAMPLER_INDEX`.
+    /// Owned by the device, do not destroy it
+    pub fn default_sampler(&self) -> SamplerID {
+        return self.inner.default_sampler();
+    }
+
+    /// Same as `default_sampler` with nearest filtering, bound at `DEFAULT_NEAREST_SAMPLER_INDEX`
+    pub fn default_nearest_sampler(&self) -> SamplerID {
+        return self.inner.default_nearest_sampler();
+    }
+
     pub fn destroy_sampler(&self, sampler_id: SamplerID) {
         self.inner.destroy_sampler(sampler_id);
     }
 }
 
+// Acceleration Structure //
+impl Device {
+    /// Builds a bottom level acceleration structure, blocks until the build is complete
+    pub fn build_blas(&self, blas_desc: &BlasDescription) -> AccelerationStructureID {
+        return self.inner.build_blas(blas_desc);
+    }
+
+    /// Builds a top level acceleration structure over previously built BLASes, blocks until the build is complete
+    pub fn build_tlas(&self, tlas_desc: &TlasDescription) -> AccelerationStructureID {
+        return self.inner.build_tlas(tlas_desc);
+    }
+
+    pub fn destroy_acceleration_structure(&self, id: AccelerationStructureID) {
+        self.inner.destroy_acceleration_structure(id);
+    }
+}
+
 // Descriptors //
 impl Device {
     pub fn write_buffer(&self, buffer_write_info: &BufferWriteInfo) {
         self.inner.write_buffer(buffer_write_info);
     }
 
+    /// Writes the buffer range to binding 4 as a uniform buffer, the range can not exceed `DeviceLimits::max_uniform_buffer_range`
+    pub fn write_uniform_buffer(&self, buffer_write_info: &BufferWriteInfo) {
+        self.inner.write_uniform_buffer(buffer_write_info);
+    }
+
     pub fn write_image(&self, image_write_info: &ImageWriteInfo) {
         self.inner.write_image(image_write_info);
     }
@@ -139,6 +340,140 @@ impl Device {
     pub fn write_sampler(&self, sampler_write_info: &SamplerWriteInfo) {
         self.inner.write_sampler(sampler_write_info);
     }
+
+    /// Writes the whole buffer to the next free index of binding 0 and returns that index
+    pub fn register_buffer(&self, buffer_id: BufferID) -> u32 {
+        return self.inner.register_buffer(buffer_id);
+    }
+
+    /// Writes the whole buffer to the next free index of binding 4 and returns that index
+    pub fn register_uniform_buffer(&self, buffer_id: BufferID) -> u32 {
+        return self.inner.register_uniform_buffer(buffer_id);
+    }
+
+    /// Writes the view to the next free index of binding 1 (sampled) or 2 (storage) and returns that index
+    pub fn register_image(&self, image_view_id: ImageViewID, image_descriptor_type: ImageDescriptorType) -> u32 {
+        return self.inner.register_image(image_view_id, image_descriptor_type);
+    }
+
+    /// Writes the sampler to the next free index of binding 3 and returns that index
+    pub fn register_sampler(&self, sampler_id: SamplerID) -> u32 {
+        return self.inner.register_sampler(sampler_id);
+    }
+
+    /// Creates the image and a view of it and registers the view as a sampled image.
+    /// The view aspect comes from the format, depth stencil formats are sampled through their depth aspect.
+    /// Returns the image, the view and the bindless sampled image index
+    pub fn create_sampled_texture(&self, image_desc: &ImageDescription, image_view_desc: &ImageViewDescription) -> (ImageID, ImageViewID, u32) {
+        let image = self.create_image(image_desc);
+
+        let aspect = match image_desc.format.aspect() {
+            ImageAspect::DepthStencil => ImageAspect::Depth,
+            aspect => aspect,
+        };
+
+        let view = self.create_image_view(image, &ImageViewDescription { aspect: aspect, ..*image_view_desc });
+
+        let index = self.register_image(view, ImageDescriptorType::SampledImage);
+
+        return (image, view, index);
+    }
+
+    /// Frees the index for reuse, the shader must no longer access it
+    pub fn unregister_buffer(&self, index: u32) {
+        self.inner.unregister_buffer(index);
+    }
+
+    pub fn unregister_uniform_buffer(&self, index: u32) {
+        self.inner.unregister_uniform_buffer(index);
+    }
+
+    pub fn unregister_image(&self, index: u32, image_descriptor_type: ImageDescriptorType) {
+        self.inner.unregister_image(index, image_descriptor_type);
+    }
+
+    pub fn unregister_sampler(&self, index: u32) {
+        self.inner.unregister_sampler(index);
+    }
+}
+
+// Device properties //
+impl Device {
+    /// Alignments to respect when placing data in staging buffers
+    pub fn copy_alignments(&self) -> CopyAlignments {
+        return self.inner.copy_alignments();
+    }
+
+    pub fn limits(&self) -> DeviceLimits {
+        return self.inner.limits();
+    }
+
+    /// Which usages `format` supports, check before creating images with less common formats
+    pub fn format_properties(&self, format: Format) -> FormatSupport {
+        return self.inner.format_properties(format);
+    }
+
+    /// Name of the selected physical device, eg. for logging
+    pub fn device_name(&self) -> String {
+        return self.inner.device_name();
+    }
+
+    /// Per heap statistics of everything allocated through this device, walks every allocation so don't call it every frame
+    pub fn memory_stats(&self) -> MemoryStats {
+        return self.inner.memory_stats();
+    }
+
+    /// Current usage and budget of every memory heap, cheap enough to poll each frame
+    pub fn memory_budget(&self) -> Vec<HeapBudget> {
+        return self.inner.memory_budget();
+    }
+
+    /// Whether presentation happens on a different queue family than graphics.
+    /// The swapchain images are shared concurrently between both families in that case
+    pub fn has_separate_present_queue(&self) -> bool {
+        return self.inner.has_separate_present_queue();
+    }
+
+    /// Whether `CommandRecorder::set_polygon_mode` can be used.
+    /// If not, wireframe needs its own pipeline
+    pub fn supports_dynamic_polygon_mode(&self) -> bool {
+        return self.inner.supports_dynamic_polygon_mode();
+    }
+
+    /// Whether samplers can use `BorderColor::Custom`
+    pub fn supports_custom_border_color(&self) -> bool {
+        return self.inner.supports_custom_border_color();
+    }
+
+    /// Whether samplers can use `SamplerDescription::max_anisotropy`, up to `DeviceLimits::max_sampler_anisotropy`
+    pub fn supports_sampler_anisotropy(&self) -> bool {
+        return self.inner.supports_sampler_anisotropy();
+    }
+
+    /// Whether samplers can use `ReductionMode::Min` and `ReductionMode::Max`
+    pub fn supports_sampler_reduction(&self) -> bool {
+        return self.inner.supports_sampler_reduction();
+    }
+
+    /// Whether index buffers can use `IndexType::Uint8`
+    pub fn supports_index_type_uint8(&self) -> bool {
+        return self.inner.supports_index_type_uint8();
+    }
+
+    /// Whether `CommandRecorder::push_descriptor_buffer` can be used
+    pub fn supports_push_descriptors(&self) -> bool {
+        return self.inner.supports_push_descriptors();
+    }
+
+    /// Whether the device was created with `DeviceDescription::ray_tracing`
+    pub fn supports_ray_tracing(&self) -> bool {
+        return self.inner.supports_ray_tracing();
+    }
+
+    /// Whether the device was created with `DeviceDescription::mesh_shaders`, needed for `CommandRecorder::draw_mesh_tasks`
+    pub fn supports_mesh_shaders(&self) -> bool {
+        return self.inner.supports_mesh_shaders();
+    }
 }
 
 // Pipeline Manager //
@@ -152,18 +487,34 @@ impl Device {
 
 // Command buffer //
 impl Device {
+    /// The recorder's buffers can be reset all at once with `reset` or one at a time with `reset_buffer`
     pub fn create_command_recorder(&self, queue_type: QueueType) -> CommandRecorder {
-        return CommandRecorder {
-            handle: self.inner.createcmd_recorder_data(queue_type),
-            commad_buffers: smallvec![],
-            exec_command_buffers: smallvec![],
-            current_commad_buffer: vk::CommandBuffer::null(),
-            queue_type: queue_type,
-            remembered_image_ids: HashMap::new(),
-            remembered_buffer_ids: HashMap::new(),
-            remembered_image_view_ids: HashMap::new(),
-            device: self.inner.clone(),
-        };
+        return CommandRecorder::new(self.inner.clone(), queue_type, CommandPoolFlags::RESET_INDIVIDUAL);
+    }
+
+    /// Recorders and their command pools must only be used by one thread at a time.
+    /// Create one recorder per recording thread, the buffers they record can then be submitted together from any thread.
+    /// `utils::recorder_pool::CommandRecorderPool` keeps one recorder per thread
+    pub fn create_command_recorder_for_thread(&self, queue_type: QueueType) -> CommandRecorder {
+        return self.create_command_recorder(queue_type);
+    }
+
+    pub fn create_command_recorder_with_flags(&self, queue_type: QueueType, flags: CommandPoolFlags) -> CommandRecorder {
+        return CommandRecorder::new(self.inner.clone(), queue_type, flags);
+    }
+
+    pub fn create_secondary_command_recorder(&self, queue_type: QueueType) -> SecondaryCommandRecorder {
+        let mut recorder = CommandRecorder::new(self.inner.clone(), queue_type, CommandPoolFlags::RESET_INDIVIDUAL);
+        recorder.level = ash::vk::CommandBufferLevel::SECONDARY;
+
+        return SecondaryCommandRecorder { recorder: recorder };
+    }
+
+    /// Records the closure on a cached recorder, submits it and waits for it to finish.
+    /// Meant for one off work like uploads, not per frame recording.
+    /// The closure may do its own immediate submits or uploads, they complete before the outer submission
+    pub fn immediate_submit(&self, queue_type: QueueType, f: impl FnOnce(&mut CommandRecorder)) {
+        self.inner.immediate_submit(queue_type, f);
     }
 }
 
@@ -175,6 +526,10 @@ impl Device {
         };
     }
 
+    pub fn create_event(&self) -> Event {
+        return Event { handle: self.inner.create_event() };
+    }
+
     pub fn create_binary_semaphore(&self) -> Semaphore {
         return Semaphore::Binary(BinarySemaphore {
             handle: self.inner.create_binary_semaphore(),
@@ -191,6 +546,12 @@ impl Device {
         self.inner.wait_fence(fence);
     }
 
+    /// Waits for all or any of `fences`, returns false if `timeout_ns` ran out first.
+    /// A timeout of 0 only polls the fences
+    pub fn wait_fences(&self, fences: &[Fence], wait_all: bool, timeout_ns: u64) -> bool {
+        return self.inner.wait_fences(fences, wait_all, timeout_ns);
+    }
+
     pub fn reset_fence(&self, fence: Fence) {
         self.inner.reset_fence(fence);
     }
@@ -199,22 +560,150 @@ impl Device {
         self.inner.destroy_fence(fence);
     }
 
+    pub fn destroy_event(&self, event: Event) {
+        self.inner.destroy_event(event);
+    }
+
     pub fn destroy_semaphore(&self, semaphore: Semaphore) {
         self.inner.destroy_semaphore(semaphore);
     }
 }
 
+// Queries //
+impl Device {
+    pub fn create_query_pool(&self, query_type: QueryType, count: u32) -> QueryPool {
+        return self.inner.create_query_pool(query_type, count);
+    }
+
+    pub fn destroy_query_pool(&self, query_pool: QueryPool) {
+        self.inner.destroy_query_pool(query_pool);
+    }
+
+    /// Blocks until the queries are available and returns `values_per_query` u64s per query
+    pub fn get_query_results(&self, query_pool: QueryPool, first_query: u32, query_count: u32) -> Vec<u64> {
+        return self.inner.get_query_results(query_pool, first_query, query_count);
+    }
+}
+
 // Queue submissions
 impl Device {
     pub fn submit(&self, submit_info: &QueueSubmitInfo) {
         self.inner.submit(submit_info);
     }
 
+    /// Submits several batches, each with its own semaphores, in a single queue submission.
+    /// Every batch must target the same queue and at most one may carry a fence
+    pub fn submit_batches(&self, submit_infos: &[QueueSubmitInfo]) {
+        self.inner.submit_batches(submit_infos);
+    }
+
+    /// Submits compute work that overlaps with graphics, signalling `timeline` to `signal_value` when it finishes.
+    /// Add the returned info to the `wait_semaphores` of the graphics submission that consumes the results,
+    /// narrowing its `pipeline_stage` to the first stage that reads them.
+    ///
+    /// Resources written here and read on the graphics queue need a release barrier from `QueueType::Compute`
+    /// at the end of this work and a matching acquire barrier on the graphics queue, unless both queues share a family
+    pub fn submit_async_compute(&self, mut submit_info: QueueSubmitInfo, timeline: Semaphore, signal_value: u64) -> SemaphoreInfo {
+        let wait_info = Device::add_async_compute_signal(&mut submit_info, timeline, signal_value);
+
+        self.inner.submit(&submit_info);
+
+        return wait_info;
+    }
+
+    // Validates an async compute submission and makes it signal the timeline, returns what the graphics side waits on
+    fn add_async_compute_signal(submit_info: &mut QueueSubmitInfo, timeline: Semaphore, signal_value: u64) -> SemaphoreInfo {
+        assert!(matches!(timeline, Semaphore::Timeline(_)), "Async compute signals a timeline semaphore, got a binary semaphore");
+
+        if let Some(cb) = submit_info.command_buffers.iter().find(|cb| cb.queue_type != QueueType::Compute) {
+            panic!("Async compute submissions must be recorded for the compute queue, got a {:?} command buffer", cb.queue_type);
+        }
+
+        submit_info.signal_semaphores.push(SemaphoreInfo {
+            semaphore: timeline,
+            pipeline_stage: PipelineStage::AllCommands,
+            value: Some(signal_value),
+        });
+
+        return SemaphoreInfo {
+            semaphore: timeline,
+            pipeline_stage: PipelineStage::AllCommands,
+            value: Some(signal_value),
+        };
+    }
+
     pub fn wait_idle(&self) {
         self.inner.wait_idle();
     }
 
+    /// Whether a submit, wait, acquire or present has returned VK_ERROR_DEVICE_LOST
+    pub fn is_device_lost(&self) -> bool {
+        return self.inner.is_device_lost();
+    }
+
+    /// Like `wait_idle`, but returns the error instead of panicking, e.g. to tear down after `DeviceError::DeviceLost`
+    pub fn try_wait_idle(&self) -> Result<(), DeviceError> {
+        return self.inner.try_wait_idle();
+    }
+
     pub fn wait_queue(&self, queue_type: QueueType) {
         self.inner.wait_queue(queue_type);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BinarySemaphore, ExecutableCommandBuffer};
+    use ash::vk;
+
+    fn command_buffer(queue_type: QueueType) -> ExecutableCommandBuffer {
+        return ExecutableCommandBuffer {
+            handle: vk::CommandBuffer::null(),
+            queue_type: queue_type,
+            resumes_rendering: false,
+            suspends_rendering: false,
+            secondary: false,
+        };
+    }
+
+    fn submit_info(queue_type: QueueType) -> QueueSubmitInfo {
+        return QueueSubmitInfo {
+            fence: None,
+            command_buffers: vec![command_buffer(queue_type)],
+            wait_semaphores: vec![],
+            signal_semaphores: vec![],
+        };
+    }
+
+    fn timeline() -> Semaphore {
+        return Semaphore::Timeline(TimelineSemaphore { handle: vk::Semaphore::null() });
+    }
+
+    #[test]
+    fn async_compute_signals_the_timeline_value() {
+        let mut info = submit_info(QueueType::Compute);
+
+        let wait = Device::add_async_compute_signal(&mut info, timeline(), 7);
+
+        assert_eq!(info.signal_semaphores.len(), 1);
+        assert!(matches!(info.signal_semaphores[0].semaphore, Semaphore::Timeline(_)));
+        assert_eq!(info.signal_semaphores[0].value, Some(7));
+        assert!(matches!(wait.semaphore, Semaphore::Timeline(_)));
+        assert_eq!(wait.value, Some(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "timeline semaphore")]
+    fn async_compute_rejects_binary_semaphores() {
+        let binary = Semaphore::Binary(BinarySemaphore { handle: vk::Semaphore::null() });
+
+        Device::add_async_compute_signal(&mut submit_info(QueueType::Compute), binary, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "compute queue")]
+    fn async_compute_rejects_other_queues() {
+        Device::add_async_compute_signal(&mut submit_info(QueueType::Graphics), timeline(), 1);
+    }
+}
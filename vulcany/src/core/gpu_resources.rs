@@ -41,3 +41,14 @@ impl ImageViewID {
         return ImageViewID { id: u64::MAX };
     }
 }
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AccelerationStructureID {
+    pub(crate) id: u64,
+}
+
+impl AccelerationStructureID {
+    pub fn null() -> AccelerationStructureID {
+        return AccelerationStructureID { id: u64::MAX };
+    }
+}
@@ -3,11 +3,14 @@ use crate::backend::{
     gpu_resources::{GpuBindlessDescriptorPool, GpuResourcePool},
     instance::InnerInstance,
 };
-use std::sync::{Arc, RwLock};
+use std::sync::{
+    Arc, Mutex, OnceLock, RwLock,
+    atomic::{AtomicBool, AtomicU64},
+};
 
 use super::device::Device;
 
-use crate::{DeviceDescription, InstanceDescription};
+use crate::{AdapterInfo, DEFAULT_NEAREST_SAMPLER_INDEX, DEFAULT_SAMPLER_INDEX, DeviceDescription, HeadlessInstanceDescription, InstanceDescription, MAX_PUSH_DESCRIPTOR_BUFFERS};
 
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 
@@ -22,10 +25,63 @@ impl Instance {
         return Instance { inner: Arc::new(inner_instance) };
     }
 
+    /// Instance without a surface, devices created from it can only do compute and offscreen rendering
+    pub fn new_headless(instance_desc: &HeadlessInstanceDescription) -> Instance {
+        let inner_instance = InnerInstance::new_headless(instance_desc);
+        return Instance { inner: Arc::new(inner_instance) };
+    }
+
+    /// Every GPU visible to the instance, in the order `DeviceDescription::adapter_index` refers to
+    pub fn enumerate_adapters(&self) -> Vec<AdapterInfo> {
+        return self.inner.enumerate_adapters();
+    }
+
     pub fn create_device(&self, device_desc: &DeviceDescription) -> Device {
         let (device, physical_device, allocator) = self.inner.create_device_data(device_desc);
-        let (graphics_queue, transfer_queue, compute_queue) = InnerInstance::create_queues(&device, &physical_device);
-        let bindless_desc = GpuBindlessDescriptorPool::new(&device, 100, 100, 100, 100);
+        let (graphics_queue, transfer_queue, compute_queue, present_queue) = InnerInstance::create_queues(&device, &physical_device);
+        // Some drivers only allow a handful of update after bind uniform buffers per stage, the push descriptor set needs some of them too
+        let indexing_props = &physical_device.descriptor_indexing_props;
+        let max_uniform_buffers = indexing_props
+            .max_per_stage_descriptor_update_after_bind_uniform_buffers
+            .saturating_sub(MAX_PUSH_DESCRIPTOR_BUFFERS)
+            .min(indexing_props.max_descriptor_set_update_after_bind_uniform_buffers)
+            .clamp(1, 100);
+        let bindless_desc = GpuBindlessDescriptorPool::new(&device, 100, 100, 100, 100, max_uniform_buffers);
+        {
+            // Kept free for the default samplers, which are only written on first use
+            let mut sampler_indices = bindless_desc.sampler_indices.lock().unwrap();
+            sampler_indices.mark_used(DEFAULT_SAMPLER_INDEX);
+            sampler_indices.mark_used(DEFAULT_NEAREST_SAMPLER_INDEX);
+        }
+        let (rt, acceleration_structure) = if device_desc.ray_tracing {
+            (
+                Some(ash::khr::ray_tracing_pipeline::Device::new(&self.inner.handle, &device)),
+                Some(ash::khr::acceleration_structure::Device::new(&self.inner.handle, &device)),
+            )
+        } else {
+            (None, None)
+        };
+
+        let extended_dynamic_state3 = if physical_device.dynamic_polygon_mode {
+            Some(ash::ext::extended_dynamic_state3::Device::new(&self.inner.handle, &device))
+        } else {
+            None
+        };
+
+        let mesh_shader = if device_desc.mesh_shaders {
+            Some(ash::ext::mesh_shader::Device::new(&self.inner.handle, &device))
+        } else {
+            None
+        };
+
+        let (push_descriptor, push_descriptor_layout) = if physical_device.push_descriptor {
+            (
+                Some(ash::khr::push_descriptor::Device::new(&self.inner.handle, &device)),
+                InnerDevice::create_push_descriptor_layout(&device),
+            )
+        } else {
+            (None, ash::vk::DescriptorSetLayout::null())
+        };
 
         return Device {
             inner: Arc::new(InnerDevice {
@@ -40,13 +96,28 @@ impl Instance {
                 image_pool: RwLock::new(GpuResourcePool::new()),
                 image_view_pool: RwLock::new(GpuResourcePool::new()),
                 sampler_pool: RwLock::new(GpuResourcePool::new()),
+                acceleration_structure_pool: RwLock::new(GpuResourcePool::new()),
+                default_sampler: OnceLock::new(),
+                default_nearest_sampler: OnceLock::new(),
 
                 //Queues
                 graphics_queue: graphics_queue,
                 transfer_queue: transfer_queue,
                 compute_queue: compute_queue,
+                present_queue: present_queue,
+                immediate_command_data: [Mutex::new(None), Mutex::new(None), Mutex::new(None)],
+                frame_counter: AtomicU64::new(0),
+                deferred_destroys: Mutex::new(Vec::new()),
+
+                rt: rt,
+                acceleration_structure: acceleration_structure,
+                extended_dynamic_state3: extended_dynamic_state3,
+                mesh_shader: mesh_shader,
+                push_descriptor: push_descriptor,
+                push_descriptor_layout: push_descriptor_layout,
 
-                rt: None,
+                on_device_lost: device_desc.on_device_lost.clone(),
+                device_lost: AtomicBool::new(false),
             }),
         };
     }
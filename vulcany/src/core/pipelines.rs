@@ -2,8 +2,8 @@ use ash::vk;
 use std::sync::Arc;
 
 use crate::{
-    ComputePipelineDescription, RasterizationPipelineDescription, ShaderStages,
-    backend::pipelines::{InnerComputePipeline, InnerPipelineManager, InnerRasterizationPipeline},
+    BufferID, ComputePipelineDescription, Format, RasterizationPipelineDescription, RayTracingPipelineDescription, ShaderStages,
+    backend::pipelines::{InnerComputePipeline, InnerPipelineManager, InnerRasterizationPipeline, InnerRayTracingPipeline},
 };
 
 #[derive(Clone)]
@@ -26,16 +26,44 @@ impl PipelineManager {
     }
 
     pub fn create_compute_pipeline(&self, compute_pipeline_desc: &ComputePipelineDescription) -> ComputePipeline {
-        let (pipeline, layout) = self.inner.create_compute_pipeline(compute_pipeline_desc);
+        let (pipeline, layout, workgroup_size) = self.inner.create_compute_pipeline(compute_pipeline_desc);
         return ComputePipeline {
             inner: Arc::new(InnerComputePipeline {
                 handle: pipeline,
                 layout: layout,
+                workgroup_size: workgroup_size,
                 desc: compute_pipeline_desc.clone(),
                 manager: self.inner.clone(),
             }),
         };
     }
+
+    /// Creates a copy of `pipeline` with its first color output switched to `new_format`.
+    /// Meant for pipelines that render to the swapchain after `Swapchain::format_changed`
+    pub fn rebuild_for_format(&self, pipeline: &RasterizationPipeline, new_format: Format) -> RasterizationPipeline {
+        let mut desc = pipeline.inner.desc.clone();
+        assert!(!desc.outputs.color.is_empty(), "Cannot rebuild a pipeline without color outputs for a new format");
+
+        desc.outputs.color[0] = new_format;
+
+        return self.create_rasterization_pipeline(&desc);
+    }
+
+    /// Requires a device created with ray tracing enabled
+    pub fn create_ray_tracing_pipeline(&self, rt_pipeline_desc: &RayTracingPipelineDescription) -> RayTracingPipeline {
+        let (pipeline, layout) = self.inner.create_rt_pipeline(rt_pipeline_desc);
+        let sbt = self.inner.create_sbt(rt_pipeline_desc, pipeline, &self.inner.device.physical_device.rt_props);
+
+        return RayTracingPipeline {
+            inner: Arc::new(InnerRayTracingPipeline {
+                handle: pipeline,
+                layout: layout,
+                desc: rt_pipeline_desc.clone(),
+                sbt: sbt,
+                manager: self.inner.clone(),
+            }),
+        };
+    }
 }
 
 pub struct RasterizationPipeline {
@@ -46,11 +74,41 @@ pub struct ComputePipeline {
     pub(crate) inner: Arc<InnerComputePipeline>,
 }
 
+pub struct RayTracingPipeline {
+    pub(crate) inner: Arc<InnerRayTracingPipeline>,
+}
+
+impl ComputePipeline {
+    /// Local workgroup size declared in the shader
+    pub fn workgroup_size(&self) -> [u32; 3] {
+        return self.inner.workgroup_size;
+    }
+}
+
+impl RayTracingPipeline {
+    pub fn shader_binding_table(&self) -> &ShaderBindingTable {
+        return &self.inner.sbt;
+    }
+}
+
+/// Strided device address regions of the raygen, miss and hit records of a ray tracing pipeline
+pub struct ShaderBindingTable {
+    pub(crate) buffer: BufferID,
+    pub(crate) rgen: vk::StridedDeviceAddressRegionKHR,
+    pub(crate) miss: vk::StridedDeviceAddressRegionKHR,
+    pub(crate) hit: vk::StridedDeviceAddressRegionKHR,
+    pub(crate) callable: vk::StridedDeviceAddressRegionKHR,
+}
+
 pub trait Pipeline {
     fn get_push_const_shader_stage(&self) -> ShaderStages;
     fn get_layout(&self) -> vk::PipelineLayout;
     fn get_handle(&self) -> vk::Pipeline;
     fn get_bind_point(&self) -> vk::PipelineBindPoint;
+    /// Multiview mask the pipeline was created with, `None` for pipelines used outside of a rendering
+    fn get_view_mask(&self) -> Option<u32> {
+        return None;
+    }
 }
 
 impl Pipeline for RasterizationPipeline {
@@ -66,6 +124,9 @@ impl Pipeline for RasterizationPipeline {
     fn get_layout(&self) -> vk::PipelineLayout {
         return self.inner.layout;
     }
+    fn get_view_mask(&self) -> Option<u32> {
+        return Some(self.inner.desc.outputs.view_mask);
+    }
 }
 
 impl Pipeline for ComputePipeline {
@@ -76,7 +137,22 @@ impl Pipeline for ComputePipeline {
         return self.inner.handle;
     }
     fn get_bind_point(&self) -> vk::PipelineBindPoint {
-        return vk::PipelineBindPoint::GRAPHICS;
+        return vk::PipelineBindPoint::COMPUTE;
+    }
+    fn get_layout(&self) -> vk::PipelineLayout {
+        return self.inner.layout;
+    }
+}
+
+impl Pipeline for RayTracingPipeline {
+    fn get_push_const_shader_stage(&self) -> ShaderStages {
+        return self.inner.desc.push_constants.stage_flags;
+    }
+    fn get_handle(&self) -> vk::Pipeline {
+        return self.inner.handle;
+    }
+    fn get_bind_point(&self) -> vk::PipelineBindPoint {
+        return vk::PipelineBindPoint::RAY_TRACING_KHR;
     }
     fn get_layout(&self) -> vk::PipelineLayout {
         return self.inner.layout;
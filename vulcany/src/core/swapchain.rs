@@ -1,6 +1,9 @@
 use std::sync::Arc;
 
-use crate::{ImageID, ImageViewID, Semaphore, backend::swapchain::InnerSwapchain};
+use crate::{
+    AccessType, Barrier, BlitInfo, BlitRegion, CommandRecorder, Extent2D, Filter, Format, ImageAspect, ImageBarrier, ImageID, ImageLayout, ImageSubresourceLayers, ImageUsage, ImageViewID, Offset3D,
+    PipelineStage, Semaphore, backend::swapchain::InnerSwapchain,
+};
 
 /// Swapchain abstraction
 /// Contains image and present semaphores internally.
@@ -19,4 +22,107 @@ impl Swapchain {
     pub fn present(&self) {
         self.inner.present();
     }
+
+    /// Format of the swapchain images, None if `Format` has no variant for it
+    pub fn format(&self) -> Option<Format> {
+        return Format::from_vk_format(self.inner.format);
+    }
+
+    /// Whether the images use an sRGB format, writes and blits into them gamma encode linear values
+    pub fn is_srgb(&self) -> bool {
+        return self.inner.is_srgb();
+    }
+
+    /// Number of images the swapchain actually has, the requested count is clamped to what the surface allows
+    /// and the driver may create more than requested
+    pub fn image_count(&self) -> u32 {
+        return self.inner.images.len() as u32;
+    }
+
+    pub fn extent(&self) -> Extent2D {
+        return Extent2D {
+            width: self.inner.extent.width,
+            height: self.inner.extent.height,
+        };
+    }
+
+    /// Records a blit of `src` into the most recently acquired image and leaves that image ready to present.
+    ///
+    /// `src` must be in `ImageLayout::TransferSrc` and the swapchain needs `ImageUsage::TRANSFER_DST`.
+    /// The submission must wait on the image semaphore at `PipelineStage::Transfer`, call `present` after submitting.
+    /// The source is treated as linear. On an sRGB swapchain (see `is_srgb`) the blit gamma encodes it,
+    /// other formats receive the values unchanged
+    pub fn present_from(&self, recorder: &mut CommandRecorder, src: ImageID, src_extent: Extent2D) {
+        assert!(
+            self.inner.usage.flags.contains(ImageUsage::TRANSFER_DST.flags),
+            "present_from needs a swapchain created with ImageUsage::TRANSFER_DST"
+        );
+
+        let index = self.inner.last_acquired.load(std::sync::atomic::Ordering::Relaxed) as usize;
+        let dst = self.inner.images[index];
+
+        let color_layer = ImageSubresourceLayers {
+            aspect: ImageAspect::Color,
+            mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        recorder.pipeline_barrier(&[Barrier::Image(ImageBarrier {
+            image: dst,
+            old_layout: ImageLayout::Undefined,
+            new_layout: ImageLayout::TransferDst,
+            src_stage: PipelineStage::Transfer,
+            dst_stage: PipelineStage::Transfer,
+            src_access: AccessType::None,
+            dst_access: AccessType::TransferWrite,
+            ..Default::default()
+        })]);
+
+        recorder.blit_image2(&BlitInfo {
+            src_image: src,
+            src_layout: ImageLayout::TransferSrc,
+            dst_image: dst,
+            dst_layout: ImageLayout::TransferDst,
+            regions: vec![BlitRegion {
+                src_subresource: color_layer,
+                src_offsets: [
+                    Offset3D { x: 0, y: 0, z: 0 },
+                    Offset3D {
+                        x: src_extent.width as i32,
+                        y: src_extent.height as i32,
+                        z: 1,
+                    },
+                ],
+                dst_subresource: color_layer,
+                dst_offsets: [
+                    Offset3D { x: 0, y: 0, z: 0 },
+                    Offset3D {
+                        x: self.inner.extent.width as i32,
+                        y: self.inner.extent.height as i32,
+                        z: 1,
+                    },
+                ],
+            }],
+            filter: Filter::Linear,
+        });
+
+        recorder.pipeline_barrier(&[Barrier::Image(ImageBarrier {
+            image: dst,
+            old_layout: ImageLayout::TransferDst,
+            new_layout: ImageLayout::PresentSrc,
+            src_stage: PipelineStage::Transfer,
+            dst_stage: PipelineStage::BottomOfPipe,
+            src_access: AccessType::TransferWrite,
+            dst_access: AccessType::None,
+            ..Default::default()
+        })]);
+    }
+
+    /// Whether this swapchain was recreated with a different format than the one it replaced.
+    /// Pipelines with the old format as a color output are incompatible with the new images
+    pub fn format_changed(&self) -> bool {
+        return self.inner.format_changed;
+    }
 }
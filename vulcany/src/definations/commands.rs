@@ -1,11 +1,17 @@
 use ash::vk;
+use std::ops::BitOr;
 use std::u64;
 
 use crate::*;
 
 use crate::{BufferID, ExecutableCommandBuffer, Fence, ImageID, ImageViewID, Semaphore};
 
-#[derive(Clone, Copy, PartialEq)]
+/// Set the push descriptors live in, set 0 is the bindless set
+pub const PUSH_DESCRIPTOR_SET: u32 = 1;
+/// Uniform buffer bindings available in the push descriptor set
+pub const MAX_PUSH_DESCRIPTOR_BUFFERS: u32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum QueueType {
     Graphics,
     Transfer,
@@ -29,9 +35,48 @@ impl CommandBufferUsage {
     }
 }
 
+/// Flags of the command pool backing a `CommandRecorder`
+///
+/// Can be combined using Bitwise Or (|)
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct CommandPoolFlags {
+    pub(crate) flags: vk::CommandPoolCreateFlags,
+}
+
+impl CommandPoolFlags {
+    /// Buffers can only be reset all at once with `CommandRecorder::reset`
+    pub const NONE: Self = Self {
+        flags: vk::CommandPoolCreateFlags::empty(),
+    };
+
+    /// Buffers are short lived, lets the driver optimize allocation for one shot recording
+    pub const TRANSIENT: Self = Self {
+        flags: vk::CommandPoolCreateFlags::TRANSIENT,
+    };
+
+    /// Buffers can be reset one at a time with `CommandRecorder::reset_buffer`
+    pub const RESET_INDIVIDUAL: Self = Self {
+        flags: vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+    };
+
+    pub fn contains(&self, other: Self) -> bool {
+        return self.flags.contains(other.flags);
+    }
+}
+
+impl BitOr for CommandPoolFlags {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self::Output {
+        Self { flags: self.flags | other.flags }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum IndexType {
     Uint32,
     Uint16,
+    /// Needs VK_EXT_index_type_uint8, check `Device::supports_index_type_uint8`
+    Uint8,
 }
 
 impl IndexType {
@@ -39,6 +84,7 @@ impl IndexType {
         match self {
             Self::Uint32 => vk::IndexType::UINT32,
             Self::Uint16 => vk::IndexType::UINT16,
+            Self::Uint8 => vk::IndexType::UINT8_EXT,
         }
     }
 }
@@ -85,6 +131,8 @@ impl StoreOp {
     }
 }
 
+/// Depth and stencil attachments can only be resolved with `SampleZero`, `Min` or `Max`.
+/// `Average` is only valid for color attachments
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ResolveMode {
     None,
@@ -148,6 +196,11 @@ impl ClearValue {
     pub const fn depth_one() -> Self {
         Self::DepthStencil { depth: 1.0, stencil: 0 }
     }
+
+    /// Depth clear for reverse-Z, pair it with `DepthStencilOptions::reverse_z`
+    pub const fn depth_zero() -> Self {
+        Self::DepthStencil { depth: 0.0, stencil: 0 }
+    }
 }
 
 pub struct RenderingAttachment {
@@ -182,6 +235,8 @@ pub enum RenderingFlags {
     ContentsSecondaryCommandBuffers,
     Suspending,
     Resuming,
+    /// Resumes a rendering from the previous command buffer and suspends it again for the next one
+    ResumingAndSuspending,
 }
 
 impl RenderingFlags {
@@ -192,6 +247,7 @@ impl RenderingFlags {
             Self::ContentsSecondaryCommandBuffers => vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS,
             Self::Suspending => vk::RenderingFlags::SUSPENDING,
             Self::Resuming => vk::RenderingFlags::RESUMING,
+            Self::ResumingAndSuspending => vk::RenderingFlags::from_raw(vk::RenderingFlags::RESUMING.as_raw() | vk::RenderingFlags::SUSPENDING.as_raw()),
         }
     }
 }
@@ -206,6 +262,26 @@ pub struct RenderingBeginInfo {
     pub stencil_attachment: Option<RenderingAttachment>,
 }
 
+impl RenderingBeginInfo {
+    /// Renders into a single color attachment covering `extent`, cleared to black.
+    /// Pass a zero extent to let `CommandRecorder::begin_rendering_auto` fill it in
+    pub fn from_color(image_view: ImageViewID, extent: Extent2D) -> RenderingBeginInfo {
+        return RenderingBeginInfo {
+            render_area: RenderArea {
+                offset: Offset2D { x: 0, y: 0 },
+                extent: extent,
+            },
+            layer_count: 1,
+            color_attachments: vec![RenderingAttachment {
+                image_view: image_view,
+                image_layout: ImageLayout::ColorAttachment,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+    }
+}
+
 impl Default for RenderingBeginInfo {
     fn default() -> Self {
         return Self {
@@ -223,6 +299,27 @@ impl Default for RenderingBeginInfo {
     }
 }
 
+/// Describes the rendering a secondary command buffer will be executed in
+pub struct SecondaryInheritanceInfo {
+    pub color_formats: Vec<Format>,
+    pub depth_format: Option<Format>,
+    pub stencil_format: Option<Format>,
+    pub samples: SampleCount,
+    pub view_mask: u32,
+}
+
+impl Default for SecondaryInheritanceInfo {
+    fn default() -> Self {
+        return Self {
+            color_formats: Vec::new(),
+            depth_format: None,
+            stencil_format: None,
+            samples: SampleCount::Type1,
+            view_mask: 0,
+        };
+    }
+}
+
 // Compute
 #[derive(Clone, Debug)]
 pub struct DispatchInfo {
@@ -237,6 +334,16 @@ pub struct DispatchIndirectInfo {
     pub offset: u64,
 }
 
+/// Iterative compute over two storage images that swap roles every iteration.
+/// Both images must be in `ImageLayout::General`
+pub struct PingPongComputeInfo {
+    pub images: [ImageID; 2],
+    /// Storage image descriptor indices of `images`, pushed to the shader every iteration
+    pub storage_indices: [u32; 2],
+    pub iterations: u32,
+    pub dispatch: DispatchInfo,
+}
+
 // Copy commands
 pub struct BufferCopyInfo {
     pub src_buffer: BufferID,
@@ -246,6 +353,14 @@ pub struct BufferCopyInfo {
     pub size: u64,
 }
 
+/// One region of `CommandRecorder::copy_buffer_regions`
+#[derive(Clone, Copy)]
+pub struct BufferCopyRegion {
+    pub src_offset: u64,
+    pub dst_offset: u64,
+    pub size: u64,
+}
+
 #[derive(Clone, Copy)]
 pub struct BufferImageCopyInfo {
     pub src_buffer: BufferID,
@@ -254,6 +369,17 @@ pub struct BufferImageCopyInfo {
     pub region: BufferImageCopyRegion,
 }
 
+/// Image to buffer copy, eg. for screenshots or reading back a render target.
+/// The region describes the same buffer and image areas as for buffer to image copies
+#[derive(Clone, Copy)]
+pub struct ImageBufferCopyInfo {
+    pub src_image: ImageID,
+    /// Usually `ImageLayout::TransferSrc`
+    pub src_image_layout: ImageLayout,
+    pub dst_buffer: BufferID,
+    pub region: BufferImageCopyRegion,
+}
+
 #[derive(Clone, Copy)]
 pub struct BufferImageCopyRegion {
     pub buffer_offset: u64,
@@ -305,11 +431,18 @@ pub struct BlitRegion {
 pub enum PipelineStage {
     TopOfPipe,
     BottomOfPipe,
+    VertexInput,
     VertexShader,
     FragmentShader,
+    /// Depth and stencil tests before fragment shading, where depth attachments are loaded
+    EarlyFragmentTests,
+    /// Depth and stencil tests after fragment shading, where depth writes finish
+    LateFragmentTests,
     ComputeShader,
     ColorAttachmentOutput,
     Transfer,
+    /// Host reads and writes of mapped memory, used to make device writes visible for readback
+    Host,
     AllCommands,
 }
 
@@ -318,11 +451,15 @@ impl PipelineStage {
         match self {
             PipelineStage::TopOfPipe => vk::PipelineStageFlags2::TOP_OF_PIPE,
             PipelineStage::BottomOfPipe => vk::PipelineStageFlags2::BOTTOM_OF_PIPE,
+            PipelineStage::VertexInput => vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT,
             PipelineStage::VertexShader => vk::PipelineStageFlags2::VERTEX_SHADER,
             PipelineStage::FragmentShader => vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            PipelineStage::EarlyFragmentTests => vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS,
+            PipelineStage::LateFragmentTests => vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
             PipelineStage::ComputeShader => vk::PipelineStageFlags2::COMPUTE_SHADER,
             PipelineStage::ColorAttachmentOutput => vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
             PipelineStage::Transfer => vk::PipelineStageFlags2::TRANSFER,
+            PipelineStage::Host => vk::PipelineStageFlags2::HOST,
             PipelineStage::AllCommands => vk::PipelineStageFlags2::ALL_COMMANDS,
         }
     }
@@ -343,6 +480,7 @@ pub enum AccessType {
     DepthStencilWrite,
     TransferRead,
     TransferWrite,
+    HostRead,
 }
 
 impl AccessType {
@@ -361,6 +499,7 @@ impl AccessType {
             AccessType::DepthStencilWrite => vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
             AccessType::TransferRead => vk::AccessFlags2::TRANSFER_READ,
             AccessType::TransferWrite => vk::AccessFlags2::TRANSFER_WRITE,
+            AccessType::HostRead => vk::AccessFlags2::HOST_READ,
         }
     }
 }
@@ -394,6 +533,7 @@ pub struct ImageBarrier {
     pub dst_stage: PipelineStage,
     pub src_access: AccessType,
     pub dst_access: AccessType,
+    /// Set both to different queues to transfer ownership, record the barrier on both queues (release then acquire)
     pub src_queue: QueueType,
     pub dst_queue: QueueType,
     pub base_mip: u32,
@@ -423,6 +563,13 @@ impl Default for ImageBarrier {
     }
 }
 
+impl ImageBarrier {
+    // True if the barrier covers every mip and layer of an image with `mip_levels` mips and `array_layers` layers
+    pub(crate) fn covers_whole_image(&self, mip_levels: u32, array_layers: u32) -> bool {
+        return self.base_mip == 0 && self.level_count >= mip_levels && self.base_layer == 0 && self.layer_count >= array_layers;
+    }
+}
+
 #[derive(Clone)]
 pub struct BufferBarrier {
     pub buffer: BufferID,
@@ -430,6 +577,7 @@ pub struct BufferBarrier {
     pub dst_stage: PipelineStage,
     pub src_access: AccessType,
     pub dst_access: AccessType,
+    /// Set both to different queues to transfer ownership, record the barrier on both queues (release then acquire)
     pub src_queue: QueueType,
     pub dst_queue: QueueType,
     pub offset: u64,
@@ -459,6 +607,62 @@ pub enum Barrier {
     Buffer(BufferBarrier),
 }
 
+impl Barrier {
+    /// Layout transition of every mip and layer, with the stages and access masks derived from the two layouts,
+    /// eg. `Undefined -> ColorAttachment` waits on nothing and blocks color attachment writes.
+    /// Depth layouts use the depth aspect, widened to depth and stencil for formats with a stencil component when recorded.
+    /// Build an `ImageBarrier` directly for partial transitions or other stages
+    pub fn image_transition(image: ImageID, from: ImageLayout, to: ImageLayout) -> Barrier {
+        let (src_stage, src_access) = Barrier::last_use(from);
+        let (dst_stage, dst_access) = Barrier::first_use(to);
+
+        let aspect = match (from, to) {
+            (ImageLayout::DepthStencilAttachment | ImageLayout::DepthStencilReadOnly, _) | (_, ImageLayout::DepthStencilAttachment | ImageLayout::DepthStencilReadOnly) => ImageAspect::Depth,
+            _ => ImageAspect::Color,
+        };
+
+        return Barrier::Image(ImageBarrier {
+            image: image,
+            aspect: aspect,
+            old_layout: from,
+            new_layout: to,
+            src_stage: src_stage,
+            dst_stage: dst_stage,
+            src_access: src_access,
+            dst_access: dst_access,
+            level_count: vk::REMAINING_MIP_LEVELS,
+            layer_count: vk::REMAINING_ARRAY_LAYERS,
+            ..Default::default()
+        });
+    }
+
+    // Reads need no availability, so read only layouts only wait on the stage
+    const fn last_use(layout: ImageLayout) -> (PipelineStage, AccessType) {
+        return match layout {
+            ImageLayout::Undefined | ImageLayout::PresentSrc => (PipelineStage::TopOfPipe, AccessType::None),
+            ImageLayout::General => (PipelineStage::ComputeShader, AccessType::ShaderWrite),
+            ImageLayout::ColorAttachment => (PipelineStage::ColorAttachmentOutput, AccessType::ColorAttachmentWrite),
+            ImageLayout::DepthStencilAttachment => (PipelineStage::LateFragmentTests, AccessType::DepthStencilWrite),
+            ImageLayout::DepthStencilReadOnly | ImageLayout::ShaderReadOnly => (PipelineStage::FragmentShader, AccessType::None),
+            ImageLayout::TransferSrc => (PipelineStage::Transfer, AccessType::None),
+            ImageLayout::TransferDst => (PipelineStage::Transfer, AccessType::TransferWrite),
+        };
+    }
+
+    const fn first_use(layout: ImageLayout) -> (PipelineStage, AccessType) {
+        return match layout {
+            ImageLayout::Undefined => panic!("Images cannot be transitioned to ImageLayout::Undefined"),
+            ImageLayout::PresentSrc => (PipelineStage::BottomOfPipe, AccessType::None),
+            ImageLayout::General => (PipelineStage::ComputeShader, AccessType::ShaderRead),
+            ImageLayout::ColorAttachment => (PipelineStage::ColorAttachmentOutput, AccessType::ColorAttachmentWrite),
+            ImageLayout::DepthStencilAttachment => (PipelineStage::EarlyFragmentTests, AccessType::DepthStencilWrite),
+            ImageLayout::DepthStencilReadOnly | ImageLayout::ShaderReadOnly => (PipelineStage::FragmentShader, AccessType::ShaderRead),
+            ImageLayout::TransferSrc => (PipelineStage::Transfer, AccessType::TransferRead),
+            ImageLayout::TransferDst => (PipelineStage::Transfer, AccessType::TransferWrite),
+        };
+    }
+}
+
 //Submit info
 pub struct SemaphoreInfo {
     pub semaphore: Semaphore,
@@ -472,3 +676,132 @@ pub struct QueueSubmitInfo {
     pub wait_semaphores: Vec<SemaphoreInfo>,
     pub signal_semaphores: Vec<SemaphoreInfo>,
 }
+
+// Queries
+/// Counters collected by a pipeline statistics query
+///
+/// Can be combined using Bitwise Or (|)
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct PipelineStatistics {
+    pub(crate) flags: vk::QueryPipelineStatisticFlags,
+}
+
+impl PipelineStatistics {
+    pub const INPUT_ASSEMBLY_VERTICES: Self = Self {
+        flags: vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES,
+    };
+
+    pub const INPUT_ASSEMBLY_PRIMITIVES: Self = Self {
+        flags: vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES,
+    };
+
+    pub const VERTEX_SHADER_INVOCATIONS: Self = Self {
+        flags: vk::QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS,
+    };
+
+    pub const CLIPPING_INVOCATIONS: Self = Self {
+        flags: vk::QueryPipelineStatisticFlags::CLIPPING_INVOCATIONS,
+    };
+
+    pub const CLIPPING_PRIMITIVES: Self = Self {
+        flags: vk::QueryPipelineStatisticFlags::CLIPPING_PRIMITIVES,
+    };
+
+    /// Useful for overdraw analysis when compared against the number of covered pixels
+    pub const FRAGMENT_SHADER_INVOCATIONS: Self = Self {
+        flags: vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS,
+    };
+
+    pub const COMPUTE_SHADER_INVOCATIONS: Self = Self {
+        flags: vk::QueryPipelineStatisticFlags::COMPUTE_SHADER_INVOCATIONS,
+    };
+}
+
+impl std::ops::BitOr for PipelineStatistics {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self::Output {
+        Self { flags: self.flags | other.flags }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueryType {
+    /// Number of samples that passed the depth and stencil tests
+    Occlusion,
+    /// One counter per enabled statistic, in the order of the vulkan flag bits
+    PipelineStatistics(PipelineStatistics),
+    /// Raw ticks, multiply by `DeviceLimits::timestamp_period` to get nanoseconds
+    Timestamp,
+}
+
+impl QueryType {
+    pub(crate) fn to_vk(&self) -> vk::QueryType {
+        match self {
+            QueryType::Occlusion => vk::QueryType::OCCLUSION,
+            QueryType::PipelineStatistics(_) => vk::QueryType::PIPELINE_STATISTICS,
+            QueryType::Timestamp => vk::QueryType::TIMESTAMP,
+        }
+    }
+
+    /// Number of u64 values every query of this type produces
+    pub fn values_per_query(&self) -> u32 {
+        match self {
+            QueryType::PipelineStatistics(statistics) => statistics.flags.as_raw().count_ones(),
+            _ => 1,
+        }
+    }
+}
+
+/// Copies query results into a buffer as tightly packed u64 values
+pub struct QueryCopyInfo {
+    pub query_pool: QueryPool,
+    pub first_query: u32,
+    pub query_count: u32,
+    pub dst_buffer: BufferID,
+    pub dst_offset: u64,
+    /// Don't wait for the queries, instead append a u64 after every query that is non zero once its result is available.
+    /// Lets a later pass skip unfinished queries instead of stalling the queue
+    pub with_availability: bool,
+}
+
+impl QueryCopyInfo {
+    /// Size in bytes of the copied results, `dst_buffer` must have this much space after `dst_offset`
+    pub fn size(&self) -> u64 {
+        let values = self.query_pool.query_type.values_per_query() + self.with_availability as u32;
+        return self.query_count as u64 * values as u64 * std::mem::size_of::<u64>() as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_barriers_do_not_cover_the_image() {
+        let mip = ImageBarrier {
+            base_mip: 2,
+            level_count: 1,
+            ..Default::default()
+        };
+        let layer = ImageBarrier {
+            level_count: vk::REMAINING_MIP_LEVELS,
+            layer_count: 1,
+            ..Default::default()
+        };
+
+        assert!(!mip.covers_whole_image(4, 1));
+        assert!(!layer.covers_whole_image(1, 6));
+    }
+
+    #[test]
+    fn full_barriers_cover_the_image() {
+        let remaining = ImageBarrier {
+            level_count: vk::REMAINING_MIP_LEVELS,
+            layer_count: vk::REMAINING_ARRAY_LAYERS,
+            ..Default::default()
+        };
+
+        assert!(ImageBarrier::default().covers_whole_image(1, 1));
+        assert!(remaining.covers_whole_image(8, 6));
+    }
+}
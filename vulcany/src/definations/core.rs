@@ -1,3 +1,4 @@
+use crate::ImageUsage;
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 use std::sync::Arc;
 
@@ -9,28 +10,161 @@ pub enum ApiVersion {
     VkApi1_3 = ash::vk::API_VERSION_1_3,
 }
 
+/// Severity of a validation layer message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DebugSeverity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+impl DebugSeverity {
+    pub(crate) fn from_vk(severity: ash::vk::DebugUtilsMessageSeverityFlagsEXT) -> DebugSeverity {
+        use ash::vk::DebugUtilsMessageSeverityFlagsEXT as S;
+
+        if severity.contains(S::ERROR) {
+            return DebugSeverity::Error;
+        } else if severity.contains(S::WARNING) {
+            return DebugSeverity::Warning;
+        } else if severity.contains(S::INFO) {
+            return DebugSeverity::Info;
+        }
+
+        return DebugSeverity::Verbose;
+    }
+}
+
+/// Kind of a validation layer message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugMessageType {
+    General,
+    Validation,
+    Performance,
+    DeviceAddressBinding,
+}
+
+impl DebugMessageType {
+    pub(crate) fn from_vk(types: ash::vk::DebugUtilsMessageTypeFlagsEXT) -> DebugMessageType {
+        use ash::vk::DebugUtilsMessageTypeFlagsEXT as T;
+
+        if types.contains(T::VALIDATION) {
+            return DebugMessageType::Validation;
+        } else if types.contains(T::PERFORMANCE) {
+            return DebugMessageType::Performance;
+        } else if types.contains(T::DEVICE_ADDRESS_BINDING) {
+            return DebugMessageType::DeviceAddressBinding;
+        }
+
+        return DebugMessageType::General;
+    }
+}
+
+/// Receives every validation layer message. Called from driver threads, so it has to be thread safe
+pub type DebugCallback = Arc<dyn Fn(DebugSeverity, DebugMessageType, &str) + Send + Sync>;
+
 /// High level abstraction for instance creation
 /// Surface gets created along with the instance
 pub struct InstanceDescription<W: HasDisplayHandle + HasWindowHandle> {
     pub api_version: ApiVersion,
     pub enable_validation_layers: bool,
+    /// Replaces printing validation messages to stdout. Only used with validation layers enabled
+    pub debug_callback: Option<DebugCallback>,
     pub window: Arc<W>,
 }
 
+/// Instance without a window, for compute only or offscreen work.
+/// Devices created from it have no present queue and cannot create swapchains
+pub struct HeadlessInstanceDescription {
+    pub api_version: ApiVersion,
+    pub enable_validation_layers: bool,
+    /// Replaces printing validation messages to stdout. Only used with validation layers enabled
+    pub debug_callback: Option<DebugCallback>,
+}
+
+/// Called once when the device is lost, before the failing call panics.
+/// Called from whichever thread hit the error, so it has to be thread safe
+pub type DeviceLostCallback = Arc<dyn Fn() + Send + Sync>;
+
 /// Very high level abstraction for device creation
 /// Need to add more options
 pub struct DeviceDescription {
     pub use_compute_queue: bool,
     pub use_transfer_queue: bool,
+    /// Enables VK_KHR_ray_tracing_pipeline, acceleration structures and ray queries
     pub ray_tracing: bool,
+    /// Enables VK_EXT_mesh_shader with task shaders, device creation panics without it
+    pub mesh_shaders: bool,
+    /// Index from `Instance::enumerate_adapters` to force a GPU, None picks the best suitable one
+    pub adapter_index: Option<u32>,
+    /// Notified when a submit, wait, acquire or present returns VK_ERROR_DEVICE_LOST, eg. to save state before the panic
+    pub on_device_lost: Option<DeviceLostCallback>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AdapterType {
+    Discrete,
+    Integrated,
+    Virtual,
+    Cpu,
+    Other,
+}
+
+impl AdapterType {
+    pub(crate) fn from_vk(device_type: ash::vk::PhysicalDeviceType) -> AdapterType {
+        return match device_type {
+            ash::vk::PhysicalDeviceType::DISCRETE_GPU => AdapterType::Discrete,
+            ash::vk::PhysicalDeviceType::INTEGRATED_GPU => AdapterType::Integrated,
+            ash::vk::PhysicalDeviceType::VIRTUAL_GPU => AdapterType::Virtual,
+            ash::vk::PhysicalDeviceType::CPU => AdapterType::Cpu,
+            _ => AdapterType::Other,
+        };
+    }
+}
+
+/// A GPU visible to the instance
+#[derive(Clone, Debug)]
+pub struct AdapterInfo {
+    /// Pass as `DeviceDescription::adapter_index` to create the device on this adapter
+    pub index: u32,
+    pub name: String,
+    pub adapter_type: AdapterType,
+    /// Total size of the device local memory heaps in bytes
+    pub device_local_memory: u64,
+    /// Whether the adapter can present to the surface and has everything else the library needs
+    pub suitable: bool,
+}
+
+/// Errors a device operation can fail with instead of panicking
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeviceError {
+    DeviceLost,
+    OutOfHostMemory,
+    OutOfDeviceMemory,
+    /// Any other VkResult, as its raw value
+    Other(i32),
+}
+
+impl DeviceError {
+    pub(crate) fn from_vk(result: ash::vk::Result) -> DeviceError {
+        return match result {
+            ash::vk::Result::ERROR_DEVICE_LOST => DeviceError::DeviceLost,
+            ash::vk::Result::ERROR_OUT_OF_HOST_MEMORY => DeviceError::OutOfHostMemory,
+            ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => DeviceError::OutOfDeviceMemory,
+            _ => DeviceError::Other(result.as_raw()),
+        };
+    }
 }
 
 /// High level swapchain description
 #[derive(Clone)]
 pub struct SwapchainDescription {
+    /// Clamped to the range the surface supports, see `Swapchain::image_count` for the actual count
     pub image_count: u32,
     pub width: u32,
     pub height: u32,
+    /// Must be supported by the surface, eg. `TRANSFER_DST` to blit into the images or `STORAGE` to write them from compute
+    pub usage: ImageUsage,
 }
 
 /// Wrapper for vk::Extent3D
@@ -93,3 +227,206 @@ impl Offset2D {
         return ash::vk::Offset2D { x: self.x, y: self.y };
     }
 }
+
+/// Commonly needed subset of vk::PhysicalDeviceLimits
+#[derive(Clone, Copy, Debug)]
+pub struct DeviceLimits {
+    pub max_image_dimension_1d: u32,
+    pub max_image_dimension_2d: u32,
+    pub max_image_dimension_3d: u32,
+    pub max_image_dimension_cube: u32,
+    pub max_image_array_layers: u32,
+    pub max_storage_buffer_range: u32,
+    pub max_uniform_buffer_range: u32,
+    pub max_push_constants_size: u32,
+    pub max_memory_allocation_count: u32,
+    pub max_sampler_anisotropy: f32,
+    pub max_color_attachments: u32,
+    pub max_viewport_dimensions: [u32; 2],
+    pub max_compute_shared_memory_size: u32,
+    pub max_compute_work_group_count: [u32; 3],
+    pub max_compute_work_group_invocations: u32,
+    pub max_compute_work_group_size: [u32; 3],
+    pub min_uniform_buffer_offset_alignment: u64,
+    pub min_storage_buffer_offset_alignment: u64,
+    /// Nanoseconds per timestamp query tick
+    pub timestamp_period: f32,
+}
+
+impl DeviceLimits {
+    pub(crate) fn from_vk(limits: &ash::vk::PhysicalDeviceLimits) -> DeviceLimits {
+        return DeviceLimits {
+            max_image_dimension_1d: limits.max_image_dimension1_d,
+            max_image_dimension_2d: limits.max_image_dimension2_d,
+            max_image_dimension_3d: limits.max_image_dimension3_d,
+            max_image_dimension_cube: limits.max_image_dimension_cube,
+            max_image_array_layers: limits.max_image_array_layers,
+            max_storage_buffer_range: limits.max_storage_buffer_range,
+            max_uniform_buffer_range: limits.max_uniform_buffer_range,
+            max_push_constants_size: limits.max_push_constants_size,
+            max_memory_allocation_count: limits.max_memory_allocation_count,
+            max_sampler_anisotropy: limits.max_sampler_anisotropy,
+            max_color_attachments: limits.max_color_attachments,
+            max_viewport_dimensions: limits.max_viewport_dimensions,
+            max_compute_shared_memory_size: limits.max_compute_shared_memory_size,
+            max_compute_work_group_count: limits.max_compute_work_group_count,
+            max_compute_work_group_invocations: limits.max_compute_work_group_invocations,
+            max_compute_work_group_size: limits.max_compute_work_group_size,
+            min_uniform_buffer_offset_alignment: limits.min_uniform_buffer_offset_alignment,
+            min_storage_buffer_offset_alignment: limits.min_storage_buffer_offset_alignment,
+            timestamp_period: limits.timestamp_period,
+        };
+    }
+}
+
+/// What a format can be used for on this device, for optimally tiled images unless noted
+#[derive(Clone, Copy, Debug)]
+pub struct FormatSupport {
+    pub sampled: bool,
+    /// Can be sampled with `Filter::Linear`
+    pub sampled_linear_filter: bool,
+    pub storage: bool,
+    pub color_attachment: bool,
+    /// Color attachment with blending
+    pub color_attachment_blend: bool,
+    pub depth_stencil_attachment: bool,
+    pub blit_src: bool,
+    pub blit_dst: bool,
+    pub transfer_src: bool,
+    pub transfer_dst: bool,
+    /// Usable as a vertex attribute format
+    pub vertex_buffer: bool,
+}
+
+impl FormatSupport {
+    pub(crate) fn from_vk(properties: &ash::vk::FormatProperties) -> FormatSupport {
+        use ash::vk::FormatFeatureFlags;
+        let features = properties.optimal_tiling_features;
+
+        return FormatSupport {
+            sampled: features.contains(FormatFeatureFlags::SAMPLED_IMAGE),
+            sampled_linear_filter: features.contains(FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR),
+            storage: features.contains(FormatFeatureFlags::STORAGE_IMAGE),
+            color_attachment: features.contains(FormatFeatureFlags::COLOR_ATTACHMENT),
+            color_attachment_blend: features.contains(FormatFeatureFlags::COLOR_ATTACHMENT_BLEND),
+            depth_stencil_attachment: features.contains(FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT),
+            blit_src: features.contains(FormatFeatureFlags::BLIT_SRC),
+            blit_dst: features.contains(FormatFeatureFlags::BLIT_DST),
+            transfer_src: features.contains(FormatFeatureFlags::TRANSFER_SRC),
+            transfer_dst: features.contains(FormatFeatureFlags::TRANSFER_DST),
+            vertex_buffer: properties.buffer_features.contains(FormatFeatureFlags::VERTEX_BUFFER),
+        };
+    }
+
+    /// Whether every flag in `usage` is supported
+    pub fn supports_usage(&self, usage: ImageUsage) -> bool {
+        return self.unsupported_usage(usage).is_none();
+    }
+
+    // First usage flag of `usage` the format can't be used for
+    pub(crate) fn unsupported_usage(&self, usage: ImageUsage) -> Option<&'static str> {
+        let checks = [
+            (ImageUsage::SAMPLED, self.sampled, "SAMPLED"),
+            (ImageUsage::STORAGE, self.storage, "STORAGE"),
+            (ImageUsage::COLOR_ATTACHMENT, self.color_attachment, "COLOR_ATTACHMENT"),
+            (ImageUsage::DEPTH_STENCIL_ATTACHMENT, self.depth_stencil_attachment, "DEPTH_STENCIL_ATTACHMENT"),
+            (ImageUsage::TRANSFER_SRC, self.transfer_src, "TRANSFER_SRC"),
+            (ImageUsage::TRANSFER_DST, self.transfer_dst, "TRANSFER_DST"),
+        ];
+
+        return checks.iter().find(|(flag, supported, _)| usage.flags.contains(flag.flags) && !supported).map(|(_, _, name)| *name);
+    }
+}
+
+/// Device limits relevant for staging copies
+#[derive(Clone, Copy, Debug)]
+pub struct CopyAlignments {
+    /// Granularity of flushes and invalidations of non coherent memory
+    pub non_coherent_atom_size: u64,
+    pub optimal_buffer_copy_offset_alignment: u64,
+    pub optimal_buffer_copy_row_pitch_alignment: u64,
+}
+
+impl CopyAlignments {
+    fn lcm(a: u64, b: u64) -> u64 {
+        let (mut x, mut y) = (a.max(1), b.max(1));
+        while y != 0 {
+            (x, y) = (y, x % y);
+        }
+        return a.max(1) / x * b.max(1);
+    }
+
+    fn align_up(value: u64, alignment: u64) -> u64 {
+        return value.div_ceil(alignment) * alignment;
+    }
+
+    /// Aligns a buffer offset for a buffer <-> image copy.
+    /// The result is a multiple of both the optimal copy alignment and the texel block size of the image format
+    pub fn align_copy_offset(&self, offset: u64, texel_block_size: u64) -> u64 {
+        return Self::align_up(offset, Self::lcm(self.optimal_buffer_copy_offset_alignment, texel_block_size));
+    }
+
+    /// Aligns the row pitch of image data placed in a staging buffer, both in bytes.
+    /// `BufferImageCopy::buffer_row_length` is in texels, use `align_row_length` for it
+    pub fn align_row_pitch(&self, row_pitch: u64, texel_block_size: u64) -> u64 {
+        return Self::align_up(row_pitch, Self::lcm(self.optimal_buffer_copy_row_pitch_alignment, texel_block_size));
+    }
+
+    /// Same as `align_row_pitch` for a row of `width` texels, returns the aligned row length in texels
+    /// for `BufferImageCopy::buffer_row_length`. Only for uncompressed formats
+    pub fn align_row_length(&self, width: u32, texel_block_size: u64) -> u32 {
+        let row_pitch = self.align_row_pitch(width as u64 * texel_block_size, texel_block_size);
+
+        return (row_pitch / texel_block_size.max(1)) as u32;
+    }
+
+    /// Aligns a size or offset to `non_coherent_atom_size` for flushing mapped memory
+    pub fn align_to_atom(&self, value: u64) -> u64 {
+        return Self::align_up(value, self.non_coherent_atom_size.max(1));
+    }
+}
+
+/// Allocation statistics of one memory heap, or of all heaps combined
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapStats {
+    /// Number of device memory blocks allocated from the driver
+    pub block_count: u32,
+    pub allocation_count: u32,
+    /// Bytes allocated from the driver, including unused space in blocks
+    pub allocated_bytes: u64,
+    /// Bytes actually occupied by resources
+    pub used_bytes: u64,
+}
+
+impl HeapStats {
+    pub(crate) fn from_vma(stats: &vk_mem::ffi::VmaStatistics) -> HeapStats {
+        return HeapStats {
+            block_count: stats.blockCount,
+            allocation_count: stats.allocationCount,
+            allocated_bytes: stats.blockBytes,
+            used_bytes: stats.allocationBytes,
+        };
+    }
+}
+
+/// Memory usage of everything allocated through the device
+#[derive(Clone, Debug)]
+pub struct MemoryStats {
+    /// Indexed by memory heap
+    pub heaps: Vec<HeapStats>,
+    pub total: HeapStats,
+}
+
+/// Usage and budget of one memory heap
+#[derive(Clone, Copy, Debug)]
+pub struct HeapBudget {
+    pub device_local: bool,
+    /// Size of the heap in bytes
+    pub size: u64,
+    /// Statistics of the allocations made by this device
+    pub stats: HeapStats,
+    /// Bytes used by the whole process, estimated by VMA if the driver can't tell
+    pub usage: u64,
+    /// Bytes the process can use before allocations are likely to fail or be slow
+    pub budget: u64,
+}
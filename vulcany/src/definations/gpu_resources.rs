@@ -1,3 +1,4 @@
+use crate::{AccelerationStructureID, AccessType, BufferID, IndexType};
 use ash::vk;
 use std::ops::BitOr;
 
@@ -34,6 +35,24 @@ impl MemoryType {
     }
 }
 
+/// How the host accesses a host visible buffer, picks the memory type VMA allocates from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostAccess {
+    /// Written front to back with memcpy, may land in write combined memory that is slow to read
+    SequentialWrite,
+    /// Read or written in any order, use for readback
+    Random,
+}
+
+impl HostAccess {
+    pub(crate) const fn to_vk_flag(&self) -> vk_mem::AllocationCreateFlags {
+        match self {
+            Self::SequentialWrite => vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+            Self::Random => vk_mem::AllocationCreateFlags::HOST_ACCESS_RANDOM,
+        }
+    }
+}
+
 /// A wrapper struct for Vulkan's buffer usage flags (`vk::BufferUsageFlags`).
 ///
 /// Can be combined using Bitwise Or (|)
@@ -78,12 +97,42 @@ impl BufferUsage {
         flags: vk::BufferUsageFlags::TRANSFER_DST,
     };
 
+    /// Specifies that the buffer holds **vertices, indices, transforms or instances** read by acceleration structure builds
+    pub const ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY: Self = Self {
+        flags: vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
+    };
+
+    /// Specifies that the buffer is used as **backing storage** for an acceleration structure
+    pub const ACCELERATION_STRUCTURE_STORAGE: Self = Self {
+        flags: vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR,
+    };
+
+    /// Specifies that the buffer holds a **shader binding table** for ray tracing
+    pub const SHADER_BINDING_TABLE: Self = Self {
+        flags: vk::BufferUsageFlags::SHADER_BINDING_TABLE_KHR,
+    };
+
     // --- Implementation Methods ---
 
     /// Converts the custom usage struct into the raw Vulkan buffer usage flags.
     pub(crate) fn to_vk_flag(&self) -> vk::BufferUsageFlags {
         self.flags
     }
+
+    // How a buffer with this usage is first read after an upload, for the acquire barrier
+    pub(crate) fn read_access(&self) -> AccessType {
+        if self.flags.contains(vk::BufferUsageFlags::VERTEX_BUFFER) {
+            return AccessType::VertexRead;
+        } else if self.flags.contains(vk::BufferUsageFlags::INDEX_BUFFER) {
+            return AccessType::IndexRead;
+        } else if self.flags.contains(vk::BufferUsageFlags::UNIFORM_BUFFER) {
+            return AccessType::UniformRead;
+        } else if self.flags.contains(vk::BufferUsageFlags::INDIRECT_BUFFER) {
+            return AccessType::Indirect;
+        }
+
+        return AccessType::ShaderRead;
+    }
 }
 
 impl BitOr for BufferUsage {
@@ -107,12 +156,19 @@ impl BitOr<&BufferUsage> for BufferUsage {
     }
 }
 
-/// Buffer descriptions, create mapped works only for perfer host memory type
+/// Buffer descriptions, create mapped works only for perfer host memory type.
+/// Prefer host buffers without create mapped can be mapped on demand with `Device::map_buffer`
 pub struct BufferDescription {
     pub usage: BufferUsage,
     pub size: vk::DeviceSize,
     pub memory_type: MemoryType,
     pub create_mapped: bool,
+    /// Only used for mapped or prefer host buffers
+    pub host_access: HostAccess,
+    /// Gives the buffer its own memory block, useful for very large buffers
+    pub dedicated: bool,
+    /// Minimum alignment of the buffer's memory, on top of what the driver requires
+    pub min_alignment: Option<u64>,
 }
 
 impl Default for BufferDescription {
@@ -122,6 +178,9 @@ impl Default for BufferDescription {
             size: 10,
             memory_type: MemoryType::Auto,
             create_mapped: false,
+            host_access: HostAccess::SequentialWrite,
+            dedicated: false,
+            min_alignment: None,
         };
     }
 }
@@ -144,34 +203,69 @@ impl ImageType {
     }
 }
 
-#[derive(Clone)]
-pub enum ImageUsage {
-    TransferSrc,
-    TransferDst,
-    Sampled,
-    Storage,
-    ColorAttachment,
-    DepthStencilAttachment,
+/// A wrapper struct for Vulkan's image usage flags (`vk::ImageUsageFlags`).
+///
+/// Can be combined using Bitwise Or (|)
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct ImageUsage {
+    pub(crate) flags: vk::ImageUsageFlags,
 }
 
 impl ImageUsage {
+    /// Specifies that the image can be used as the **source** in a transfer operation
+    pub const TRANSFER_SRC: Self = Self {
+        flags: vk::ImageUsageFlags::TRANSFER_SRC,
+    };
+
+    /// Specifies that the image can be used as the **destination** in a transfer operation
+    pub const TRANSFER_DST: Self = Self {
+        flags: vk::ImageUsageFlags::TRANSFER_DST,
+    };
+
+    /// Specifies that the image can be **sampled** in shaders
+    pub const SAMPLED: Self = Self { flags: vk::ImageUsageFlags::SAMPLED };
+
+    /// Specifies that the image is used as a **storage image** in shaders
+    pub const STORAGE: Self = Self { flags: vk::ImageUsageFlags::STORAGE };
+
+    /// Specifies that the image can be rendered to as a **color attachment**
+    pub const COLOR_ATTACHMENT: Self = Self {
+        flags: vk::ImageUsageFlags::COLOR_ATTACHMENT,
+    };
+
+    /// Specifies that the image can be rendered to as a **depth/stencil attachment**
+    pub const DEPTH_STENCIL_ATTACHMENT: Self = Self {
+        flags: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+    };
+
+    // --- Implementation Methods ---
+
+    /// Converts the custom usage struct into the raw Vulkan image usage flags.
     pub(crate) const fn to_vk_flag(&self) -> vk::ImageUsageFlags {
-        return match self {
-            Self::TransferSrc => vk::ImageUsageFlags::TRANSFER_SRC,
-            Self::TransferDst => vk::ImageUsageFlags::TRANSFER_DST,
-            Self::Sampled => vk::ImageUsageFlags::SAMPLED,
-            Self::Storage => vk::ImageUsageFlags::STORAGE,
-            Self::ColorAttachment => vk::ImageUsageFlags::COLOR_ATTACHMENT,
-            Self::DepthStencilAttachment => vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
-        };
+        self.flags
     }
 }
-#[derive(Clone, Copy)]
+
+impl BitOr for ImageUsage {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self::Output {
+        Self { flags: self.flags | other.flags }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Format {
     // --- Unsigned Normalized (UNORM) Formats - Standard Color & Textures ---
     Rgba8Unorm,
     Bgra8Unorm,
     Rgb565Unorm,
+    R8Unorm,
+    Rg8Unorm,
+    A2Bgr10Unorm,
+
+    // --- sRGB Formats - Gamma encoded color, linear when sampled or written ---
+    Rgba8Srgb,
+    Bgra8Srgb,
 
     // --- Signed/Unsigned Integers (SINT/UINT) ---
     Rgba8Uint,
@@ -183,6 +277,7 @@ pub enum Format {
     Rgb32Float,
     Rgba32Float,
     R32Float,
+    R11G11B10Float,
 
     // --- Depth and Stencil Formats ---
     D32Float,
@@ -201,6 +296,13 @@ impl Format {
             Self::Rgba8Unorm => vk::Format::R8G8B8A8_UNORM,
             Self::Bgra8Unorm => vk::Format::B8G8R8A8_UNORM,
             Self::Rgb565Unorm => vk::Format::R5G6B5_UNORM_PACK16,
+            Self::R8Unorm => vk::Format::R8_UNORM,
+            Self::Rg8Unorm => vk::Format::R8G8_UNORM,
+            Self::A2Bgr10Unorm => vk::Format::A2B10G10R10_UNORM_PACK32,
+
+            // sRGB
+            Self::Rgba8Srgb => vk::Format::R8G8B8A8_SRGB,
+            Self::Bgra8Srgb => vk::Format::B8G8R8A8_SRGB,
 
             // Signed/Unsigned Integers (SINT/UINT)
             Self::Rgba8Uint => vk::Format::R8G8B8A8_UINT,
@@ -212,6 +314,7 @@ impl Format {
             Self::Rgb32Float => vk::Format::R32G32B32_SFLOAT,
             Self::Rgba32Float => vk::Format::R32G32B32A32_SFLOAT,
             Self::R32Float => vk::Format::R32_SFLOAT,
+            Self::R11G11B10Float => vk::Format::B10G11R11_UFLOAT_PACK32,
 
             // Depth and Stencil
             Self::D32Float => vk::Format::D32_SFLOAT,
@@ -223,6 +326,62 @@ impl Format {
             Self::BC7Unorm => vk::Format::BC7_UNORM_BLOCK,
         };
     }
+
+    /// Whether the format has a depth or stencil component
+    pub const fn is_depth_stencil(&self) -> bool {
+        return matches!(self, Self::D32Float | Self::D24UnormS8Uint | Self::D16Unorm);
+    }
+
+    /// Aspect covering every component of the format, eg. for barriers and views of the whole image
+    pub const fn aspect(&self) -> ImageAspect {
+        return match self {
+            Self::D32Float | Self::D16Unorm => ImageAspect::Depth,
+            Self::D24UnormS8Uint => ImageAspect::DepthStencil,
+            _ => ImageAspect::Color,
+        };
+    }
+
+    /// Size of one texel in bytes, for block compressed formats the size of one 4x4 block
+    pub const fn bytes_per_texel(&self) -> u32 {
+        return match self {
+            Self::R8Unorm => 1,
+            Self::Rg8Unorm | Self::Rgb565Unorm | Self::D16Unorm => 2,
+            Self::Rgba8Unorm | Self::Bgra8Unorm | Self::A2Bgr10Unorm | Self::Rgba8Srgb | Self::Bgra8Srgb | Self::Rgba8Uint => 4,
+            Self::R32Float | Self::R11G11B10Float | Self::D32Float | Self::D24UnormS8Uint => 4,
+            Self::Rgba16Float | Self::Rg32Float => 8,
+            Self::Rgb32Float => 12,
+            Self::Rgba32Float | Self::Rgba32Sint => 16,
+            Self::BC1RgbaUnorm => 8,
+            Self::BC7Unorm => 16,
+        };
+    }
+
+    pub(crate) const fn from_vk_format(format: vk::Format) -> Option<Format> {
+        return match format {
+            vk::Format::R8G8B8A8_UNORM => Some(Self::Rgba8Unorm),
+            vk::Format::B8G8R8A8_UNORM => Some(Self::Bgra8Unorm),
+            vk::Format::R5G6B5_UNORM_PACK16 => Some(Self::Rgb565Unorm),
+            vk::Format::R8_UNORM => Some(Self::R8Unorm),
+            vk::Format::R8G8_UNORM => Some(Self::Rg8Unorm),
+            vk::Format::A2B10G10R10_UNORM_PACK32 => Some(Self::A2Bgr10Unorm),
+            vk::Format::R8G8B8A8_SRGB => Some(Self::Rgba8Srgb),
+            vk::Format::B8G8R8A8_SRGB => Some(Self::Bgra8Srgb),
+            vk::Format::R8G8B8A8_UINT => Some(Self::Rgba8Uint),
+            vk::Format::R32G32B32A32_SINT => Some(Self::Rgba32Sint),
+            vk::Format::R16G16B16A16_SFLOAT => Some(Self::Rgba16Float),
+            vk::Format::R32G32_SFLOAT => Some(Self::Rg32Float),
+            vk::Format::R32G32B32_SFLOAT => Some(Self::Rgb32Float),
+            vk::Format::R32G32B32A32_SFLOAT => Some(Self::Rgba32Float),
+            vk::Format::R32_SFLOAT => Some(Self::R32Float),
+            vk::Format::B10G11R11_UFLOAT_PACK32 => Some(Self::R11G11B10Float),
+            vk::Format::D32_SFLOAT => Some(Self::D32Float),
+            vk::Format::D24_UNORM_S8_UINT => Some(Self::D24UnormS8Uint),
+            vk::Format::D16_UNORM => Some(Self::D16Unorm),
+            vk::Format::BC1_RGBA_UNORM_BLOCK => Some(Self::BC1RgbaUnorm),
+            vk::Format::BC7_UNORM_BLOCK => Some(Self::BC7Unorm),
+            _ => None,
+        };
+    }
 }
 
 #[repr(u32)]
@@ -277,6 +436,20 @@ impl ImageLayout {
             ImageLayout::PresentSrc => vk::ImageLayout::PRESENT_SRC_KHR,
         }
     }
+
+    // Every access an image in this layout is typically used with, for barriers derived from layouts
+    pub(crate) const fn to_vk_access(self) -> vk::AccessFlags2 {
+        match self {
+            ImageLayout::Undefined | ImageLayout::PresentSrc => vk::AccessFlags2::NONE,
+            ImageLayout::General => vk::AccessFlags2::from_raw(vk::AccessFlags2::SHADER_READ.as_raw() | vk::AccessFlags2::SHADER_WRITE.as_raw()),
+            ImageLayout::ColorAttachment => vk::AccessFlags2::from_raw(vk::AccessFlags2::COLOR_ATTACHMENT_READ.as_raw() | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE.as_raw()),
+            ImageLayout::DepthStencilAttachment => vk::AccessFlags2::from_raw(vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ.as_raw() | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()),
+            ImageLayout::DepthStencilReadOnly => vk::AccessFlags2::from_raw(vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ.as_raw() | vk::AccessFlags2::SHADER_READ.as_raw()),
+            ImageLayout::ShaderReadOnly => vk::AccessFlags2::SHADER_READ,
+            ImageLayout::TransferSrc => vk::AccessFlags2::TRANSFER_READ,
+            ImageLayout::TransferDst => vk::AccessFlags2::TRANSFER_WRITE,
+        }
+    }
 }
 
 pub struct ImageDescription {
@@ -290,12 +463,16 @@ pub struct ImageDescription {
     pub mip_levels: u32,
     pub array_layers: u32,
     pub samples: SampleCount,
+    /// Gives the image its own memory block, recommended for large render targets
+    pub dedicated: bool,
+    /// Allows `Cube` and `CubeArray` views, needs square faces and a multiple of 6 array layers
+    pub cube_compatible: bool,
 }
 
 impl Default for ImageDescription {
     fn default() -> Self {
         return Self {
-            usage: ImageUsage::Sampled,
+            usage: ImageUsage::SAMPLED,
             format: Format::Rgba16Float,
             image_type: ImageType::Type2D,
             height: 1,
@@ -305,6 +482,8 @@ impl Default for ImageDescription {
             mip_levels: 1,
             array_layers: 1,
             samples: SampleCount::Type1,
+            dedicated: false,
+            cube_compatible: false,
         };
     }
 }
@@ -417,6 +596,26 @@ impl SamplerMipmapMode {
     }
 }
 
+/// How the texels covered by a filtered lookup are combined
+#[derive(Clone, Copy, Debug)]
+pub enum ReductionMode {
+    WeightedAverage,
+    /// Smallest texel value, eg. for min depth pyramids
+    Min,
+    /// Largest texel value, eg. for Hi-Z occlusion culling
+    Max,
+}
+
+impl ReductionMode {
+    pub(crate) fn to_vk(self) -> vk::SamplerReductionMode {
+        match self {
+            ReductionMode::WeightedAverage => vk::SamplerReductionMode::WEIGHTED_AVERAGE,
+            ReductionMode::Min => vk::SamplerReductionMode::MIN,
+            ReductionMode::Max => vk::SamplerReductionMode::MAX,
+        }
+    }
+}
+
 /// Addressing (wrap/clamp modes)
 #[derive(Clone, Copy, Debug)]
 pub enum SamplerAddressMode {
@@ -445,6 +644,8 @@ pub enum BorderColor {
     IntOpaqueBlack,
     FloatOpaqueWhite,
     IntOpaqueWhite,
+    /// Float RGBA border, needs `Device::supports_custom_border_color`
+    Custom([f32; 4]),
 }
 impl BorderColor {
     pub(crate) fn to_vk(self) -> vk::BorderColor {
@@ -455,6 +656,7 @@ impl BorderColor {
             BorderColor::IntOpaqueBlack => vk::BorderColor::INT_OPAQUE_BLACK,
             BorderColor::FloatOpaqueWhite => vk::BorderColor::FLOAT_OPAQUE_WHITE,
             BorderColor::IntOpaqueWhite => vk::BorderColor::INT_OPAQUE_WHITE,
+            BorderColor::Custom(_) => vk::BorderColor::FLOAT_CUSTOM_EXT,
         }
     }
 }
@@ -494,12 +696,16 @@ pub struct SamplerDescription {
     pub address_mode_v: SamplerAddressMode,
     pub address_mode_w: SamplerAddressMode,
     pub mip_lod_bias: f32,
+    /// Clamped to `DeviceLimits::max_sampler_anisotropy`, ignored when `Device::supports_sampler_anisotropy` is false
     pub max_anisotropy: Option<f32>,
     pub compare_op: Option<CompareOp>,
     pub min_lod: f32,
+    /// The default leaves the mip range unclamped, see `SamplerDescription::for_texture`
     pub max_lod: f32,
     pub border_color: BorderColor,
     pub unnormalized_coordinates: bool,
+    /// Min and max need VK_EXT_sampler_filter_minmax, check `Device::supports_sampler_reduction`
+    pub reduction_mode: Option<ReductionMode>,
 }
 
 impl Default for SamplerDescription {
@@ -518,6 +724,149 @@ impl Default for SamplerDescription {
             max_lod: 1000.0,
             border_color: BorderColor::IntOpaqueBlack,
             unnormalized_coordinates: false,
+            reduction_mode: None,
         }
     }
 }
+
+impl SamplerDescription {
+    /// Comparison sampler for hardware PCF on a depth image, sample it with `SampleCmp` in slang.
+    /// Lookups outside the shadow map count as lit
+    pub fn shadow_pcf() -> SamplerDescription {
+        return SamplerDescription {
+            mipmap_mode: SamplerMipmapMode::Nearest,
+            address_mode_u: SamplerAddressMode::ClampToBorder,
+            address_mode_v: SamplerAddressMode::ClampToBorder,
+            address_mode_w: SamplerAddressMode::ClampToBorder,
+            compare_op: Some(CompareOp::LessOrEqual),
+            max_lod: 0.0,
+            border_color: BorderColor::FloatOpaqueWhite,
+            ..Default::default()
+        };
+    }
+
+    /// Default sampler with `max_lod` clamped to the last mip of a texture with `mip_levels` mips.
+    /// With linear mipmapping an unclamped `max_lod` lets large lod values blend towards levels the
+    /// texture might not have, which shows up as over blurring
+    pub fn for_texture(mip_levels: u32) -> SamplerDescription {
+        return SamplerDescription {
+            max_lod: mip_levels.max(1) as f32 - 1.0,
+            ..Default::default()
+        };
+    }
+}
+
+//// ACCELERATION STRUCTURES ////
+
+/// Whether the driver should optimize an acceleration structure for trace performance or build speed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccelerationStructureBuildPreference {
+    FastTrace,
+    FastBuild,
+}
+
+impl AccelerationStructureBuildPreference {
+    pub(crate) const fn to_vk(&self) -> vk::BuildAccelerationStructureFlagsKHR {
+        match self {
+            Self::FastTrace => vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE,
+            Self::FastBuild => vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_BUILD,
+        }
+    }
+}
+
+/// Row major 3x4 transform, identical in layout to `VkTransformMatrixKHR`
+pub type Transform = [[f32; 4]; 3];
+
+pub const IDENTITY_TRANSFORM: Transform = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]];
+
+/// Triangle geometry for a bottom level acceleration structure.
+/// The vertex and index buffers must be usable as acceleration structure build inputs
+pub struct BlasGeometry {
+    pub vertex_buffer: BufferID,
+    pub vertex_offset: u64,
+    pub vertex_stride: u64,
+    pub vertex_format: Format,
+    /// Highest vertex index referenced by this geometry
+    pub max_vertex: u32,
+    /// Leave as `None` for non indexed geometry
+    pub index_buffer: Option<BufferID>,
+    pub index_offset: u64,
+    pub index_type: IndexType,
+    pub triangle_count: u32,
+    pub transform: Transform,
+    pub opaque: bool,
+}
+
+impl Default for BlasGeometry {
+    fn default() -> Self {
+        return BlasGeometry {
+            vertex_buffer: BufferID::null(),
+            vertex_offset: 0,
+            vertex_stride: 12,
+            vertex_format: Format::Rgb32Float,
+            max_vertex: 0,
+            index_buffer: None,
+            index_offset: 0,
+            index_type: IndexType::Uint32,
+            triangle_count: 0,
+            transform: IDENTITY_TRANSFORM,
+            opaque: true,
+        };
+    }
+}
+
+pub struct BlasDescription {
+    pub geometries: Vec<BlasGeometry>,
+    pub build_preference: AccelerationStructureBuildPreference,
+}
+
+impl Default for BlasDescription {
+    fn default() -> Self {
+        return BlasDescription {
+            geometries: vec![],
+            build_preference: AccelerationStructureBuildPreference::FastTrace,
+        };
+    }
+}
+
+/// A single instance of a BLAS placed in a top level acceleration structure
+#[derive(Clone, Copy)]
+pub struct TlasInstance {
+    pub blas: AccelerationStructureID,
+    pub transform: Transform,
+    /// Only the low 24 bits are used, exposed in shaders as `InstanceID()`
+    pub custom_index: u32,
+    pub mask: u8,
+    /// Only the low 24 bits are used
+    pub sbt_record_offset: u32,
+    pub disable_culling: bool,
+    pub force_opaque: bool,
+}
+
+impl Default for TlasInstance {
+    fn default() -> Self {
+        return TlasInstance {
+            blas: AccelerationStructureID::null(),
+            transform: IDENTITY_TRANSFORM,
+            custom_index: 0,
+            mask: 0xFF,
+            sbt_record_offset: 0,
+            disable_culling: false,
+            force_opaque: false,
+        };
+    }
+}
+
+pub struct TlasDescription {
+    pub instances: Vec<TlasInstance>,
+    pub build_preference: AccelerationStructureBuildPreference,
+}
+
+impl Default for TlasDescription {
+    fn default() -> Self {
+        return TlasDescription {
+            instances: vec![],
+            build_preference: AccelerationStructureBuildPreference::FastTrace,
+        };
+    }
+}
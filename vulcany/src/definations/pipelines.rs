@@ -5,9 +5,11 @@ use std::{ops::BitOr, u64};
 
 ////Descriptors////
 
+/// Writes to a caller chosen index, `Device::register_buffer` picks a free index instead
 pub struct BufferWriteInfo {
     pub buffer: BufferID,
     pub offset: u64,
+    /// `u64::MAX` covers the rest of the buffer
     pub range: u64,
     pub index: u32,
 }
@@ -17,21 +19,28 @@ impl Default for BufferWriteInfo {
         return BufferWriteInfo {
             buffer: BufferID::null(),
             offset: 0,
-            range: 0,
+            range: u64::MAX,
             index: 0,
         };
     }
 }
 
+/// The descriptor type also fixes the layout the image has to be in when a shader accesses it
 pub enum ImageDescriptorType {
+    /// Must be transitioned to `ImageLayout::ShaderReadOnly` before sampling
     SampledImage,
+    /// Must be transitioned to `ImageLayout::General`
     StorageImage,
 }
 
+/// Writes to a caller chosen index, `Device::register_image` picks a free index instead
 pub struct ImageWriteInfo {
     pub view: ImageViewID,
     pub image_descriptor_type: ImageDescriptorType,
     pub index: u32,
+    /// Layout the image is in when the shader accesses it.
+    /// None uses `ShaderReadOnly` for sampled images and `General` for storage images
+    pub layout: Option<ImageLayout>,
 }
 
 impl Default for ImageWriteInfo {
@@ -40,10 +49,17 @@ impl Default for ImageWriteInfo {
             view: ImageViewID::null(),
             image_descriptor_type: ImageDescriptorType::SampledImage,
             index: 0,
+            layout: None,
         };
     }
 }
 
+/// Bindless sampler index of `Device::default_sampler`, reserved on every device
+pub const DEFAULT_SAMPLER_INDEX: u32 = 0;
+/// Bindless sampler index of `Device::default_nearest_sampler`, reserved on every device
+pub const DEFAULT_NEAREST_SAMPLER_INDEX: u32 = 1;
+
+/// Writes to a caller chosen index, `Device::register_sampler` picks a free index instead
 pub struct SamplerWriteInfo {
     pub sampler: SamplerID,
     pub index: u32,
@@ -59,6 +75,9 @@ impl Default for SamplerWriteInfo {
 
 pub trait VertexFormat {
     const FORMAT: Format;
+    /// Consecutive locations taken by the type, each one holds `FORMAT`.
+    /// Matrices take one location per column
+    const LOCATIONS: u32 = 1;
 }
 
 impl VertexFormat for f32 {
@@ -73,9 +92,23 @@ impl VertexFormat for [f32; 3] {
 impl VertexFormat for [f32; 4] {
     const FORMAT: Format = Format::Rgba32Float;
 }
+/// Normalized to 0..1, meant for compact colors
 impl VertexFormat for [u8; 4] {
     const FORMAT: Format = Format::Rgba8Unorm;
 }
+impl VertexFormat for [u8; 2] {
+    const FORMAT: Format = Format::Rg8Unorm;
+}
+/// Column major mat3
+impl VertexFormat for [[f32; 3]; 3] {
+    const FORMAT: Format = Format::Rgb32Float;
+    const LOCATIONS: u32 = 3;
+}
+/// Column major mat4, like an instance transform
+impl VertexFormat for [[f32; 4]; 4] {
+    const FORMAT: Format = Format::Rgba32Float;
+    const LOCATIONS: u32 = 4;
+}
 
 #[derive(Clone, Copy, Debug)]
 pub enum VertexInputRate {
@@ -201,12 +234,94 @@ impl PolygonMode {
     }
 }
 
+#[derive(Clone, Copy)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    IncrementAndClamp,
+    DecrementAndClamp,
+    Invert,
+    IncrementAndWrap,
+    DecrementAndWrap,
+}
+
+impl StencilOp {
+    pub(crate) const fn to_vk(&self) -> vk::StencilOp {
+        match self {
+            Self::Keep => vk::StencilOp::KEEP,
+            Self::Zero => vk::StencilOp::ZERO,
+            Self::Replace => vk::StencilOp::REPLACE,
+            Self::IncrementAndClamp => vk::StencilOp::INCREMENT_AND_CLAMP,
+            Self::DecrementAndClamp => vk::StencilOp::DECREMENT_AND_CLAMP,
+            Self::Invert => vk::StencilOp::INVERT,
+            Self::IncrementAndWrap => vk::StencilOp::INCREMENT_AND_WRAP,
+            Self::DecrementAndWrap => vk::StencilOp::DECREMENT_AND_WRAP,
+        }
+    }
+}
+
+/// Stencil behaviour for one face
+#[derive(Clone, Copy)]
+pub struct StencilOpState {
+    pub fail_op: StencilOp,
+    pub pass_op: StencilOp,
+    pub depth_fail_op: StencilOp,
+    pub compare_op: CompareOp,
+    pub compare_mask: u32,
+    pub write_mask: u32,
+    /// Ignored when the pipeline uses `dynamic_stencil_reference`
+    pub reference: u32,
+}
+
+impl StencilOpState {
+    pub(crate) fn to_vk(&self) -> vk::StencilOpState {
+        return vk::StencilOpState {
+            fail_op: self.fail_op.to_vk(),
+            pass_op: self.pass_op.to_vk(),
+            depth_fail_op: self.depth_fail_op.to_vk(),
+            compare_op: self.compare_op.to_vk(),
+            compare_mask: self.compare_mask,
+            write_mask: self.write_mask,
+            reference: self.reference,
+        };
+    }
+}
+
+impl Default for StencilOpState {
+    fn default() -> Self {
+        Self {
+            fail_op: StencilOp::Keep,
+            pass_op: StencilOp::Keep,
+            depth_fail_op: StencilOp::Keep,
+            compare_op: CompareOp::Always,
+            compare_mask: 0xff,
+            write_mask: 0xff,
+            reference: 0,
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct DepthStencilOptions {
     pub depth_test_enable: bool,
     pub depth_write_enable: bool,
+    /// `Less` expects a `ClearValue::depth_one` clear, reverse-Z uses `Greater` with `ClearValue::depth_zero`
     pub depth_compare_op: CompareOp,
     pub stencil_test_enable: bool,
+    pub stencil_front: StencilOpState,
+    pub stencil_back: StencilOpState,
+}
+
+impl DepthStencilOptions {
+    /// Depth test and write with `CompareOp::Greater`, for a projection that maps the near plane to 1 and far to 0.
+    /// Clear the depth attachment with `ClearValue::depth_zero`
+    pub fn reverse_z() -> DepthStencilOptions {
+        return DepthStencilOptions {
+            depth_compare_op: CompareOp::Greater,
+            ..Default::default()
+        };
+    }
 }
 
 impl Default for DepthStencilOptions {
@@ -216,6 +331,8 @@ impl Default for DepthStencilOptions {
             depth_write_enable: true,
             depth_compare_op: CompareOp::Less,
             stencil_test_enable: false,
+            stencil_front: StencilOpState::default(),
+            stencil_back: StencilOpState::default(),
         }
     }
 }
@@ -226,6 +343,8 @@ pub struct PipelineOutputs {
     pub color: Vec<Format>,
     pub depth: Option<Format>,
     pub stencil: Option<Format>,
+    /// Views rendered by multiview, one bit per view. Must match `RenderingBeginInfo::view_mask`, 0 disables multiview
+    pub view_mask: u32,
 }
 
 impl Default for PipelineOutputs {
@@ -234,6 +353,7 @@ impl Default for PipelineOutputs {
             color: vec![Format::Rgba16Float],
             depth: None,
             stencil: None,
+            view_mask: 0,
         };
     }
 }
@@ -281,18 +401,87 @@ impl Default for PushConstantsDescription {
     }
 }
 
+/// Value of a specialization constant, matched to the shader constant by its id
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpecConstantValue {
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    Bool(bool),
+}
+
+impl SpecConstantValue {
+    // Every supported type is 4 bytes, bools are VkBool32
+    fn to_bytes(&self) -> [u8; 4] {
+        match self {
+            SpecConstantValue::U32(v) => v.to_ne_bytes(),
+            SpecConstantValue::I32(v) => v.to_ne_bytes(),
+            SpecConstantValue::F32(v) => v.to_ne_bytes(),
+            SpecConstantValue::Bool(v) => (*v as vk::Bool32).to_ne_bytes(),
+        }
+    }
+
+    /// Packs the constants into one blob along with the map entries pointing into it
+    pub(crate) fn pack(constants: &[(u32, SpecConstantValue)]) -> (Vec<vk::SpecializationMapEntry>, Vec<u8>) {
+        let mut entries = Vec::with_capacity(constants.len());
+        let mut data = Vec::with_capacity(constants.len() * 4);
+
+        for (id, value) in constants {
+            entries.push(vk::SpecializationMapEntry::default().constant_id(*id).offset(data.len() as u32).size(4));
+            data.extend_from_slice(&value.to_bytes());
+        }
+
+        return (entries, data);
+    }
+}
+
+/// Tessellation stages of a rasterization pipeline, the vertex shader then outputs patch control points
+#[derive(Clone)]
+pub struct TessellationDescription {
+    pub control_shader_path: &'static str,
+    pub evaluation_shader_path: &'static str,
+    /// Number of vertices per patch, vertices are assembled as a patch list
+    pub patch_control_points: u32,
+}
+
 #[derive(Clone)]
 pub struct RasterizationPipelineDescription {
     pub vertex_input: VertexInputDescription,
     pub push_constants: PushConstantsDescription,
     pub vertex_shader_path: &'static str,
-    pub fragment_shader_path: &'static str,
+    /// None for depth only pipelines, which must not have color outputs
+    pub fragment_shader_path: Option<&'static str>,
+    /// Needs the geometryShader device feature
+    pub geometry_shader_path: Option<&'static str>,
+    /// Needs the tessellationShader device feature
+    pub tessellation: Option<TessellationDescription>,
     pub cull_mode: CullMode,
     pub front_face: FrontFace,
     pub polygon_mode: PolygonMode,
+    /// Clamps depth instead of clipping against the near and far planes, needs the depthClamp device feature
+    pub depth_clamp_enable: bool,
+    /// Primitives are discarded before rasterization, for passes that only run the vertex stages
+    pub rasterizer_discard_enable: bool,
+    /// Polygon mode is set with `CommandRecorder::set_polygon_mode` instead of `polygon_mode`.
+    /// Needs VK_EXT_extended_dynamic_state3, check `Device::supports_dynamic_polygon_mode`
+    pub dynamic_polygon_mode: bool,
+    /// Line width is set with `CommandRecorder::set_line_width` instead of being fixed at 1.0
+    pub dynamic_line_width: bool,
+    /// Enables depth bias, the factors are set with `CommandRecorder::set_depth_bias`
+    pub dynamic_depth_bias: bool,
+    /// Cull mode is set with `CommandRecorder::set_cull_mode`, `cull_mode` is ignored and it must be recorded before every draw
+    pub dynamic_cull_mode: bool,
+    /// Front face is set with `CommandRecorder::set_front_face`, `front_face` is ignored and it must be recorded before every draw
+    pub dynamic_front_face: bool,
+    /// Vertex buffer strides are given at bind time with `CommandRecorder::bind_vertex_buffers_with_stride`
+    pub dynamic_vertex_stride: bool,
+    /// Stencil reference is set with `CommandRecorder::set_stencil_reference` instead of `StencilOpState::reference`
+    pub dynamic_stencil_reference: bool,
     pub depth_stencil: DepthStencilOptions,
     pub alpha_blend_enable: bool,
     pub outputs: PipelineOutputs,
+    /// Applied to every stage, (constant id, value)
+    pub specialization: Vec<(u32, SpecConstantValue)>,
 }
 
 impl Default for RasterizationPipelineDescription {
@@ -301,13 +490,25 @@ impl Default for RasterizationPipelineDescription {
             vertex_input: VertexInputDescription::default(),
             push_constants: PushConstantsDescription::default(),
             vertex_shader_path: " ",
-            fragment_shader_path: " ",
+            fragment_shader_path: None,
+            geometry_shader_path: None,
+            tessellation: None,
             cull_mode: CullMode::None,
             front_face: FrontFace::CounterClockwise,
             polygon_mode: PolygonMode::Fill,
+            depth_clamp_enable: false,
+            rasterizer_discard_enable: false,
+            dynamic_polygon_mode: false,
+            dynamic_line_width: false,
+            dynamic_depth_bias: false,
+            dynamic_cull_mode: false,
+            dynamic_front_face: false,
+            dynamic_vertex_stride: false,
+            dynamic_stencil_reference: false,
             depth_stencil: DepthStencilOptions::default(),
             alpha_blend_enable: false,
             outputs: PipelineOutputs::default(),
+            specialization: Vec::new(),
         }
     }
 }
@@ -317,6 +518,8 @@ impl Default for RasterizationPipelineDescription {
 pub struct ComputePipelineDescription {
     pub shader_path: &'static str,
     pub push_constants: PushConstantsDescription,
+    /// (constant id, value), eg. the workgroup size
+    pub specialization: Vec<(u32, SpecConstantValue)>,
 }
 
 //// Ray tracing pipeline info ////
@@ -340,5 +543,9 @@ pub struct RayTracingPipelineDescription {
     pub raygen: &'static str,
     pub miss: Vec<&'static str>,
     pub hit_grps: Vec<HitGroupDescription>,
+    /// Callable shaders, called from other stages by their index in this list
+    pub callable: Vec<&'static str>,
     pub push_constants: PushConstantsDescription,
+    /// Applied to every stage, (constant id, value)
+    pub specialization: Vec<(u32, SpecConstantValue)>,
 }
@@ -18,12 +18,7 @@ pub use memoffset;
 
 #[macro_export]
 macro_rules! vertex {
-    (
-        $name:ident {
-            input_rate: $rate:ident,
-            $( $field:ident : $ty:ty ),* $(,)?
-        }
-    ) => {
+    (@struct $name:ident, $rate:ident, $( $field:ident : $ty:ty ),*) => {
         #[repr(C)]
         #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
         pub struct $name {
@@ -31,29 +26,80 @@ macro_rules! vertex {
         }
 
         impl $name {
-            pub fn vertex_input_description() -> $crate::VertexInputDescription {
-                use std::mem;
-                let mut location = 0u32;
+            pub fn vertex_binding(binding: u32) -> $crate::VertexBinding {
+                $crate::VertexBinding {
+                    binding,
+                    stride: std::mem::size_of::<Self>() as u32,
+                    input_rate: $crate::VertexInputRate::$rate,
+                }
+            }
+
+            /// Attributes of every field in declaration order, starting at `first_location`.
+            /// Fields taking several locations, like matrices, get one attribute per location
+            pub fn vertex_attributes(binding: u32, first_location: u32) -> Vec<$crate::VertexAttribute> {
+                let mut location = first_location;
 
                 let mut attributes = Vec::new();
                 $(
-                    attributes.push($crate::VertexAttribute {
-                        location,
-                        binding: 0,
-                        format: <$ty as $crate::VertexFormat>::FORMAT,
-                        offset: memoffset::offset_of!($name, $field) as u32,
-                    });
-                    location += 1;
+                    let locations = <$ty as $crate::VertexFormat>::LOCATIONS;
+                    let location_size = std::mem::size_of::<$ty>() as u32 / locations;
+
+                    for i in 0..locations {
+                        attributes.push($crate::VertexAttribute {
+                            location,
+                            binding,
+                            format: <$ty as $crate::VertexFormat>::FORMAT,
+                            offset: memoffset::offset_of!($name, $field) as u32 + i * location_size,
+                        });
+                        location += 1;
+                    }
                 )*
 
+                attributes
+            }
+        }
+    };
+
+    (
+        $name:ident {
+            input_rate: $rate:ident,
+            $( $field:ident : $ty:ty ),* $(,)?
+        }
+    ) => {
+        $crate::vertex!(@struct $name, $rate, $( $field : $ty ),*);
+
+        impl $name {
+            pub fn vertex_input_description() -> $crate::VertexInputDescription {
+                $crate::VertexInputDescription {
+                    bindings: vec![Self::vertex_binding(0)],
+                    attributes: Self::vertex_attributes(0, 0),
+                }
+            }
+        }
+    };
+
+    // Second block goes into binding 1, its locations follow the ones of the first block
+    (
+        $name:ident {
+            input_rate: $rate:ident,
+            $( $field:ident : $ty:ty ),* $(,)?
+        }
+        $instance_name:ident {
+            input_rate: $instance_rate:ident,
+            $( $instance_field:ident : $instance_ty:ty ),* $(,)?
+        }
+    ) => {
+        $crate::vertex!(@struct $name, $rate, $( $field : $ty ),*);
+        $crate::vertex!(@struct $instance_name, $instance_rate, $( $instance_field : $instance_ty ),*);
+
+        impl $name {
+            /// Binding 0 holds this struct, binding 1 the second struct of the macro
+            pub fn vertex_input_description() -> $crate::VertexInputDescription {
+                let mut attributes = Self::vertex_attributes(0, 0);
+                attributes.extend($instance_name::vertex_attributes(1, attributes.len() as u32));
+
                 $crate::VertexInputDescription {
-                    bindings: vec![
-                        $crate::VertexBinding {
-                            binding: 0,
-                            stride: mem::size_of::<Self>() as u32,
-                            input_rate: $crate::VertexInputRate::$rate,
-                        }
-                    ],
+                    bindings: vec![Self::vertex_binding(0), $instance_name::vertex_binding(1)],
                     attributes,
                 }
             }
@@ -0,0 +1,98 @@
+use ash::vk::Handle;
+
+use crate::{BufferID, CommandRecorder, IndexType, Pipeline, RasterizationPipeline};
+
+/// A single indexed draw collected by a `DrawBatch`
+pub struct DrawItem<'a> {
+    pub pipeline: &'a RasterizationPipeline,
+    pub vertex_buffer: BufferID,
+    pub index_buffer: BufferID,
+    pub index_type: IndexType,
+    pub index_count: u32,
+    pub first_index: u32,
+    pub vertex_offset: i32,
+    /// Raw push constant bytes, pushed at offset 0. Leave empty to skip
+    pub push_constants: Vec<u8>,
+}
+
+/// Collects draws and records them sorted by pipeline and buffers,
+/// so binds are only issued when the state actually changes
+pub struct DrawBatch<'a> {
+    items: Vec<DrawItem<'a>>,
+}
+
+impl<'a> Default for DrawBatch<'a> {
+    fn default() -> Self {
+        return DrawBatch::new();
+    }
+}
+
+impl<'a> DrawBatch<'a> {
+    pub fn new() -> DrawBatch<'a> {
+        return DrawBatch { items: Vec::new() };
+    }
+
+    pub fn push(&mut self, item: DrawItem<'a>) {
+        self.items.push(item);
+    }
+
+    /// Convenience for drawing a whole index buffer
+    pub fn push_draw(&mut self, pipeline: &'a RasterizationPipeline, vertex_buffer: BufferID, index_buffer: BufferID, index_count: u32, push_constants: &impl bytemuck::Pod) {
+        self.items.push(DrawItem {
+            pipeline: pipeline,
+            vertex_buffer: vertex_buffer,
+            index_buffer: index_buffer,
+            index_type: IndexType::Uint32,
+            index_count: index_count,
+            first_index: 0,
+            vertex_offset: 0,
+            push_constants: bytemuck::bytes_of(push_constants).to_vec(),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        return self.items.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.items.is_empty();
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Records all draws into the recorder, must be called inside a rendering.
+    /// Viewport and scissor are left to the caller
+    pub fn record(&mut self, recorder: &mut CommandRecorder) {
+        self.items.sort_by_key(|item| (item.pipeline.get_handle().as_raw(), item.vertex_buffer.id, item.index_buffer.id));
+
+        let mut bound_pipeline = None;
+        let mut bound_vertex_buffer = None;
+        let mut bound_index_buffer = None;
+
+        for item in &self.items {
+            let pipeline = item.pipeline.get_handle().as_raw();
+            if bound_pipeline != Some(pipeline) {
+                recorder.bind_pipeline(item.pipeline);
+                bound_pipeline = Some(pipeline);
+            }
+
+            if bound_vertex_buffer != Some(item.vertex_buffer) {
+                recorder.bind_vertex_buffer(item.vertex_buffer, 0);
+                bound_vertex_buffer = Some(item.vertex_buffer);
+            }
+
+            if bound_index_buffer != Some((item.index_buffer, item.index_type)) {
+                recorder.bind_index_buffer(item.index_buffer, 0, item.index_type);
+                bound_index_buffer = Some((item.index_buffer, item.index_type));
+            }
+
+            if !item.push_constants.is_empty() {
+                recorder.set_push_constant_bytes(&item.push_constants, item.pipeline);
+            }
+
+            recorder.draw_indexed(item.index_count, 1, item.first_index, item.vertex_offset, 0);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{CommandBufferUsage, CommandRecorder, Device, Fence, QueueSubmitInfo, QueueType};
+
+/// Owns a command recorder and a fence for each of the `N` frames in flight and rotates through them.
+///
+/// Call `begin_frame`, record into the returned recorder, then hand the rest of the submission to `end_frame`
+pub struct Frames<const N: usize> {
+    device: Device,
+    recorders: [CommandRecorder; N],
+    fences: [Fence; N],
+    current: usize,
+}
+
+impl<const N: usize> Frames<N> {
+    pub fn new(device: &Device, queue_type: QueueType) -> Frames<N> {
+        return Frames {
+            device: device.clone(),
+            recorders: std::array::from_fn(|_| device.create_command_recorder(queue_type)),
+            fences: std::array::from_fn(|_| device.create_fence(true)),
+            current: 0,
+        };
+    }
+
+    /// Waits for the previous submission of this frame, resets its recorder and begins recording
+    pub fn begin_frame(&mut self) -> &mut CommandRecorder {
+        self.device.wait_fence(self.fences[self.current]);
+
+        let recorder = &mut self.recorders[self.current];
+        recorder.reset();
+        recorder.begin_recording(CommandBufferUsage::OneTimeSubmit);
+
+        return recorder;
+    }
+
+    /// Ends recording and submits it ahead of `submit_info.command_buffers`, signalling this frame's fence.
+    /// Moves on to the next frame
+    pub fn end_frame(&mut self, mut submit_info: QueueSubmitInfo) {
+        assert!(submit_info.fence.is_none(), "Frames signals its own fence, leave QueueSubmitInfo::fence as None");
+
+        let exec_buffer = self.recorders[self.current].end_recording();
+        submit_info.command_buffers.insert(0, exec_buffer);
+        submit_info.fence = Some(self.fences[self.current]);
+
+        // Only reset right before the submit, a frame that never reaches end_frame keeps its fence signalled
+        self.device.reset_fence(self.fences[self.current]);
+        self.device.submit(&submit_info);
+
+        self.current = (self.current + 1) % N;
+    }
+
+    /// Recorder of the current frame, only valid to record into between `begin_frame` and `end_frame`
+    pub fn recorder(&mut self) -> &mut CommandRecorder {
+        return &mut self.recorders[self.current];
+    }
+
+    /// Index of the current frame, in `0..N`
+    pub fn frame_index(&self) -> usize {
+        return self.current;
+    }
+
+    /// Fence signalled by the current frame's last submission
+    pub fn fence(&self) -> Fence {
+        return self.fences[self.current];
+    }
+}
+
+impl<const N: usize> Drop for Frames<N> {
+    fn drop(&mut self) {
+        for &fence in &self.fences {
+            self.device.wait_fence(fence);
+            self.device.destroy_fence(fence);
+        }
+    }
+}
@@ -1 +1,7 @@
+pub mod draw_batch;
+pub mod frames;
+pub mod recorder_pool;
+pub mod staging_ring;
+#[cfg(feature = "text")]
+pub mod text;
 pub mod vulkan_context;
@@ -0,0 +1,55 @@
+use std::sync::Mutex;
+use std::thread::ThreadId;
+
+use ahash::{HashMap, HashMapExt};
+
+use crate::{CommandRecorder, Device, QueueType};
+
+/// Hands out one command recorder per thread for parallel recording, every recorder has its own command pool.
+///
+/// A thread takes its recorder with `acquire`, records without holding any lock and gives it back with `release`.
+/// The same thread gets the same recorder back on its next `acquire`
+pub struct CommandRecorderPool {
+    device: Device,
+    queue_type: QueueType,
+    recorders: Mutex<HashMap<ThreadId, CommandRecorder>>,
+}
+
+impl CommandRecorderPool {
+    pub fn new(device: &Device, queue_type: QueueType) -> CommandRecorderPool {
+        return CommandRecorderPool {
+            device: device.clone(),
+            queue_type: queue_type,
+            recorders: Mutex::new(HashMap::new()),
+        };
+    }
+
+    /// Takes the calling thread's recorder out of the pool, creating it on first use
+    pub fn acquire(&self) -> CommandRecorder {
+        let thread_id = std::thread::current().id();
+        let recorder = self.recorders.lock().unwrap().remove(&thread_id);
+
+        return match recorder {
+            Some(recorder) => recorder,
+            None => self.device.create_command_recorder_for_thread(self.queue_type),
+        };
+    }
+
+    /// Gives a recorder back, it has to be released on the thread that acquired it
+    pub fn release(&self, recorder: CommandRecorder) {
+        assert!(recorder.queue_type == self.queue_type, "Recorder released into a pool of a different queue type");
+
+        let thread_id = std::thread::current().id();
+        let previous = self.recorders.lock().unwrap().insert(thread_id, recorder);
+
+        assert!(previous.is_none(), "Thread {:?} released a recorder while its own recorder was still in the pool", thread_id);
+    }
+
+    /// Resets every recorder in the pool. Recorders that are currently acquired are not reset,
+    /// and every buffer recorded from the pool must have finished executing
+    pub fn reset(&mut self) {
+        for recorder in self.recorders.get_mut().unwrap().values_mut() {
+            recorder.reset();
+        }
+    }
+}
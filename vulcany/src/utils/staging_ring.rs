@@ -0,0 +1,106 @@
+use crate::{BufferDescription, BufferID, BufferUsage, Device, Fence, MemoryType};
+
+/// Suballocates upload space from one persistently mapped host buffer.
+///
+/// Regions are recycled per frame index: call `begin_frame` with the fence of the
+/// submission that last used that frame index, then `allocate` as often as needed.
+/// Frames are expected to complete in the order they were submitted
+pub struct StagingRing {
+    device: Device,
+    buffer: BufferID,
+    ptr: *mut u8,
+    capacity: u64,
+    alignment: u64,
+    // Offsets only ever grow, the physical offset is `offset % capacity`
+    head: u64,
+    tail: u64,
+    frame_ends: Vec<u64>,
+    current_frame: usize,
+}
+
+impl StagingRing {
+    pub fn new(device: &Device, capacity: u64, frames_in_flight: usize) -> StagingRing {
+        let buffer = device.create_buffer(&BufferDescription {
+            usage: BufferUsage::TRANSFER_SRC,
+            size: capacity,
+            memory_type: MemoryType::PreferHost,
+            create_mapped: true,
+            ..Default::default()
+        });
+
+        let ptr = device.mapped_ptr(buffer).expect("Staging ring buffer is not mapped");
+
+        // All of these are powers of two, so the largest one satisfies every one of them
+        let alignments = device.copy_alignments();
+        let alignment = alignments.non_coherent_atom_size.max(alignments.optimal_buffer_copy_offset_alignment).max(16);
+
+        return StagingRing {
+            device: device.clone(),
+            buffer: buffer,
+            ptr: ptr,
+            capacity: capacity,
+            alignment: alignment,
+            head: 0,
+            tail: 0,
+            frame_ends: vec![0; frames_in_flight],
+            current_frame: 0,
+        };
+    }
+
+    /// Waits on `fence` and frees everything allocated the last time `frame_index` was used.
+    /// Call this before resetting the fence
+    pub fn begin_frame(&mut self, frame_index: usize, fence: Fence) {
+        self.device.wait_fence(fence);
+
+        self.tail = self.tail.max(self.frame_ends[frame_index]);
+        self.current_frame = frame_index;
+    }
+
+    /// Returns the ring buffer, the offset of the region inside it and a pointer to the region.
+    /// Memory may be non coherent, use `write` or flush the region yourself
+    pub fn allocate(&mut self, size: u64) -> (BufferID, u64, *mut u8) {
+        assert!(size <= self.capacity, "Staging allocation of {} bytes is larger than the ring ({} bytes)", size, self.capacity);
+
+        let mut start = self.head.div_ceil(self.alignment) * self.alignment;
+
+        // Regions never wrap around the end of the buffer
+        if start % self.capacity + size > self.capacity {
+            start = start.div_ceil(self.capacity) * self.capacity;
+        }
+
+        if start + size - self.tail > self.capacity {
+            panic!("StagingRing is full, increase its capacity or wait on older frames");
+        }
+
+        self.head = start + size;
+        self.frame_ends[self.current_frame] = self.head;
+
+        let offset = start % self.capacity;
+
+        return (self.buffer, offset, unsafe { self.ptr.add(offset as usize) });
+    }
+
+    /// Copies `data` into a fresh region and flushes it
+    pub fn write<T: bytemuck::Pod>(&mut self, data: &[T]) -> (BufferID, u64) {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let (buffer, offset, ptr) = self.allocate(bytes.len() as u64);
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+        }
+
+        self.device.flush_buffer(buffer, offset, bytes.len() as u64);
+
+        return (buffer, offset);
+    }
+
+    pub fn buffer(&self) -> BufferID {
+        return self.buffer;
+    }
+}
+
+impl Drop for StagingRing {
+    fn drop(&mut self) {
+        self.device.destroy_buffer(self.buffer);
+    }
+}
@@ -0,0 +1,387 @@
+use std::path::Path;
+
+use crate::{
+    AccessType, Barrier, BufferDescription, BufferID, BufferImageCopyInfo, BufferImageCopyRegion, BufferUsage, CommandRecorder, DepthStencilOptions, Device, Extent3D, Format, ImageAspect,
+    ImageBarrier, ImageDescription, ImageDescriptorType, ImageID, ImageLayout, ImageSubresourceLayers, ImageUsage, ImageViewDescription, ImageViewID, MemoryType, Offset3D, PipelineManager,
+    PipelineOutputs, PipelineStage, PushConstantsDescription, QueueType, RasterizationPipeline, RasterizationPipelineDescription, SamplerAddressMode, SamplerDescription, SamplerID, ShaderStages,
+};
+
+const FIRST_CHAR: u8 = b' ';
+const LAST_CHAR: u8 = b'~';
+const ATLAS_WIDTH: u32 = 512;
+
+// The shaders ship inside the crate and are written next to the shader cache, pipelines are compiled from paths
+const VERTEX_SHADER_PATH: &str = ".cache/vulcany_text_vertex.slang";
+const FRAGMENT_SHADER_PATH: &str = ".cache/vulcany_text_fragment.slang";
+const VERTEX_SHADER: &str = include_str!("shaders/text_vertex.slang");
+const FRAGMENT_SHADER: &str = include_str!("shaders/text_fragment.slang");
+
+crate::vertex!(TextVertex {
+    input_rate: Vertex,
+    pos: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+});
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextPushConstants {
+    atlas_index: u32,
+    sampler_index: u32,
+}
+
+#[derive(Clone, Copy)]
+struct Glyph {
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    size: [f32; 2],
+    // Bitmap corner relative to the pen position on the baseline, y points up
+    offset: [f32; 2],
+    advance: f32,
+}
+
+pub struct TextRendererDescription<'a> {
+    /// TTF or OTF font data
+    pub font_data: &'a [u8],
+    /// Glyph height in pixels, the atlas is rasterized once at this size
+    pub pixel_size: f32,
+    /// Format of the color attachment the text is drawn into
+    pub color_format: Format,
+    /// Maximum number of glyphs recorded per frame
+    pub max_glyphs: u32,
+    pub frames_in_flight: usize,
+}
+
+/// Draws ASCII debug text from a font atlas registered in the bindless sampled images.
+///
+/// Queue strings with `queue_text` and record them all with `record` inside a rendering.
+/// Every frame index has its own region of the vertex buffer, so a frame index must not be
+/// recorded again before the gpu is done with its last use
+pub struct TextRenderer {
+    device: Device,
+    pipeline: RasterizationPipeline,
+    atlas: ImageID,
+    atlas_view: ImageViewID,
+    sampler: SamplerID,
+    atlas_index: u32,
+    sampler_index: u32,
+    glyphs: Vec<Glyph>,
+    ascent: f32,
+    line_height: f32,
+    vertex_buffer: BufferID,
+    vertex_ptr: *mut TextVertex,
+    max_glyphs: u32,
+    frames_in_flight: usize,
+    vertices: Vec<TextVertex>,
+}
+
+impl TextRenderer {
+    pub fn new(device: &Device, pipeline_manager: &PipelineManager, text_desc: &TextRendererDescription) -> TextRenderer {
+        let font = fontdue::Font::from_bytes(text_desc.font_data, fontdue::FontSettings::default()).expect("Failed to parse font");
+        let line_metrics = font.horizontal_line_metrics(text_desc.pixel_size).expect("Font has no horizontal line metrics");
+
+        let rasterized: Vec<(fontdue::Metrics, Vec<u8>)> = (FIRST_CHAR..=LAST_CHAR).map(|c| font.rasterize(c as char, text_desc.pixel_size)).collect();
+
+        // Shelf packing with a one texel gap so linear filtering never bleeds into a neighbour
+        let mut positions = Vec::with_capacity(rasterized.len());
+        let (mut x, mut y, mut row_height) = (0u32, 0u32, 0u32);
+
+        for (metrics, _) in &rasterized {
+            let (width, height) = (metrics.width as u32, metrics.height as u32);
+            assert!(width < ATLAS_WIDTH, "Glyphs are too large for the text atlas, lower the pixel size");
+
+            if x + width + 1 > ATLAS_WIDTH {
+                x = 0;
+                y += row_height + 1;
+                row_height = 0;
+            }
+
+            positions.push((x, y));
+            x += width + 1;
+            row_height = row_height.max(height);
+        }
+
+        let atlas_height = (y + row_height).max(1).next_power_of_two();
+
+        // White texels with the coverage in alpha
+        let texel_size = Format::Rgba8Unorm.bytes_per_texel();
+        let mut pixels = vec![0u8; (ATLAS_WIDTH * atlas_height * texel_size) as usize];
+        let mut glyphs = Vec::with_capacity(rasterized.len());
+
+        for ((metrics, bitmap), (gx, gy)) in rasterized.iter().zip(&positions) {
+            for row in 0..metrics.height {
+                for col in 0..metrics.width {
+                    let texel = (((gy + row as u32) * ATLAS_WIDTH + gx + col as u32) * texel_size) as usize;
+                    pixels[texel..texel + texel_size as usize].copy_from_slice(&[255, 255, 255, bitmap[row * metrics.width + col]]);
+                }
+            }
+
+            glyphs.push(Glyph {
+                uv_min: [*gx as f32 / ATLAS_WIDTH as f32, *gy as f32 / atlas_height as f32],
+                uv_max: [(gx + metrics.width as u32) as f32 / ATLAS_WIDTH as f32, (gy + metrics.height as u32) as f32 / atlas_height as f32],
+                size: [metrics.width as f32, metrics.height as f32],
+                offset: [metrics.xmin as f32, metrics.ymin as f32],
+                advance: metrics.advance_width,
+            });
+        }
+
+        let atlas = device.create_image(&ImageDescription {
+            usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+            format: Format::Rgba8Unorm,
+            width: ATLAS_WIDTH,
+            height: atlas_height,
+            depth: 1,
+            memory_type: MemoryType::DeviceLocal,
+            ..Default::default()
+        });
+
+        TextRenderer::upload_atlas(device, atlas, &pixels, atlas_height);
+
+        let atlas_view = device.create_image_view(atlas, &ImageViewDescription::default());
+        let sampler = device.create_sampler(&SamplerDescription {
+            address_mode_u: SamplerAddressMode::ClampToEdge,
+            address_mode_v: SamplerAddressMode::ClampToEdge,
+            address_mode_w: SamplerAddressMode::ClampToEdge,
+            ..SamplerDescription::for_texture(1)
+        });
+
+        let atlas_index = device.register_image(atlas_view, ImageDescriptorType::SampledImage);
+        let sampler_index = device.register_sampler(sampler);
+
+        TextRenderer::write_shader(VERTEX_SHADER_PATH, VERTEX_SHADER);
+        TextRenderer::write_shader(FRAGMENT_SHADER_PATH, FRAGMENT_SHADER);
+
+        let pipeline = pipeline_manager.create_rasterization_pipeline(&RasterizationPipelineDescription {
+            vertex_input: TextVertex::vertex_input_description(),
+            push_constants: PushConstantsDescription {
+                stage_flags: ShaderStages::FRAGMENT,
+                offset: 0,
+                size: size_of::<TextPushConstants>() as u32,
+            },
+            vertex_shader_path: VERTEX_SHADER_PATH,
+            fragment_shader_path: Some(FRAGMENT_SHADER_PATH),
+            depth_stencil: DepthStencilOptions {
+                depth_test_enable: false,
+                depth_write_enable: false,
+                ..Default::default()
+            },
+            alpha_blend_enable: true,
+            outputs: PipelineOutputs {
+                color: vec![text_desc.color_format],
+                depth: None,
+                stencil: None,
+                view_mask: 0,
+            },
+            ..Default::default()
+        });
+
+        let vertex_count = text_desc.max_glyphs as u64 * 6 * text_desc.frames_in_flight as u64;
+        let vertex_buffer = device.create_buffer(&BufferDescription {
+            usage: BufferUsage::VERTEX,
+            size: vertex_count * size_of::<TextVertex>() as u64,
+            memory_type: MemoryType::PreferHost,
+            create_mapped: true,
+            ..Default::default()
+        });
+
+        let vertex_ptr = device.mapped_ptr(vertex_buffer).expect("Text vertex buffer is not mapped") as *mut TextVertex;
+
+        return TextRenderer {
+            device: device.clone(),
+            pipeline: pipeline,
+            atlas: atlas,
+            atlas_view: atlas_view,
+            sampler: sampler,
+            atlas_index: atlas_index,
+            sampler_index: sampler_index,
+            glyphs: glyphs,
+            ascent: line_metrics.ascent,
+            line_height: line_metrics.new_line_size,
+            vertex_buffer: vertex_buffer,
+            vertex_ptr: vertex_ptr,
+            max_glyphs: text_desc.max_glyphs,
+            frames_in_flight: text_desc.frames_in_flight,
+            vertices: Vec::new(),
+        };
+    }
+
+    fn upload_atlas(device: &Device, atlas: ImageID, pixels: &[u8], atlas_height: u32) {
+        let staging = device.create_buffer(&BufferDescription {
+            usage: BufferUsage::TRANSFER_SRC,
+            size: pixels.len() as u64,
+            memory_type: MemoryType::PreferHost,
+            create_mapped: true,
+            ..Default::default()
+        });
+
+        device.write_data_to_buffer(staging, pixels);
+
+        device.immediate_submit(QueueType::Graphics, |recorder| {
+            recorder.pipeline_barrier(&[Barrier::Image(ImageBarrier {
+                image: atlas,
+                old_layout: ImageLayout::Undefined,
+                new_layout: ImageLayout::TransferDst,
+                src_stage: PipelineStage::TopOfPipe,
+                dst_stage: PipelineStage::Transfer,
+                src_access: AccessType::None,
+                dst_access: AccessType::TransferWrite,
+                ..Default::default()
+            })]);
+
+            recorder.copy_buffer_to_image(&BufferImageCopyInfo {
+                src_buffer: staging,
+                dst_image: atlas,
+                dst_image_layout: ImageLayout::TransferDst,
+                region: BufferImageCopyRegion {
+                    buffer_offset: 0,
+                    buffer_row_length: 0,
+                    buffer_image_height: 0,
+                    image_subresource: ImageSubresourceLayers {
+                        aspect: ImageAspect::Color,
+                        mip_level: 0,
+                        level_count: 1,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    },
+                    image_offset: Offset3D { x: 0, y: 0, z: 0 },
+                    image_extent: Extent3D {
+                        width: ATLAS_WIDTH,
+                        height: atlas_height,
+                        depth: 1,
+                    },
+                },
+            });
+
+            recorder.pipeline_barrier(&[Barrier::Image(ImageBarrier {
+                image: atlas,
+                old_layout: ImageLayout::TransferDst,
+                new_layout: ImageLayout::ShaderReadOnly,
+                src_stage: PipelineStage::Transfer,
+                dst_stage: PipelineStage::FragmentShader,
+                src_access: AccessType::TransferWrite,
+                dst_access: AccessType::ShaderRead,
+                ..Default::default()
+            })]);
+        });
+
+        device.destroy_buffer(staging);
+    }
+
+    // Only rewritten when the contents differ, a new timestamp would recompile the shader every run
+    fn write_shader(path: &str, source: &str) {
+        let path = Path::new(path);
+
+        if std::fs::read_to_string(path).map_or(true, |existing| existing != source) {
+            std::fs::write(path, source).expect("Failed to write text shader");
+        }
+    }
+
+    /// Queues `text` with the top left corner of its first line at `(x, y)` in pixels.
+    /// Characters outside printable ASCII are skipped, `\n` starts a new line
+    pub fn queue_text(&mut self, text: &str, x: f32, y: f32, color: [f32; 4]) {
+        let mut pen_x = x;
+        let mut baseline = y + self.ascent;
+
+        for c in text.chars() {
+            if c == '\n' {
+                pen_x = x;
+                baseline += self.line_height;
+                continue;
+            }
+
+            if !c.is_ascii() || (c as u8) < FIRST_CHAR || (c as u8) > LAST_CHAR {
+                continue;
+            }
+
+            let glyph = self.glyphs[(c as u8 - FIRST_CHAR) as usize];
+
+            if glyph.size[0] > 0.0 && glyph.size[1] > 0.0 {
+                let left = pen_x + glyph.offset[0];
+                let top = baseline - glyph.offset[1] - glyph.size[1];
+                let right = left + glyph.size[0];
+                let bottom = top + glyph.size[1];
+
+                let vertex = |pos: [f32; 2], uv: [f32; 2]| TextVertex { pos: pos, uv: uv, color: color };
+
+                let top_left = vertex([left, top], glyph.uv_min);
+                let top_right = vertex([right, top], [glyph.uv_max[0], glyph.uv_min[1]]);
+                let bottom_left = vertex([left, bottom], [glyph.uv_min[0], glyph.uv_max[1]]);
+                let bottom_right = vertex([right, bottom], glyph.uv_max);
+
+                self.vertices.extend_from_slice(&[top_left, bottom_left, top_right, top_right, bottom_left, bottom_right]);
+            }
+
+            pen_x += glyph.advance;
+        }
+    }
+
+    /// Height of one line of text in pixels
+    pub fn line_height(&self) -> f32 {
+        return self.line_height;
+    }
+
+    /// Records every queued string and clears the queue. Must be called inside a rendering
+    /// into a `width` x `height` target, the viewport and scissor are left to the caller
+    pub fn record(&mut self, recorder: &mut CommandRecorder, frame_index: usize, width: u32, height: u32) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let glyph_count = self.vertices.len() / 6;
+        assert!(
+            glyph_count <= self.max_glyphs as usize,
+            "Queued {} glyphs but the text renderer only fits {} per frame",
+            glyph_count,
+            self.max_glyphs
+        );
+
+        // Every frame writes its own part of the vertex buffer
+        assert!(
+            frame_index < self.frames_in_flight,
+            "Frame index {} is out of range, the text renderer was created for {} frames in flight",
+            frame_index,
+            self.frames_in_flight
+        );
+
+        let first_vertex = frame_index * self.max_glyphs as usize * 6;
+
+        // Pixels to normalized device coordinates, vulkan's y already points down
+        for (i, v) in self.vertices.iter().enumerate() {
+            let vertex = TextVertex {
+                pos: [v.pos[0] / width as f32 * 2.0 - 1.0, v.pos[1] / height as f32 * 2.0 - 1.0],
+                ..*v
+            };
+
+            unsafe {
+                self.vertex_ptr.add(first_vertex + i).write(vertex);
+            }
+        }
+
+        let offset = (first_vertex * size_of::<TextVertex>()) as u64;
+        self.device.flush_buffer(self.vertex_buffer, offset, (self.vertices.len() * size_of::<TextVertex>()) as u64);
+
+        recorder.bind_pipeline(&self.pipeline);
+        recorder.set_push_constants(
+            &TextPushConstants {
+                atlas_index: self.atlas_index,
+                sampler_index: self.sampler_index,
+            },
+            &self.pipeline,
+        );
+        recorder.bind_vertex_buffer(self.vertex_buffer, offset);
+        recorder.draw(self.vertices.len() as u32, 1, 0, 0);
+
+        self.vertices.clear();
+    }
+}
+
+impl Drop for TextRenderer {
+    fn drop(&mut self) {
+        self.device.unregister_image(self.atlas_index, ImageDescriptorType::SampledImage);
+        self.device.unregister_sampler(self.sampler_index);
+        self.device.destroy_buffer(self.vertex_buffer);
+        self.device.destroy_sampler(self.sampler);
+        self.device.destroy_image_view(self.atlas_view);
+        self.device.destroy_image(self.atlas);
+    }
+}
@@ -30,16 +30,30 @@ impl VulkanContext {
 }
 
 impl VulkanContext {
-    pub fn resize(&mut self, width: u32, height: u32) {
+    /// Recreates the swapchain with the original image count and usage.
+    /// Returns false without recreating while the window is minimized, skip rendering until it returns true
+    pub fn resize(&mut self, width: u32, height: u32) -> bool {
+        let extent = self.device.inner.surface_extent(width, height);
+
+        if extent.width == 0 || extent.height == 0 {
+            return false;
+        }
+
         self.wait_idle();
         let d = SwapchainDescription {
-            width: width,
-            height: height,
+            width: extent.width,
+            height: extent.height,
             image_count: self.swapchain_description.image_count,
+            usage: self.swapchain_description.usage,
         };
         let new_swapchain = self.device.recreate_swapchain(&d, &self.swapchain);
         let old_swapchain = std::mem::replace(&mut self.swapchain, new_swapchain);
         drop(old_swapchain);
+
+        self.swapchain_description.width = extent.width;
+        self.swapchain_description.height = extent.height;
+
+        return true;
     }
 }
 
@@ -48,34 +62,93 @@ impl VulkanContext {
         to self.device {
             //Buffer
             pub fn create_buffer(&self, buffer_desc: &BufferDescription) -> BufferID;
+            pub fn create_buffer_with_fallback(&self, buffer_desc: &BufferDescription) -> (BufferID, MemoryType);
             pub fn destroy_buffer(&self, id: BufferID);
+            pub fn destroy_buffer_deferred(&self, id: BufferID, frames: u32);
             pub fn write_data_to_buffer<T: Copy>(&self, buffer_id: BufferID, data: &[T]);
+            pub fn upload_buffer<T: Copy>(&self, data: &[T], usage: BufferUsage) -> BufferID;
+            pub fn flush_buffer(&self, buffer_id: BufferID, offset: u64, size: u64);
+            pub fn invalidate_buffer(&self, buffer_id: BufferID, offset: u64, size: u64);
+            pub fn mapped_ptr(&self, buffer_id: BufferID) -> Option<*mut u8>;
+            pub fn map_buffer(&self, buffer_id: BufferID) -> *mut u8;
+            pub fn unmap_buffer(&self, buffer_id: BufferID);
+            pub fn buffer_device_address(&self, buffer_id: BufferID) -> u64;
             //Image
             pub fn create_image(&self, image_desc: &ImageDescription) -> ImageID;
+            pub fn create_image_with_fallback(&self, image_desc: &ImageDescription) -> (ImageID, MemoryType);
             pub fn destroy_image(&self, image_id: ImageID);
+            pub fn destroy_image_deferred(&self, image_id: ImageID, frames: u32);
             //Image view
             pub fn create_image_view(&self, image_id: ImageID, image_view_desc: &ImageViewDescription) -> ImageViewID;
             pub fn destroy_image_view(&self, image_view_id: ImageViewID);
+            pub fn create_depth_target(&self, width: u32, height: u32, format: Format) -> (ImageID, ImageViewID);
             //Sampler
             pub fn create_sampler(&self, sampler_desc: &SamplerDescription) -> SamplerID;
+            pub fn default_sampler(&self) -> SamplerID;
+            pub fn default_nearest_sampler(&self) -> SamplerID;
             pub fn destroy_sampler(&self, sampler_id: SamplerID);
+            //Acceleration structure
+            pub fn build_blas(&self, blas_desc: &BlasDescription) -> AccelerationStructureID;
+            pub fn build_tlas(&self, tlas_desc: &TlasDescription) -> AccelerationStructureID;
+            pub fn destroy_acceleration_structure(&self, id: AccelerationStructureID);
             // Descriptors
             pub fn write_buffer(&self, buffer_write_info: &BufferWriteInfo);
+            pub fn write_uniform_buffer(&self, buffer_write_info: &BufferWriteInfo);
             pub fn write_image(&self, image_write_info: &ImageWriteInfo);
             pub fn write_sampler(&self, sampler_write_info: &SamplerWriteInfo);
+            pub fn register_buffer(&self, buffer_id: BufferID) -> u32;
+            pub fn register_uniform_buffer(&self, buffer_id: BufferID) -> u32;
+            pub fn register_image(&self, image_view_id: ImageViewID, image_descriptor_type: ImageDescriptorType) -> u32;
+            pub fn register_sampler(&self, sampler_id: SamplerID) -> u32;
+            pub fn create_sampled_texture(&self, image_desc: &ImageDescription, image_view_desc: &ImageViewDescription) -> (ImageID, ImageViewID, u32);
+            pub fn unregister_buffer(&self, index: u32);
+            pub fn unregister_uniform_buffer(&self, index: u32);
+            pub fn unregister_image(&self, index: u32, image_descriptor_type: ImageDescriptorType);
+            pub fn unregister_sampler(&self, index: u32);
+            // Device properties
+            pub fn copy_alignments(&self) -> CopyAlignments;
+            pub fn limits(&self) -> DeviceLimits;
+            pub fn format_properties(&self, format: Format) -> FormatSupport;
+            pub fn device_name(&self) -> String;
+            pub fn memory_stats(&self) -> MemoryStats;
+            pub fn memory_budget(&self) -> Vec<HeapBudget>;
+            pub fn has_separate_present_queue(&self) -> bool;
+            pub fn supports_dynamic_polygon_mode(&self) -> bool;
+            pub fn supports_custom_border_color(&self) -> bool;
+            pub fn supports_sampler_anisotropy(&self) -> bool;
+            pub fn supports_sampler_reduction(&self) -> bool;
+            pub fn supports_index_type_uint8(&self) -> bool;
+            pub fn supports_push_descriptors(&self) -> bool;
+            pub fn supports_ray_tracing(&self) -> bool;
+            pub fn supports_mesh_shaders(&self) -> bool;
             // Command buffer
             pub fn create_command_recorder(&self, queue_type: QueueType) -> CommandRecorder;
+            pub fn create_command_recorder_for_thread(&self, queue_type: QueueType) -> CommandRecorder;
+            pub fn create_command_recorder_with_flags(&self, queue_type: QueueType, flags: CommandPoolFlags) -> CommandRecorder;
+            pub fn create_secondary_command_recorder(&self, queue_type: QueueType) -> SecondaryCommandRecorder;
+            pub fn immediate_submit(&self, queue_type: QueueType, f: impl FnOnce(&mut CommandRecorder));
             // Sync
             pub fn create_fence(&self, signaled: bool) -> Fence;
+            pub fn create_event(&self) -> Event;
             pub fn create_binary_semaphore(&self) -> Semaphore;
             pub fn create_timeline_semaphore(&self) -> Semaphore;
             pub fn wait_fence(&self, fence: Fence);
+            pub fn wait_fences(&self, fences: &[Fence], wait_all: bool, timeout_ns: u64) -> bool;
             pub fn reset_fence(&self, fence: Fence);
             pub fn destroy_fence(&self, fence: Fence);
+            pub fn destroy_event(&self, event: Event);
             pub fn destroy_semaphore(&self, semaphore: Semaphore);
+            // Queries
+            pub fn create_query_pool(&self, query_type: QueryType, count: u32) -> QueryPool;
+            pub fn destroy_query_pool(&self, query_pool: QueryPool);
+            pub fn get_query_results(&self, query_pool: QueryPool, first_query: u32, query_count: u32) -> Vec<u64>;
             // Queue submissions
             pub fn submit(&self, submit_info: &QueueSubmitInfo);
+            pub fn submit_batches(&self, submit_infos: &[QueueSubmitInfo]);
+            pub fn submit_async_compute(&self, submit_info: QueueSubmitInfo, timeline: Semaphore, signal_value: u64) -> SemaphoreInfo;
             pub fn wait_idle(&self);
+            pub fn try_wait_idle(&self) -> Result<(), DeviceError>;
+            pub fn is_device_lost(&self) -> bool;
             pub fn wait_queue(&self, queue_type: QueueType);
         }
         to self.swapchain {
@@ -85,6 +158,7 @@ impl VulkanContext {
         to self.pipeline_manager {
             pub fn create_rasterization_pipeline(&self, raster_pipeline_desc: &RasterizationPipelineDescription) -> RasterizationPipeline;
             pub fn create_compute_pipeline(&self, compute_pipeline_desc: &ComputePipelineDescription) -> ComputePipeline;
+            pub fn create_ray_tracing_pipeline(&self, rt_pipeline_desc: &RayTracingPipelineDescription) -> RayTracingPipeline;
         }
     }
 }
@@ -0,0 +1,100 @@
+use vulcany::*;
+
+const COUNT: u32 = 1024;
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Copy, Clone)]
+struct PushConstants {
+    count: u32,
+}
+
+fn main() {
+    let instance = Instance::new_headless(&HeadlessInstanceDescription {
+        api_version: ApiVersion::VkApi1_3,
+        enable_validation_layers: true,
+        debug_callback: None,
+    });
+
+    let device = instance.create_device(&DeviceDescription {
+        use_compute_queue: true,
+        use_transfer_queue: false,
+        ray_tracing: false,
+        mesh_shaders: false,
+        adapter_index: None,
+        on_device_lost: None,
+    });
+
+    let pipeline_manager = device.create_pipeline_manager();
+    let pipeline = pipeline_manager.create_compute_pipeline(&ComputePipelineDescription {
+        shader_path: "shaders/double.slang",
+        push_constants: PushConstantsDescription {
+            stage_flags: ShaderStages::COMPUTE,
+            offset: 0,
+            size: size_of::<PushConstants>() as u32,
+        },
+        specialization: vec![],
+    });
+
+    // Host visible so the result can be read back without a copy
+    let buffer = device.create_buffer(&BufferDescription {
+        usage: BufferUsage::STORAGE,
+        size: COUNT as u64 * 4,
+        memory_type: MemoryType::PreferHost,
+        create_mapped: true,
+        host_access: HostAccess::Random,
+        ..Default::default()
+    });
+
+    let input: Vec<u32> = (0..COUNT).collect();
+    device.write_data_to_buffer(buffer, &input);
+    device.write_buffer(&BufferWriteInfo {
+        buffer: buffer,
+        offset: 0,
+        range: u64::MAX,
+        index: 0,
+    });
+
+    // Recorded and submitted once
+    let mut recorder =
+        device.create_command_recorder_with_flags(QueueType::Compute, CommandPoolFlags::TRANSIENT);
+    recorder.begin_recording(CommandBufferUsage::OneTimeSubmit);
+
+    recorder.bind_pipeline(&pipeline);
+    recorder.set_push_constants(&PushConstants { count: COUNT }, &pipeline);
+    recorder.dispatch_for(&pipeline, COUNT, 1, 1);
+
+    // Make the shader writes visible to the host once the fence is signalled
+    recorder.pipeline_barrier(&[Barrier::Memory(MemoryBarrier {
+        src_stage: PipelineStage::ComputeShader,
+        dst_stage: PipelineStage::Host,
+        src_access: AccessType::ShaderWrite,
+        dst_access: AccessType::HostRead,
+    })]);
+
+    let exec_buffer = recorder.end_recording();
+
+    let fence = device.create_fence(false);
+    device.submit(&QueueSubmitInfo {
+        fence: Some(fence),
+        command_buffers: vec![exec_buffer],
+        wait_semaphores: vec![],
+        signal_semaphores: vec![],
+    });
+    device.wait_fence(fence);
+
+    device.invalidate_buffer(buffer, 0, COUNT as u64 * 4);
+
+    let ptr = device
+        .mapped_ptr(buffer)
+        .expect("Storage buffer is not mapped") as *const u32;
+    let output = unsafe { std::slice::from_raw_parts(ptr, COUNT as usize) };
+
+    for (i, &value) in output.iter().enumerate() {
+        assert_eq!(value, i as u32 * 2, "Wrong value at index {}", i);
+    }
+
+    println!("All {} values doubled on the gpu", COUNT);
+
+    device.destroy_fence(fence);
+    device.destroy_buffer(buffer);
+}
@@ -26,6 +26,8 @@ pub struct Renderer {
     pipeline: RasterizationPipeline,
     curr_frame: usize,
     frame_data: [FrameData; FRAMES_IN_FLIGHT],
+    // Set while the window is minimized and the swapchain could not be recreated
+    minimized: bool,
 }
 
 impl Renderer {
@@ -36,23 +38,29 @@ impl Renderer {
             &InstanceDescription {
                 api_version: ApiVersion::VkApi1_3,
                 enable_validation_layers: false,
+                debug_callback: None,
                 window: window.clone(),
             },
             &DeviceDescription {
                 use_compute_queue: true,
                 use_transfer_queue: true,
+                ray_tracing: false,
+                mesh_shaders: false,
+                adapter_index: None,
+                on_device_lost: None,
             },
             &SwapchainDescription {
                 image_count: 3,
                 width: size.width,
                 height: size.height,
+                usage: ImageUsage::COLOR_ATTACHMENT,
             },
         );
 
         let pipeline =
             vk_context.create_rasterization_pipeline(&RasterizationPipelineDescription {
                 vertex_shader_path: "shaders/vertex.slang",
-                fragment_shader_path: "shaders/fragment.slang",
+                fragment_shader_path: Some("shaders/fragment.slang"),
                 cull_mode: CullMode::Back,
                 front_face: FrontFace::Clockwise,
                 push_constants: PushConstantsDescription {
@@ -64,6 +72,7 @@ impl Renderer {
                     color: vec![Format::Rgba16Float],
                     depth: None,
                     stencil: None,
+                    view_mask: 0,
                 },
                 ..Default::default()
             });
@@ -78,14 +87,19 @@ impl Renderer {
             pipeline: pipeline,
             curr_frame: 0,
             frame_data: frame_data,
+            minimized: false,
         };
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
-        self.vk_context.resize(width, height);
+        self.minimized = !self.vk_context.resize(width, height);
     }
 
     pub fn render(&mut self, camera: &Camera, time: f32, size: PhysicalSize<u32>) {
+        if self.minimized {
+            return;
+        }
+
         let push_constants = MyPushConstants {
             view_proj_mat: camera.get_inv_view_proj(),
             pos: camera.get_pos(),
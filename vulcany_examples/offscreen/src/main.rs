@@ -0,0 +1,356 @@
+//! Render to texture.
+//!
+//! A spinning triangle is rendered into an `Rgba16Float` image that is not part of the swapchain.
+//! A second pass draws a fullscreen triangle that samples that image with the device's default
+//! sampler and writes the result to the swapchain image.
+//!
+//! The target needs both `COLOR_ATTACHMENT` and `SAMPLED` usage and is written to a bindless
+//! sampled image slot once with `write_image`. `transition_image` moves it between
+//! `ColorAttachment` and `ShaderReadOnly` every frame, the recorder remembers the previous layout.
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulcany::utils::frames::Frames;
+use vulcany::*;
+use winit::{
+    application::ApplicationHandler, event::WindowEvent, event_loop::EventLoop, window::Window,
+};
+
+const FRAME_IN_FLIGHT: usize = 3;
+const TARGET_SIZE: u32 = 512;
+// Bindless sampled image slot of the offscreen target
+const TARGET_INDEX: u32 = 0;
+
+vertex!(MyVertex {
+    input_rate: Vertex,
+    pos: [f32; 2],
+    color: [f32; 3],
+});
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Copy, Clone)]
+struct MyPushConstants {
+    time: f32,
+    texture_index: u32,
+    sampler_index: u32,
+    pad: u32,
+}
+
+#[allow(unused)]
+struct VulkanApp {
+    window: Arc<Window>,
+    instance: Instance,
+    device: Device,
+    swapchain: Swapchain,
+    pipeline_manager: PipelineManager,
+    scene_pipeline: RasterizationPipeline,
+    present_pipeline: RasterizationPipeline,
+    vertex_buffer: BufferID,
+    target: ImageID,
+    target_view: ImageViewID,
+    start: Instant,
+    frames: Frames<FRAME_IN_FLIGHT>,
+}
+
+impl VulkanApp {
+    fn new(event_loop: &EventLoop<()>) -> VulkanApp {
+        let window = Arc::new(
+            event_loop
+                .create_window(Window::default_attributes())
+                .expect("Failed to create window"),
+        );
+
+        let size = window.inner_size();
+
+        let instance = Instance::new(&InstanceDescription {
+            api_version: ApiVersion::VkApi1_3,
+            enable_validation_layers: true,
+            debug_callback: None,
+            window: window.clone(),
+        });
+
+        let device = instance.create_device(&DeviceDescription {
+            use_compute_queue: false,
+            use_transfer_queue: true,
+            ray_tracing: false,
+            mesh_shaders: false,
+            adapter_index: None,
+            on_device_lost: None,
+        });
+
+        let swapchain = device.create_swapchain(&SwapchainDescription {
+            image_count: 3,
+            width: size.width,
+            height: size.height,
+            usage: ImageUsage::COLOR_ATTACHMENT,
+        });
+
+        let push_constants = PushConstantsDescription {
+            stage_flags: ShaderStages::ALL_GRAPHICS,
+            offset: 0,
+            size: size_of::<MyPushConstants>() as u32,
+        };
+
+        let pipeline_manager = device.create_pipeline_manager();
+
+        let scene_pipeline =
+            pipeline_manager.create_rasterization_pipeline(&RasterizationPipelineDescription {
+                vertex_input: MyVertex::vertex_input_description(),
+                push_constants: push_constants,
+                vertex_shader_path: "shaders/scene_vertex.slang",
+                fragment_shader_path: Some("shaders/scene_fragment.slang"),
+                outputs: PipelineOutputs {
+                    color: vec![Format::Rgba16Float],
+                    depth: None,
+                    stencil: None,
+                    view_mask: 0,
+                },
+                ..Default::default()
+            });
+
+        // No vertex input, the vertex shader generates the fullscreen triangle
+        let present_pipeline =
+            pipeline_manager.create_rasterization_pipeline(&RasterizationPipelineDescription {
+                push_constants: push_constants,
+                vertex_shader_path: "shaders/present_vertex.slang",
+                fragment_shader_path: Some("shaders/present_fragment.slang"),
+                outputs: PipelineOutputs {
+                    color: vec![Format::Rgba16Float],
+                    depth: None,
+                    stencil: None,
+                    view_mask: 0,
+                },
+                ..Default::default()
+            });
+
+        let vertex_data = [
+            MyVertex {
+                pos: [0.0, -0.6],
+                color: [0.9, 0.3, 0.2],
+            },
+            MyVertex {
+                pos: [0.52, 0.3],
+                color: [0.2, 0.9, 0.3],
+            },
+            MyVertex {
+                pos: [-0.52, 0.3],
+                color: [0.2, 0.3, 0.9],
+            },
+        ];
+
+        let vertex_buffer = device.upload_buffer(&vertex_data, BufferUsage::VERTEX);
+
+        // Rendered to as a color attachment, then sampled by the present pass
+        let target = device.create_image(&ImageDescription {
+            usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+            format: Format::Rgba16Float,
+            width: TARGET_SIZE,
+            height: TARGET_SIZE,
+            memory_type: MemoryType::DeviceLocal,
+            dedicated: true,
+            ..Default::default()
+        });
+        let target_view = device.create_image_view(target, &ImageViewDescription::default());
+
+        device.write_image(&ImageWriteInfo {
+            view: target_view,
+            image_descriptor_type: ImageDescriptorType::SampledImage,
+            index: TARGET_INDEX,
+            layout: Some(ImageLayout::ShaderReadOnly),
+        });
+
+        // Created on first use, it stays bound at DEFAULT_SAMPLER_INDEX
+        device.default_sampler();
+
+        return VulkanApp {
+            frames: Frames::new(&device, QueueType::Graphics),
+            window: window,
+            instance: instance,
+            device: device,
+            swapchain: swapchain,
+            pipeline_manager: pipeline_manager,
+            scene_pipeline: scene_pipeline,
+            present_pipeline: present_pipeline,
+            vertex_buffer: vertex_buffer,
+            target: target,
+            target_view: target_view,
+            start: Instant::now(),
+        };
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        // Minimized, the swapchain is recreated once the window has a size again
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        // The offscreen target keeps its size, only the swapchain follows the window
+        self.device.wait_idle();
+        let new_swapchain = self.device.recreate_swapchain(
+            &SwapchainDescription {
+                image_count: 3,
+                width: width,
+                height: height,
+                usage: ImageUsage::COLOR_ATTACHMENT,
+            },
+            &self.swapchain,
+        );
+        let old_swapchain = std::mem::replace(&mut self.swapchain, new_swapchain);
+        drop(old_swapchain);
+    }
+
+    fn render(&mut self) {
+        let size = self.window.inner_size();
+
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        let push_constants = MyPushConstants {
+            time: self.start.elapsed().as_secs_f32(),
+            texture_index: TARGET_INDEX,
+            sampler_index: DEFAULT_SAMPLER_INDEX,
+            pad: 0,
+        };
+
+        let recorder = self.frames.begin_frame();
+
+        let (img, img_view, image_semaphore, present_semaphore) = self.swapchain.acquire_image();
+
+        // The previous frame might still be sampling the target
+        recorder.transition_image(
+            self.target,
+            ImageLayout::ColorAttachment,
+            PipelineStage::FragmentShader,
+            PipelineStage::ColorAttachmentOutput,
+        );
+
+        // Offscreen pass
+        recorder.begin_rendering(&RenderingBeginInfo {
+            render_area: RenderArea {
+                offset: Offset2D { x: 0, y: 0 },
+                extent: Extent2D {
+                    width: TARGET_SIZE,
+                    height: TARGET_SIZE,
+                },
+            },
+            layer_count: 1,
+            color_attachments: vec![RenderingAttachment {
+                image_view: self.target_view,
+                image_layout: ImageLayout::ColorAttachment,
+                clear_value: ClearValue::ColorFloat([0.1, 0.1, 0.1, 1.0]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        recorder.bind_pipeline(&self.scene_pipeline);
+        recorder.set_viewport_and_scissor(TARGET_SIZE, TARGET_SIZE);
+        recorder.set_push_constants(&push_constants, &self.scene_pipeline);
+        recorder.bind_vertex_buffer(self.vertex_buffer, 0);
+        recorder.draw(3, 1, 0, 0);
+        recorder.end_rendering();
+
+        // Color writes have to finish before the present pass samples the target
+        recorder.transition_image(
+            self.target,
+            ImageLayout::ShaderReadOnly,
+            PipelineStage::ColorAttachmentOutput,
+            PipelineStage::FragmentShader,
+        );
+        recorder.pipeline_barrier(&[Barrier::image_transition(
+            img,
+            ImageLayout::Undefined,
+            ImageLayout::ColorAttachment,
+        )]);
+
+        // Present pass
+        recorder.begin_rendering(&RenderingBeginInfo {
+            render_area: RenderArea {
+                offset: Offset2D { x: 0, y: 0 },
+                extent: Extent2D {
+                    width: size.width,
+                    height: size.height,
+                },
+            },
+            layer_count: 1,
+            color_attachments: vec![RenderingAttachment {
+                image_view: img_view,
+                image_layout: ImageLayout::ColorAttachment,
+                load_op: LoadOp::DontCare,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        recorder.bind_pipeline(&self.present_pipeline);
+        recorder.set_viewport_and_scissor(size.width, size.height);
+        recorder.set_push_constants(&push_constants, &self.present_pipeline);
+        recorder.draw(3, 1, 0, 0);
+        recorder.end_rendering();
+
+        recorder.pipeline_barrier(&[Barrier::image_transition(
+            img,
+            ImageLayout::ColorAttachment,
+            ImageLayout::PresentSrc,
+        )]);
+
+        self.frames.end_frame(QueueSubmitInfo {
+            fence: None,
+            command_buffers: vec![],
+            wait_semaphores: vec![SemaphoreInfo {
+                semaphore: image_semaphore,
+                pipeline_stage: PipelineStage::ColorAttachmentOutput,
+                value: None,
+            }],
+            signal_semaphores: vec![SemaphoreInfo {
+                semaphore: present_semaphore,
+                pipeline_stage: PipelineStage::BottomOfPipe,
+                value: None,
+            }],
+        });
+
+        self.swapchain.present();
+    }
+}
+
+impl Drop for VulkanApp {
+    fn drop(&mut self) {
+        self.device.wait_idle();
+        self.device.destroy_buffer(self.vertex_buffer);
+        self.device.destroy_image_view(self.target_view);
+        self.device.destroy_image(self.target);
+    }
+}
+
+#[allow(unused)]
+impl ApplicationHandler for VulkanApp {
+    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {}
+
+    fn window_event(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        window_id: winit::window::WindowId,
+        event: winit::event::WindowEvent,
+    ) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => self.resize(size.width, size.height),
+            WindowEvent::RedrawRequested => {
+                self.render();
+                self.window.request_redraw();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let event_loop: EventLoop<()> = EventLoop::with_user_event()
+        .build()
+        .expect("Failed to create event loop");
+
+    let mut app = VulkanApp::new(&event_loop);
+
+    event_loop
+        .run_app(&mut app)
+        .expect("Failed to run event loop");
+}
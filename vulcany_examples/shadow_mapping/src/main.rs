@@ -0,0 +1,571 @@
+//! Shadow mapping with hardware PCF.
+//!
+//! The scene is first rendered from the light into a depth only shadow map. The main pass then
+//! samples that depth image through a comparison sampler (`SamplerDescription::shadow_pcf`), so
+//! `SampleCmp` in the fragment shader returns a filtered lit factor instead of a raw depth.
+//!
+//! The shadow map needs both `DEPTH_STENCIL_ATTACHMENT` and `SAMPLED` usage, a view with the depth
+//! aspect and has to be transitioned to `ShaderReadOnly` between the two passes.
+use std::sync::Arc;
+use std::time::Instant;
+
+use glam::{Mat4, Vec3};
+use vulcany::*;
+use winit::{
+    application::ApplicationHandler, event::WindowEvent, event_loop::EventLoop, window::Window,
+};
+
+const FRAME_IN_FLIGHT: usize = 3;
+const SHADOW_MAP_SIZE: u32 = 2048;
+
+vertex!(MyVertex {
+    input_rate: Vertex,
+    pos: [f32; 3],
+    normal: [f32; 3],
+    color: [f32; 3],
+});
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Copy, Clone)]
+struct SceneData {
+    view_proj: [[f32; 4]; 4],
+    light_view_proj: [[f32; 4]; 4],
+    light_dir: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Copy, Clone)]
+struct MyPushConstants {
+    scene_index: u32,
+    shadow_map_index: u32,
+    sampler_index: u32,
+    pad: u32,
+}
+
+struct FrameData {
+    command_recorder: CommandRecorder,
+    fence: Fence,
+    // Rewritten every frame, so every frame in flight gets its own
+    scene_buffer: BufferID,
+    scene_index: u32,
+}
+
+#[allow(unused)]
+struct VulkanApp {
+    window: Arc<Window>,
+    instance: Instance,
+    device: Device,
+    swapchain: Swapchain,
+    pipeline_manager: PipelineManager,
+    shadow_pipeline: RasterizationPipeline,
+    scene_pipeline: RasterizationPipeline,
+    vertex_buffer: BufferID,
+    vertex_count: u32,
+    shadow_map: ImageID,
+    shadow_map_view: ImageViewID,
+    shadow_map_index: u32,
+    shadow_sampler: SamplerID,
+    sampler_index: u32,
+    depth_image: ImageID,
+    depth_view: ImageViewID,
+    start: Instant,
+    curr_frame: usize,
+    frame_data: Vec<FrameData>,
+}
+
+// Two triangles, corners given counter clockwise
+fn quad(corners: [Vec3; 4], normal: Vec3, color: [f32; 3]) -> [MyVertex; 6] {
+    return [0, 1, 2, 0, 2, 3].map(|i| MyVertex {
+        pos: corners[i].to_array(),
+        normal: normal.to_array(),
+        color: color,
+    });
+}
+
+fn cube(center: Vec3, half: f32, color: [f32; 3]) -> Vec<MyVertex> {
+    let mut vertices = Vec::new();
+
+    for normal in [
+        Vec3::X,
+        Vec3::NEG_X,
+        Vec3::Y,
+        Vec3::NEG_Y,
+        Vec3::Z,
+        Vec3::NEG_Z,
+    ] {
+        let u = if normal.y.abs() > 0.5 {
+            Vec3::X
+        } else {
+            Vec3::Y
+        };
+        let v = normal.cross(u);
+        let face = center + normal * half;
+
+        vertices.extend(quad(
+            [
+                face - u * half - v * half,
+                face + u * half - v * half,
+                face + u * half + v * half,
+                face - u * half + v * half,
+            ],
+            normal,
+            color,
+        ));
+    }
+
+    return vertices;
+}
+
+fn scene_vertices() -> Vec<MyVertex> {
+    let mut vertices = quad(
+        [
+            Vec3::new(-6.0, 0.0, -6.0),
+            Vec3::new(-6.0, 0.0, 6.0),
+            Vec3::new(6.0, 0.0, 6.0),
+            Vec3::new(6.0, 0.0, -6.0),
+        ],
+        Vec3::Y,
+        [0.7, 0.7, 0.7],
+    )
+    .to_vec();
+
+    vertices.extend(cube(Vec3::new(0.0, 1.0, 0.0), 1.0, [0.8, 0.3, 0.2]));
+    vertices.extend(cube(Vec3::new(2.5, 0.5, 1.5), 0.5, [0.2, 0.5, 0.8]));
+    vertices.extend(cube(Vec3::new(-2.0, 2.5, -1.5), 0.4, [0.3, 0.8, 0.3]));
+
+    return vertices;
+}
+
+impl VulkanApp {
+    fn new(event_loop: &EventLoop<()>) -> VulkanApp {
+        let window = Arc::new(
+            event_loop
+                .create_window(Window::default_attributes())
+                .expect("Failed to create window"),
+        );
+
+        let size = window.inner_size();
+
+        let instance = Instance::new(&InstanceDescription {
+            api_version: ApiVersion::VkApi1_3,
+            enable_validation_layers: true,
+            debug_callback: None,
+            window: window.clone(),
+        });
+
+        let device = instance.create_device(&DeviceDescription {
+            use_compute_queue: true,
+            use_transfer_queue: true,
+            ray_tracing: false,
+            mesh_shaders: false,
+            adapter_index: None,
+            on_device_lost: None,
+        });
+
+        let swapchain = device.create_swapchain(&SwapchainDescription {
+            image_count: 3,
+            width: size.width,
+            height: size.height,
+            usage: ImageUsage::COLOR_ATTACHMENT,
+        });
+
+        let push_constants = PushConstantsDescription {
+            stage_flags: ShaderStages::ALL_GRAPHICS,
+            offset: 0,
+            size: size_of::<MyPushConstants>() as u32,
+        };
+
+        let pipeline_manager = device.create_pipeline_manager();
+
+        // Depth only, the bias pushes the stored depth back a bit to avoid shadow acne
+        let shadow_pipeline =
+            pipeline_manager.create_rasterization_pipeline(&RasterizationPipelineDescription {
+                vertex_input: MyVertex::vertex_input_description(),
+                push_constants: push_constants,
+                vertex_shader_path: "shaders/shadow_vertex.slang",
+                fragment_shader_path: None,
+                dynamic_depth_bias: true,
+                outputs: PipelineOutputs {
+                    color: vec![],
+                    depth: Some(Format::D32Float),
+                    stencil: None,
+                    view_mask: 0,
+                },
+                ..Default::default()
+            });
+
+        let scene_pipeline =
+            pipeline_manager.create_rasterization_pipeline(&RasterizationPipelineDescription {
+                vertex_input: MyVertex::vertex_input_description(),
+                push_constants: push_constants,
+                vertex_shader_path: "shaders/scene_vertex.slang",
+                fragment_shader_path: Some("shaders/scene_fragment.slang"),
+                outputs: PipelineOutputs {
+                    color: vec![Format::Rgba16Float],
+                    depth: Some(Format::D32Float),
+                    stencil: None,
+                    view_mask: 0,
+                },
+                ..Default::default()
+            });
+
+        // Geometry, uploaded once
+        let vertices = scene_vertices();
+        let vertex_size = std::mem::size_of_val(vertices.as_slice()) as u64;
+
+        let staging_buffer = device.create_buffer(&BufferDescription {
+            usage: BufferUsage::TRANSFER_SRC,
+            size: vertex_size,
+            memory_type: MemoryType::PreferHost,
+            create_mapped: true,
+            ..Default::default()
+        });
+        device.write_data_to_buffer(staging_buffer, &vertices);
+
+        let vertex_buffer = device.create_buffer(&BufferDescription {
+            usage: BufferUsage::TRANSFER_DST | BufferUsage::VERTEX,
+            size: vertex_size,
+            memory_type: MemoryType::DeviceLocal,
+            create_mapped: false,
+            ..Default::default()
+        });
+
+        device.immediate_submit(QueueType::Transfer, |recorder| {
+            recorder.copy_buffer(&BufferCopyInfo {
+                src_buffer: staging_buffer,
+                dst_buffer: vertex_buffer,
+                src_offset: 0,
+                dst_offset: 0,
+                size: vertex_size,
+            });
+        });
+        device.destroy_buffer(staging_buffer);
+
+        // Rendered to as a depth attachment, then sampled with the comparison sampler
+        let (shadow_map, shadow_map_view, shadow_map_index) = device.create_sampled_texture(
+            &ImageDescription {
+                usage: ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::SAMPLED,
+                format: Format::D32Float,
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                memory_type: MemoryType::DeviceLocal,
+                dedicated: true,
+                ..Default::default()
+            },
+            &ImageViewDescription::default(),
+        );
+
+        let shadow_sampler = device.create_sampler(&SamplerDescription::shadow_pcf());
+        let sampler_index = device.register_sampler(shadow_sampler);
+
+        let (depth_image, depth_view) =
+            device.create_depth_target(size.width, size.height, Format::D32Float);
+
+        let frame_data = (0..FRAME_IN_FLIGHT)
+            .map(|_| {
+                let scene_buffer = device.create_buffer(&BufferDescription {
+                    usage: BufferUsage::STORAGE,
+                    size: size_of::<SceneData>() as u64,
+                    memory_type: MemoryType::PreferHost,
+                    create_mapped: true,
+                    ..Default::default()
+                });
+
+                FrameData {
+                    command_recorder: device.create_command_recorder(QueueType::Graphics),
+                    fence: device.create_fence(true),
+                    scene_buffer: scene_buffer,
+                    scene_index: device.register_buffer(scene_buffer),
+                }
+            })
+            .collect();
+
+        return VulkanApp {
+            window: window,
+            instance: instance,
+            device: device,
+            swapchain: swapchain,
+            pipeline_manager: pipeline_manager,
+            shadow_pipeline: shadow_pipeline,
+            scene_pipeline: scene_pipeline,
+            vertex_buffer: vertex_buffer,
+            vertex_count: vertices.len() as u32,
+            shadow_map: shadow_map,
+            shadow_map_view: shadow_map_view,
+            shadow_map_index: shadow_map_index,
+            shadow_sampler: shadow_sampler,
+            sampler_index: sampler_index,
+            depth_image: depth_image,
+            depth_view: depth_view,
+            start: Instant::now(),
+            curr_frame: 0,
+            frame_data: frame_data,
+        };
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        // Minimized, the swapchain is recreated once the window has a size again
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        self.device.wait_idle();
+        let new_swapchain = self.device.recreate_swapchain(
+            &SwapchainDescription {
+                image_count: 3,
+                width: width,
+                height: height,
+                usage: ImageUsage::COLOR_ATTACHMENT,
+            },
+            &self.swapchain,
+        );
+        let old_swapchain = std::mem::replace(&mut self.swapchain, new_swapchain);
+        drop(old_swapchain);
+
+        self.device.destroy_image_view(self.depth_view);
+        self.device.destroy_image(self.depth_image);
+        (self.depth_image, self.depth_view) =
+            self.device
+                .create_depth_target(width, height, Format::D32Float);
+    }
+
+    fn scene_data(&self, width: u32, height: u32) -> SceneData {
+        let mut proj =
+            Mat4::perspective_rh(45f32.to_radians(), width as f32 / height as f32, 0.1, 100.0);
+        // Vulkan clip space y points down
+        proj.y_axis.y *= -1.0;
+        let view = Mat4::look_at_rh(Vec3::new(8.0, 7.0, 8.0), Vec3::ZERO, Vec3::Y);
+
+        // Directional light circling the scene, the orthographic box covers the whole ground
+        let time = self.start.elapsed().as_secs_f32() * 0.5;
+        let light_pos = Vec3::new(time.cos() * 8.0, 10.0, time.sin() * 8.0);
+        let light_view = Mat4::look_at_rh(light_pos, Vec3::ZERO, Vec3::Y);
+        let light_proj = Mat4::orthographic_rh(-9.0, 9.0, -9.0, 9.0, 0.1, 30.0);
+
+        return SceneData {
+            view_proj: (proj * view).to_cols_array_2d(),
+            light_view_proj: (light_proj * light_view).to_cols_array_2d(),
+            light_dir: (-light_pos.normalize()).extend(0.0).to_array(),
+        };
+    }
+
+    fn render(&mut self) {
+        let size = self.window.inner_size();
+
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        let scene_data = self.scene_data(size.width, size.height);
+        let frame = &mut self.frame_data[self.curr_frame];
+
+        self.device.wait_fence(frame.fence);
+        self.device.reset_fence(frame.fence);
+
+        self.device
+            .write_data_to_buffer(frame.scene_buffer, &[scene_data]);
+
+        let push_constants = MyPushConstants {
+            scene_index: frame.scene_index,
+            shadow_map_index: self.shadow_map_index,
+            sampler_index: self.sampler_index,
+            pad: 0,
+        };
+
+        let (img, img_view, image_semaphore, present_semaphore) = self.swapchain.acquire_image();
+
+        let recorder = &mut frame.command_recorder;
+        recorder.reset();
+        recorder.begin_recording(CommandBufferUsage::OneTimeSubmit);
+
+        // The previous frame might still be sampling the shadow map, wait for its fragment shaders
+        recorder.pipeline_barrier(&[
+            Barrier::Image(ImageBarrier {
+                image: self.shadow_map,
+                aspect: ImageAspect::Depth,
+                old_layout: ImageLayout::Undefined,
+                new_layout: ImageLayout::DepthStencilAttachment,
+                src_stage: PipelineStage::FragmentShader,
+                dst_stage: PipelineStage::EarlyFragmentTests,
+                src_access: AccessType::None,
+                dst_access: AccessType::DepthStencilWrite,
+                ..Default::default()
+            }),
+            Barrier::Image(ImageBarrier {
+                image: self.depth_image,
+                aspect: ImageAspect::Depth,
+                old_layout: ImageLayout::Undefined,
+                new_layout: ImageLayout::DepthStencilAttachment,
+                src_stage: PipelineStage::LateFragmentTests,
+                dst_stage: PipelineStage::EarlyFragmentTests,
+                src_access: AccessType::DepthStencilWrite,
+                dst_access: AccessType::DepthStencilWrite,
+                ..Default::default()
+            }),
+            Barrier::Image(ImageBarrier {
+                image: img,
+                old_layout: ImageLayout::Undefined,
+                new_layout: ImageLayout::ColorAttachment,
+                src_stage: PipelineStage::TopOfPipe,
+                dst_stage: PipelineStage::ColorAttachmentOutput,
+                src_access: AccessType::None,
+                dst_access: AccessType::ColorAttachmentWrite,
+                ..Default::default()
+            }),
+        ]);
+
+        // Shadow pass
+        recorder.begin_rendering(&RenderingBeginInfo {
+            render_area: RenderArea {
+                offset: Offset2D { x: 0, y: 0 },
+                extent: Extent2D {
+                    width: SHADOW_MAP_SIZE,
+                    height: SHADOW_MAP_SIZE,
+                },
+            },
+            layer_count: 1,
+            color_attachments: vec![],
+            depth_attachment: Some(RenderingAttachment {
+                image_view: self.shadow_map_view,
+                image_layout: ImageLayout::DepthStencilAttachment,
+                clear_value: ClearValue::depth_one(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        recorder.bind_pipeline(&self.shadow_pipeline);
+        recorder.set_viewport_and_scissor(SHADOW_MAP_SIZE, SHADOW_MAP_SIZE);
+        recorder.set_depth_bias(1.25, 0.0, 1.75);
+        recorder.set_push_constants(&push_constants, &self.shadow_pipeline);
+        recorder.bind_vertex_buffer(self.vertex_buffer, 0);
+        recorder.draw(self.vertex_count, 1, 0, 0);
+        recorder.end_rendering();
+
+        // Depth writes have to finish before the main pass samples the shadow map
+        recorder.pipeline_barrier(&[Barrier::Image(ImageBarrier {
+            image: self.shadow_map,
+            aspect: ImageAspect::Depth,
+            old_layout: ImageLayout::DepthStencilAttachment,
+            new_layout: ImageLayout::ShaderReadOnly,
+            src_stage: PipelineStage::LateFragmentTests,
+            dst_stage: PipelineStage::FragmentShader,
+            src_access: AccessType::DepthStencilWrite,
+            dst_access: AccessType::ShaderRead,
+            ..Default::default()
+        })]);
+
+        // Main pass
+        recorder.begin_rendering(&RenderingBeginInfo {
+            render_area: RenderArea {
+                offset: Offset2D { x: 0, y: 0 },
+                extent: Extent2D {
+                    width: size.width,
+                    height: size.height,
+                },
+            },
+            layer_count: 1,
+            color_attachments: vec![RenderingAttachment {
+                image_view: img_view,
+                image_layout: ImageLayout::ColorAttachment,
+                clear_value: ClearValue::ColorFloat([0.05, 0.05, 0.08, 1.0]),
+                ..Default::default()
+            }],
+            depth_attachment: Some(RenderingAttachment {
+                image_view: self.depth_view,
+                image_layout: ImageLayout::DepthStencilAttachment,
+                store_op: StoreOp::DontCare,
+                clear_value: ClearValue::depth_one(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        recorder.bind_pipeline(&self.scene_pipeline);
+        recorder.set_viewport_and_scissor(size.width, size.height);
+        recorder.set_push_constants(&push_constants, &self.scene_pipeline);
+        recorder.bind_vertex_buffer(self.vertex_buffer, 0);
+        recorder.draw(self.vertex_count, 1, 0, 0);
+        recorder.end_rendering();
+
+        recorder.pipeline_barrier(&[Barrier::Image(ImageBarrier {
+            image: img,
+            old_layout: ImageLayout::ColorAttachment,
+            new_layout: ImageLayout::PresentSrc,
+            src_stage: PipelineStage::ColorAttachmentOutput,
+            dst_stage: PipelineStage::BottomOfPipe,
+            src_access: AccessType::ColorAttachmentWrite,
+            dst_access: AccessType::None,
+            ..Default::default()
+        })]);
+        let exec_buffer = recorder.end_recording();
+
+        self.device.submit(&QueueSubmitInfo {
+            fence: Some(frame.fence),
+            command_buffers: vec![exec_buffer],
+            wait_semaphores: vec![SemaphoreInfo {
+                semaphore: image_semaphore,
+                pipeline_stage: PipelineStage::ColorAttachmentOutput,
+                value: None,
+            }],
+            signal_semaphores: vec![SemaphoreInfo {
+                semaphore: present_semaphore,
+                pipeline_stage: PipelineStage::BottomOfPipe,
+                value: None,
+            }],
+        });
+
+        self.swapchain.present();
+
+        self.curr_frame = (self.curr_frame + 1) % FRAME_IN_FLIGHT;
+    }
+}
+
+impl Drop for VulkanApp {
+    fn drop(&mut self) {
+        self.device.wait_idle();
+        self.device.destroy_buffer(self.vertex_buffer);
+        self.device.destroy_sampler(self.shadow_sampler);
+        self.device.destroy_image_view(self.shadow_map_view);
+        self.device.destroy_image(self.shadow_map);
+        self.device.destroy_image_view(self.depth_view);
+        self.device.destroy_image(self.depth_image);
+
+        for frame in &self.frame_data {
+            self.device.destroy_buffer(frame.scene_buffer);
+            self.device.destroy_fence(frame.fence);
+        }
+    }
+}
+
+#[allow(unused)]
+impl ApplicationHandler for VulkanApp {
+    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {}
+
+    fn window_event(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        window_id: winit::window::WindowId,
+        event: winit::event::WindowEvent,
+    ) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => self.resize(size.width, size.height),
+            WindowEvent::RedrawRequested => {
+                self.render();
+                self.window.request_redraw();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let event_loop: EventLoop<()> = EventLoop::with_user_event()
+        .build()
+        .expect("Failed to create event loop");
+
+    let mut app = VulkanApp::new(&event_loop);
+
+    event_loop
+        .run_app(&mut app)
+        .expect("Failed to run event loop");
+}
@@ -0,0 +1,345 @@
+//! Splits a single dynamic rendering across two command buffers.
+//!
+//! The first command buffer begins rendering with `RenderingFlags::Suspending` and draws the
+//! left triangle. The second begins the same rendering with `RenderingFlags::Resuming` and draws
+//! the right triangle. Both buffers go into one submission, in order, so the driver sees a single
+//! render pass instance. This is what allows a pass to be recorded in parallel.
+//!
+//! Rules for suspended renderings:
+//! 1. The resumed `RenderingBeginInfo` must match the suspended one, apart from the flags.
+//! 2. Nothing outside the rendering (barriers, copies, ...) may be recorded between the suspend
+//!    and the resume.
+//! 3. The buffer that resumes has to come right after the buffer that suspends in the same
+//!    `QueueSubmitInfo`, `Device::submit` panics otherwise.
+use vulcany::*;
+use winit::{
+    application::ApplicationHandler, event::WindowEvent, event_loop::EventLoop, window::Window,
+};
+
+use std::sync::Arc;
+
+const FRAME_IN_FLIGHT: usize = 3;
+
+vertex!(MyVertex {
+    input_rate: Vertex,
+    pos: [f32; 2],
+    color: [f32; 3],
+});
+
+struct FrameData {
+    first_half: CommandRecorder,
+    second_half: CommandRecorder,
+    fence: Fence,
+}
+
+#[allow(unused)]
+struct VulkanApp {
+    window: Arc<Window>,
+    instance: Instance,
+    device: Device,
+    swapchain: Swapchain,
+    pipeline_manager: PipelineManager,
+    raster_pipeline: RasterizationPipeline,
+    vertex_buffer: BufferID,
+    curr_frame: usize,
+    frame_data: Vec<FrameData>,
+}
+
+impl VulkanApp {
+    fn new(event_loop: &EventLoop<()>) -> VulkanApp {
+        let window = Arc::new(
+            event_loop
+                .create_window(Window::default_attributes())
+                .expect("Failed to create window"),
+        );
+
+        let size = window.inner_size();
+
+        let instance = Instance::new(&InstanceDescription {
+            api_version: ApiVersion::VkApi1_3,
+            enable_validation_layers: true,
+            debug_callback: None,
+            window: window.clone(),
+        });
+
+        let device = instance.create_device(&DeviceDescription {
+            use_compute_queue: true,
+            use_transfer_queue: true,
+            ray_tracing: false,
+            mesh_shaders: false,
+            adapter_index: None,
+            on_device_lost: None,
+        });
+
+        let swapchain = device.create_swapchain(&SwapchainDescription {
+            image_count: 3,
+            width: size.width,
+            height: size.height,
+            usage: ImageUsage::COLOR_ATTACHMENT,
+        });
+
+        let pipeline_manager = device.create_pipeline_manager();
+        let raster_pipeline =
+            pipeline_manager.create_rasterization_pipeline(&RasterizationPipelineDescription {
+                vertex_input: MyVertex::vertex_input_description(),
+                vertex_shader_path: "shaders/vertex_shader.slang",
+                fragment_shader_path: Some("shaders/fragment_shader.slang"),
+                outputs: PipelineOutputs {
+                    color: vec![Format::Rgba16Float],
+                    depth: None,
+                    stencil: None,
+                    view_mask: 0,
+                },
+                ..Default::default()
+            });
+
+        // Two triangles, one for each half of the rendering
+        let vertex_data = [
+            MyVertex {
+                pos: [-0.9, 0.5],
+                color: [0.8, 0.2, 0.2],
+            },
+            MyVertex {
+                pos: [-0.1, 0.5],
+                color: [0.8, 0.2, 0.2],
+            },
+            MyVertex {
+                pos: [-0.5, -0.5],
+                color: [0.8, 0.2, 0.2],
+            },
+            MyVertex {
+                pos: [0.1, 0.5],
+                color: [0.2, 0.2, 0.8],
+            },
+            MyVertex {
+                pos: [0.9, 0.5],
+                color: [0.2, 0.2, 0.8],
+            },
+            MyVertex {
+                pos: [0.5, -0.5],
+                color: [0.2, 0.2, 0.8],
+            },
+        ];
+
+        let vertex_buffer = device.create_buffer(&BufferDescription {
+            usage: BufferUsage::VERTEX,
+            size: std::mem::size_of_val(&vertex_data) as u64,
+            memory_type: MemoryType::PreferHost,
+            create_mapped: true,
+            ..Default::default()
+        });
+        device.write_data_to_buffer(vertex_buffer, &vertex_data);
+
+        let frame_data = (0..FRAME_IN_FLIGHT)
+            .map(|_| FrameData {
+                first_half: device.create_command_recorder(QueueType::Graphics),
+                second_half: device.create_command_recorder(QueueType::Graphics),
+                fence: device.create_fence(true),
+            })
+            .collect();
+
+        return VulkanApp {
+            window: window,
+            instance: instance,
+            device: device,
+            swapchain: swapchain,
+            pipeline_manager: pipeline_manager,
+            raster_pipeline: raster_pipeline,
+            vertex_buffer: vertex_buffer,
+            curr_frame: 0,
+            frame_data: frame_data,
+        };
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        // Minimized, the swapchain is recreated once the window has a size again
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        self.device.wait_idle();
+        let new_swapchain = self.device.recreate_swapchain(
+            &SwapchainDescription {
+                image_count: 3,
+                width: width,
+                height: height,
+                usage: ImageUsage::COLOR_ATTACHMENT,
+            },
+            &self.swapchain,
+        );
+        let old_swapchain = std::mem::replace(&mut self.swapchain, new_swapchain);
+        drop(old_swapchain);
+    }
+
+    // Both halves must use the same begin info, only the flags differ
+    fn rendering_info(
+        img_view: ImageViewID,
+        width: u32,
+        height: u32,
+        flags: RenderingFlags,
+    ) -> RenderingBeginInfo {
+        return RenderingBeginInfo {
+            render_area: RenderArea {
+                offset: Offset2D { x: 0, y: 0 },
+                extent: Extent2D {
+                    width: width,
+                    height: height,
+                },
+            },
+            rendering_flags: flags,
+            layer_count: 1,
+            color_attachments: vec![RenderingAttachment {
+                image_view: img_view,
+                image_layout: ImageLayout::ColorAttachment,
+                clear_value: ClearValue::ColorFloat([0.2, 0.2, 0.2, 1.0]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+    }
+
+    fn render(&mut self) {
+        let size = self.window.inner_size();
+
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        let frame = &mut self.frame_data[self.curr_frame];
+
+        self.device.wait_fence(frame.fence);
+        self.device.reset_fence(frame.fence);
+
+        let (img, img_view, image_semaphore, present_semaphore) = self.swapchain.acquire_image();
+
+        // First half, transitions the image and suspends the rendering
+        frame.first_half.reset();
+        frame
+            .first_half
+            .begin_recording(CommandBufferUsage::OneTimeSubmit);
+        frame
+            .first_half
+            .pipeline_barrier(&[Barrier::Image(ImageBarrier {
+                image: img,
+                old_layout: ImageLayout::Undefined,
+                new_layout: ImageLayout::ColorAttachment,
+                src_stage: PipelineStage::TopOfPipe,
+                dst_stage: PipelineStage::ColorAttachmentOutput,
+                src_access: AccessType::None,
+                dst_access: AccessType::ColorAttachmentWrite,
+                ..Default::default()
+            })]);
+        frame.first_half.begin_rendering(&Self::rendering_info(
+            img_view,
+            size.width,
+            size.height,
+            RenderingFlags::Suspending,
+        ));
+        frame.first_half.bind_pipeline(&self.raster_pipeline);
+        frame
+            .first_half
+            .set_viewport_and_scissor(size.width, size.height);
+        frame.first_half.bind_vertex_buffer(self.vertex_buffer, 0);
+        frame.first_half.draw(3, 1, 0, 0);
+        frame.first_half.end_rendering();
+        let first_half = frame.first_half.end_recording();
+
+        // Second half, resumes the rendering and transitions the image for presentation
+        // Dynamic state and bindings do not carry over between command buffers
+        frame.second_half.reset();
+        frame
+            .second_half
+            .begin_recording(CommandBufferUsage::OneTimeSubmit);
+        frame.second_half.begin_rendering(&Self::rendering_info(
+            img_view,
+            size.width,
+            size.height,
+            RenderingFlags::Resuming,
+        ));
+        frame.second_half.bind_pipeline(&self.raster_pipeline);
+        frame
+            .second_half
+            .set_viewport_and_scissor(size.width, size.height);
+        frame.second_half.bind_vertex_buffer(self.vertex_buffer, 0);
+        frame.second_half.draw(3, 1, 3, 0);
+        frame.second_half.end_rendering();
+        frame
+            .second_half
+            .pipeline_barrier(&[Barrier::Image(ImageBarrier {
+                image: img,
+                old_layout: ImageLayout::ColorAttachment,
+                new_layout: ImageLayout::PresentSrc,
+                src_stage: PipelineStage::ColorAttachmentOutput,
+                dst_stage: PipelineStage::BottomOfPipe,
+                src_access: AccessType::ColorAttachmentWrite,
+                dst_access: AccessType::None,
+                ..Default::default()
+            })]);
+        let second_half = frame.second_half.end_recording();
+
+        // Order matters, the resuming buffer must directly follow the suspending one
+        self.device.submit(&QueueSubmitInfo {
+            fence: Some(frame.fence),
+            command_buffers: vec![first_half, second_half],
+            wait_semaphores: vec![SemaphoreInfo {
+                semaphore: image_semaphore,
+                pipeline_stage: PipelineStage::ColorAttachmentOutput,
+                value: None,
+            }],
+            signal_semaphores: vec![SemaphoreInfo {
+                semaphore: present_semaphore,
+                pipeline_stage: PipelineStage::BottomOfPipe,
+                value: None,
+            }],
+        });
+
+        self.swapchain.present();
+
+        self.curr_frame = (self.curr_frame + 1) % FRAME_IN_FLIGHT;
+    }
+}
+
+impl Drop for VulkanApp {
+    fn drop(&mut self) {
+        self.device.wait_idle();
+        self.device.destroy_buffer(self.vertex_buffer);
+
+        for frame in &self.frame_data {
+            self.device.destroy_fence(frame.fence);
+        }
+    }
+}
+
+#[allow(unused)]
+impl ApplicationHandler for VulkanApp {
+    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {}
+
+    fn window_event(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        window_id: winit::window::WindowId,
+        event: winit::event::WindowEvent,
+    ) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => self.resize(size.width, size.height),
+            WindowEvent::RedrawRequested => {
+                self.render();
+                self.window.request_redraw();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let event_loop: EventLoop<()> = EventLoop::with_user_event()
+        .build()
+        .expect("Failed to create event loop");
+
+    let mut app = VulkanApp::new(&event_loop);
+
+    event_loop
+        .run_app(&mut app)
+        .expect("Failed to run event loop");
+}
@@ -0,0 +1,372 @@
+//! Streams vertex data on the transfer queue while the graphics queue renders.
+//!
+//! Every frame the new vertices go into a `StagingRing` and a transfer command buffer copies them
+//! into a device local vertex buffer. The transfer submission signals a timeline semaphore and the
+//! graphics submission waits on it at the vertex input stage, so only the vertex fetch waits for
+//! the upload, everything before it runs concurrently.
+//!
+//! The vertex buffer is owned by the transfer queue family while it is written, so the transfer
+//! side releases it and the graphics side acquires it with matching buffer barriers. When both
+//! queues share a family the barriers are plain memory barriers and the semaphore does the work.
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulcany::utils::staging_ring::StagingRing;
+use vulcany::*;
+use winit::{
+    application::ApplicationHandler, event::WindowEvent, event_loop::EventLoop, window::Window,
+};
+
+const FRAME_IN_FLIGHT: usize = 3;
+const VERTEX_COUNT: usize = 3;
+
+vertex!(MyVertex {
+    input_rate: Vertex,
+    pos: [f32; 2],
+    color: [f32; 3],
+});
+
+struct FrameData {
+    transfer_recorder: CommandRecorder,
+    graphics_recorder: CommandRecorder,
+    // One buffer per frame, the copy for this frame must not race the draw of an older one
+    vertex_buffer: BufferID,
+    fence: Fence,
+}
+
+#[allow(unused)]
+struct VulkanApp {
+    window: Arc<Window>,
+    instance: Instance,
+    device: Device,
+    swapchain: Swapchain,
+    pipeline_manager: PipelineManager,
+    raster_pipeline: RasterizationPipeline,
+    staging_ring: StagingRing,
+    upload_semaphore: Semaphore,
+    upload_value: u64,
+    start: Instant,
+    curr_frame: usize,
+    frame_data: Vec<FrameData>,
+}
+
+impl VulkanApp {
+    fn new(event_loop: &EventLoop<()>) -> VulkanApp {
+        let window = Arc::new(
+            event_loop
+                .create_window(Window::default_attributes())
+                .expect("Failed to create window"),
+        );
+
+        let size = window.inner_size();
+
+        let instance = Instance::new(&InstanceDescription {
+            api_version: ApiVersion::VkApi1_3,
+            enable_validation_layers: true,
+            debug_callback: None,
+            window: window.clone(),
+        });
+
+        let device = instance.create_device(&DeviceDescription {
+            use_compute_queue: true,
+            use_transfer_queue: true,
+            ray_tracing: false,
+            mesh_shaders: false,
+            adapter_index: None,
+            on_device_lost: None,
+        });
+
+        let swapchain = device.create_swapchain(&SwapchainDescription {
+            image_count: 3,
+            width: size.width,
+            height: size.height,
+            usage: ImageUsage::COLOR_ATTACHMENT,
+        });
+
+        let pipeline_manager = device.create_pipeline_manager();
+        let raster_pipeline =
+            pipeline_manager.create_rasterization_pipeline(&RasterizationPipelineDescription {
+                vertex_input: MyVertex::vertex_input_description(),
+                vertex_shader_path: "shaders/vertex_shader.slang",
+                fragment_shader_path: Some("shaders/fragment_shader.slang"),
+                outputs: PipelineOutputs {
+                    color: vec![Format::Rgba16Float],
+                    depth: None,
+                    stencil: None,
+                    view_mask: 0,
+                },
+                ..Default::default()
+            });
+
+        let vertex_size = (VERTEX_COUNT * std::mem::size_of::<MyVertex>()) as u64;
+
+        let frame_data = (0..FRAME_IN_FLIGHT)
+            .map(|_| FrameData {
+                transfer_recorder: device.create_command_recorder(QueueType::Transfer),
+                graphics_recorder: device.create_command_recorder(QueueType::Graphics),
+                vertex_buffer: device.create_buffer(&BufferDescription {
+                    usage: BufferUsage::TRANSFER_DST | BufferUsage::VERTEX,
+                    size: vertex_size,
+                    memory_type: MemoryType::DeviceLocal,
+                    create_mapped: false,
+                    ..Default::default()
+                }),
+                fence: device.create_fence(true),
+            })
+            .collect();
+
+        let staging_ring = StagingRing::new(&device, 64 * 1024, FRAME_IN_FLIGHT);
+        let upload_semaphore = device.create_timeline_semaphore();
+
+        return VulkanApp {
+            window: window,
+            instance: instance,
+            device: device,
+            swapchain: swapchain,
+            pipeline_manager: pipeline_manager,
+            raster_pipeline: raster_pipeline,
+            staging_ring: staging_ring,
+            upload_semaphore: upload_semaphore,
+            upload_value: 0,
+            start: Instant::now(),
+            curr_frame: 0,
+            frame_data: frame_data,
+        };
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        // Minimized, the swapchain is recreated once the window has a size again
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        self.device.wait_idle();
+        let new_swapchain = self.device.recreate_swapchain(
+            &SwapchainDescription {
+                image_count: 3,
+                width: width,
+                height: height,
+                usage: ImageUsage::COLOR_ATTACHMENT,
+            },
+            &self.swapchain,
+        );
+        let old_swapchain = std::mem::replace(&mut self.swapchain, new_swapchain);
+        drop(old_swapchain);
+    }
+
+    // A triangle spinning around the center, regenerated on the cpu every frame
+    fn vertices(time: f32) -> [MyVertex; VERTEX_COUNT] {
+        let colors = [[0.8, 0.2, 0.2], [0.2, 0.8, 0.2], [0.2, 0.2, 0.8]];
+
+        return std::array::from_fn(|i| {
+            let angle = time + i as f32 * std::f32::consts::TAU / VERTEX_COUNT as f32;
+            MyVertex {
+                pos: [0.6 * angle.cos(), 0.6 * angle.sin()],
+                color: colors[i],
+            }
+        });
+    }
+
+    fn render(&mut self) {
+        let size = self.window.inner_size();
+
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        let frame = &mut self.frame_data[self.curr_frame];
+
+        // Frees this frame's staging region once the fence says the gpu is done with it
+        self.staging_ring.begin_frame(self.curr_frame, frame.fence);
+        self.device.reset_fence(frame.fence);
+
+        // Transfer queue: copy the new vertices and release the buffer to the graphics queue
+        let vertices = Self::vertices(self.start.elapsed().as_secs_f32());
+        let (staging_buffer, staging_offset) = self.staging_ring.write(&vertices);
+        let vertex_size = std::mem::size_of_val(&vertices) as u64;
+
+        frame.transfer_recorder.reset();
+        frame
+            .transfer_recorder
+            .begin_recording(CommandBufferUsage::OneTimeSubmit);
+        frame.transfer_recorder.copy_buffer(&BufferCopyInfo {
+            src_buffer: staging_buffer,
+            dst_buffer: frame.vertex_buffer,
+            src_offset: staging_offset,
+            dst_offset: 0,
+            size: vertex_size,
+        });
+        frame
+            .transfer_recorder
+            .pipeline_barrier(&[Barrier::Buffer(BufferBarrier {
+                buffer: frame.vertex_buffer,
+                src_stage: PipelineStage::Transfer,
+                dst_stage: PipelineStage::BottomOfPipe,
+                src_access: AccessType::TransferWrite,
+                dst_access: AccessType::None,
+                src_queue: QueueType::Transfer,
+                dst_queue: QueueType::Graphics,
+                offset: 0,
+                size: vertex_size,
+            })]);
+        let transfer_cmd = frame.transfer_recorder.end_recording();
+
+        self.upload_value += 1;
+
+        self.device.submit(&QueueSubmitInfo {
+            fence: None,
+            command_buffers: vec![transfer_cmd],
+            wait_semaphores: vec![],
+            signal_semaphores: vec![SemaphoreInfo {
+                semaphore: self.upload_semaphore,
+                pipeline_stage: PipelineStage::Transfer,
+                value: Some(self.upload_value),
+            }],
+        });
+
+        // Graphics queue: acquire the buffer and draw with it
+        let (img, img_view, image_semaphore, present_semaphore) = self.swapchain.acquire_image();
+
+        frame.graphics_recorder.reset();
+        frame
+            .graphics_recorder
+            .begin_recording(CommandBufferUsage::OneTimeSubmit);
+        frame.graphics_recorder.pipeline_barrier(&[
+            Barrier::Buffer(BufferBarrier {
+                buffer: frame.vertex_buffer,
+                src_stage: PipelineStage::Transfer,
+                dst_stage: PipelineStage::VertexInput,
+                src_access: AccessType::TransferWrite,
+                dst_access: AccessType::VertexRead,
+                src_queue: QueueType::Transfer,
+                dst_queue: QueueType::Graphics,
+                offset: 0,
+                size: vertex_size,
+            }),
+            Barrier::Image(ImageBarrier {
+                image: img,
+                old_layout: ImageLayout::Undefined,
+                new_layout: ImageLayout::ColorAttachment,
+                src_stage: PipelineStage::TopOfPipe,
+                dst_stage: PipelineStage::ColorAttachmentOutput,
+                src_access: AccessType::None,
+                dst_access: AccessType::ColorAttachmentWrite,
+                ..Default::default()
+            }),
+        ]);
+        frame
+            .graphics_recorder
+            .begin_rendering(&RenderingBeginInfo {
+                render_area: RenderArea {
+                    offset: Offset2D { x: 0, y: 0 },
+                    extent: Extent2D {
+                        width: size.width,
+                        height: size.height,
+                    },
+                },
+                layer_count: 1,
+                color_attachments: vec![RenderingAttachment {
+                    image_view: img_view,
+                    image_layout: ImageLayout::ColorAttachment,
+                    clear_value: ClearValue::ColorFloat([0.1, 0.1, 0.1, 1.0]),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            });
+        frame.graphics_recorder.bind_pipeline(&self.raster_pipeline);
+        frame
+            .graphics_recorder
+            .set_viewport_and_scissor(size.width, size.height);
+        frame
+            .graphics_recorder
+            .bind_vertex_buffer(frame.vertex_buffer, 0);
+        frame.graphics_recorder.draw(VERTEX_COUNT as u32, 1, 0, 0);
+        frame.graphics_recorder.end_rendering();
+        frame
+            .graphics_recorder
+            .pipeline_barrier(&[Barrier::Image(ImageBarrier {
+                image: img,
+                old_layout: ImageLayout::ColorAttachment,
+                new_layout: ImageLayout::PresentSrc,
+                src_stage: PipelineStage::ColorAttachmentOutput,
+                dst_stage: PipelineStage::BottomOfPipe,
+                src_access: AccessType::ColorAttachmentWrite,
+                dst_access: AccessType::None,
+                ..Default::default()
+            })]);
+        let graphics_cmd = frame.graphics_recorder.end_recording();
+
+        // Only vertex input waits for the upload, the rest of the frame can start right away
+        self.device.submit(&QueueSubmitInfo {
+            fence: Some(frame.fence),
+            command_buffers: vec![graphics_cmd],
+            wait_semaphores: vec![
+                SemaphoreInfo {
+                    semaphore: image_semaphore,
+                    pipeline_stage: PipelineStage::ColorAttachmentOutput,
+                    value: None,
+                },
+                SemaphoreInfo {
+                    semaphore: self.upload_semaphore,
+                    pipeline_stage: PipelineStage::VertexInput,
+                    value: Some(self.upload_value),
+                },
+            ],
+            signal_semaphores: vec![SemaphoreInfo {
+                semaphore: present_semaphore,
+                pipeline_stage: PipelineStage::BottomOfPipe,
+                value: None,
+            }],
+        });
+
+        self.swapchain.present();
+
+        self.curr_frame = (self.curr_frame + 1) % FRAME_IN_FLIGHT;
+    }
+}
+
+impl Drop for VulkanApp {
+    fn drop(&mut self) {
+        self.device.wait_idle();
+        self.device.destroy_semaphore(self.upload_semaphore);
+
+        for frame in &self.frame_data {
+            self.device.destroy_buffer(frame.vertex_buffer);
+            self.device.destroy_fence(frame.fence);
+        }
+    }
+}
+
+#[allow(unused)]
+impl ApplicationHandler for VulkanApp {
+    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {}
+
+    fn window_event(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        window_id: winit::window::WindowId,
+        event: winit::event::WindowEvent,
+    ) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => self.resize(size.width, size.height),
+            WindowEvent::RedrawRequested => {
+                self.render();
+                self.window.request_redraw();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let event_loop: EventLoop<()> = EventLoop::with_user_event()
+        .build()
+        .expect("Failed to create event loop");
+
+    let mut app = VulkanApp::new(&event_loop);
+
+    event_loop
+        .run_app(&mut app)
+        .expect("Failed to run event loop");
+}
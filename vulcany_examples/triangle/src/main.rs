@@ -1,4 +1,5 @@
 use std::time::Instant;
+use vulcany::utils::frames::Frames;
 use vulcany::*;
 use winit::{
     application::ApplicationHandler, event::WindowEvent, event_loop::EventLoop, window::Window,
@@ -8,16 +9,17 @@ use std::sync::Arc;
 
 const FRAME_IN_FLIGHT: usize = 3;
 
-vertex!(MyVertex {
-    input_rate: Vertex,
-    pos: [f32; 2],
-    color: [f32; 3],
-});
-
-struct FrameData {
-    command_recorder: CommandRecorder,
-    fence: Fence,
-}
+// The color is per instance, it lives in binding 1 at location 1
+vertex!(
+    MyVertex {
+        input_rate: Vertex,
+        pos: [f32; 2],
+    }
+    MyInstance {
+        input_rate: Instance,
+        color: [f32; 3],
+    }
+);
 
 #[allow(unused)]
 struct VulkanApp {
@@ -28,9 +30,9 @@ struct VulkanApp {
     pipeline_manager: PipelineManager,
     raster_pipeline: RasterizationPipeline,
     vertex_buffer: BufferID,
-    color_buffer: BufferID,
+    instance_buffer: BufferID,
     time: f32,
-    frame_data: [FrameData; FRAME_IN_FLIGHT],
+    frames: Frames<FRAME_IN_FLIGHT>,
 }
 
 impl VulkanApp {
@@ -48,6 +50,7 @@ impl VulkanApp {
         let instance = Instance::new(&InstanceDescription {
             api_version: ApiVersion::VkApi1_3,
             enable_validation_layers: true,
+            debug_callback: None,
             window: window.clone(),
         });
 
@@ -55,12 +58,16 @@ impl VulkanApp {
             use_compute_queue: true,
             use_transfer_queue: true,
             ray_tracing: false,
+            mesh_shaders: false,
+            adapter_index: None,
+            on_device_lost: None,
         });
 
         let swapchain = device.create_swapchain(&SwapchainDescription {
             image_count: 8,
             width: size.width,
             height: size.height,
+            usage: ImageUsage::COLOR_ATTACHMENT,
         });
 
         let pipeline_manager = device.create_pipeline_manager();
@@ -68,96 +75,42 @@ impl VulkanApp {
             pipeline_manager.create_rasterization_pipeline(&RasterizationPipelineDescription {
                 vertex_input: MyVertex::vertex_input_description(),
                 vertex_shader_path: "shaders/vertex_shader.slang",
-                fragment_shader_path: "shaders/fragment_shader.slang",
+                fragment_shader_path: Some("shaders/fragment_shader.slang"),
                 alpha_blend_enable: false,
                 outputs: PipelineOutputs {
                     color: vec![Format::Rgba16Float],
                     depth: None,
                     stencil: None,
+                    view_mask: 0,
                 },
                 ..Default::default()
             });
 
         let vertex_data = [
-            MyVertex {
-                pos: [0.5, 0.5],
-                color: [0.2, 0.2, 0.8],
-            },
-            MyVertex {
-                pos: [-0.5, 0.5],
-                color: [0.2, 0.8, 0.2],
-            },
-            MyVertex {
-                pos: [0.0, -0.5],
-                color: [0.8, 0.2, 0.2],
-            },
+            MyVertex { pos: [0.5, 0.5] },
+            MyVertex { pos: [-0.5, 0.5] },
+            MyVertex { pos: [0.0, -0.5] },
         ];
 
-        let staging_buffer = device.create_buffer(&BufferDescription {
-            usage: BufferUsage::TRANSFER_SRC,
-            size: 60,
-            memory_type: MemoryType::PreferHost,
-            create_mapped: true,
-        });
-
-        device.write_data_to_buffer(staging_buffer, &vertex_data);
-
-        let vertex_buffer = device.create_buffer(&BufferDescription {
-            usage: BufferUsage::TRANSFER_DST | BufferUsage::VERTEX,
-            size: 60,
-            memory_type: MemoryType::DeviceLocal,
-            create_mapped: false,
-        });
-
-        let mut recorder = device.create_command_recorder(QueueType::Transfer);
-        recorder.begin_recording(CommandBufferUsage::OneTimeSubmit);
-        recorder.copy_buffer(&BufferCopyInfo {
-            src_buffer: staging_buffer,
-            dst_buffer: vertex_buffer,
-            size: 60,
-            src_offset: 0,
-            dst_offset: 0,
-        });
-        let exec_cmd = recorder.end_recording();
-        device.submit(&QueueSubmitInfo {
-            fence: None,
-            command_buffers: vec![exec_cmd],
-            wait_semaphores: vec![],
-            signal_semaphores: vec![],
-        });
-        device.wait_queue(QueueType::Transfer);
-        device.destroy_buffer(staging_buffer);
+        let vertex_buffer = device.upload_buffer(&vertex_data, BufferUsage::VERTEX);
 
-        let color_buffer = device.create_buffer(&BufferDescription {
-            usage: BufferUsage::STORAGE,
-            size: 12,
+        // Rewritten every frame, so it stays host visible
+        let instance_buffer = device.create_buffer(&BufferDescription {
+            usage: BufferUsage::VERTEX,
+            size: size_of::<MyInstance>() as u64,
             memory_type: MemoryType::PreferHost,
             create_mapped: true,
+            ..Default::default()
         });
-        let color_data = [[0.1, 0.8, 0.1]];
-        device.write_data_to_buffer(color_buffer, &color_data);
-        device.write_buffer(&BufferWriteInfo {
-            buffer: color_buffer,
-            offset: 0,
-            range: 12,
-            index: 0,
-        });
+        device.write_data_to_buffer(
+            instance_buffer,
+            &[MyInstance {
+                color: [0.1, 0.8, 0.1],
+            }],
+        );
 
         return VulkanApp {
-            frame_data: [
-                FrameData {
-                    command_recorder: device.create_command_recorder(QueueType::Graphics),
-                    fence: device.create_fence(true),
-                },
-                FrameData {
-                    command_recorder: device.create_command_recorder(QueueType::Graphics),
-                    fence: device.create_fence(true),
-                },
-                FrameData {
-                    command_recorder: device.create_command_recorder(QueueType::Graphics),
-                    fence: device.create_fence(true),
-                },
-            ],
+            frames: Frames::new(&device, QueueType::Graphics),
             window: window,
             instance: instance,
             device: device,
@@ -165,18 +118,24 @@ impl VulkanApp {
             pipeline_manager: pipeline_manager,
             raster_pipeline: raster_pipeline,
             vertex_buffer: vertex_buffer,
-            color_buffer: color_buffer,
+            instance_buffer: instance_buffer,
             time: 0.0,
         };
     }
 
     fn resize(&mut self, width: u32, height: u32) {
+        // Minimized, the swapchain is recreated once the window has a size again
+        if width == 0 || height == 0 {
+            return;
+        }
+
         self.device.wait_idle();
         let new_swapchain = self.device.recreate_swapchain(
             &SwapchainDescription {
                 image_count: 3,
                 width: width,
                 height: height,
+                usage: ImageUsage::COLOR_ATTACHMENT,
             },
             &self.swapchain,
         );
@@ -184,9 +143,8 @@ impl VulkanApp {
         drop(old_swapchain);
     }
 
-    unsafe fn render(&mut self) {
+    fn render(&mut self) {
         let size = self.window.inner_size();
-        static mut curr_frame: usize = 0;
 
         if size.width == 0 || size.height == 0 {
             return;
@@ -201,86 +159,54 @@ impl VulkanApp {
         };
 
         self.device
-            .write_data_to_buffer(self.color_buffer, &[color]);
+            .write_data_to_buffer(self.instance_buffer, &[MyInstance { color: color }]);
 
-        self.device.wait_fence(self.frame_data[curr_frame].fence);
-        self.device.reset_fence(self.frame_data[curr_frame].fence);
+        let recorder = self.frames.begin_frame();
 
         let (img, img_view, image_semaphore, present_semaphore) = self.swapchain.acquire_image();
 
-        self.frame_data[curr_frame].command_recorder.reset();
-
-        self.frame_data[curr_frame]
-            .command_recorder
-            .begin_recording(CommandBufferUsage::OneTimeSubmit);
-
-        self.frame_data[curr_frame]
-            .command_recorder
-            .pipeline_barrier(&[Barrier::Image(ImageBarrier {
-                image: img,
-                old_layout: ImageLayout::Undefined,
-                new_layout: ImageLayout::ColorAttachment,
-                src_stage: PipelineStage::TopOfPipe,
-                dst_stage: PipelineStage::ColorAttachmentOutput,
-                src_access: AccessType::None,
-                dst_access: AccessType::ColorAttachmentWrite,
-                ..Default::default()
-            })]);
-
-        self.frame_data[curr_frame]
-            .command_recorder
-            .begin_rendering(&RenderingBeginInfo {
-                render_area: RenderArea {
-                    offset: Offset2D { x: 0, y: 0 },
-                    extent: Extent2D {
-                        width: size.width,
-                        height: size.height,
-                    },
+        recorder.pipeline_barrier(&[Barrier::image_transition(
+            img,
+            ImageLayout::Undefined,
+            ImageLayout::ColorAttachment,
+        )]);
+
+        recorder.begin_rendering(&RenderingBeginInfo {
+            render_area: RenderArea {
+                offset: Offset2D { x: 0, y: 0 },
+                extent: Extent2D {
+                    width: size.width,
+                    height: size.height,
                 },
-                rendering_flags: RenderingFlags::None,
-                view_mask: 0,
-                layer_count: 1,
-                color_attachments: vec![RenderingAttachment {
-                    image_view: img_view,
-                    image_layout: ImageLayout::ColorAttachment,
-                    clear_value: ClearValue::ColorFloat([0.2, 0.2, 0.4, 1.0]),
-                    ..Default::default()
-                }],
-                depth_attachment: None,
-                stencil_attachment: None,
-            });
-
-        self.frame_data[curr_frame]
-            .command_recorder
-            .bind_pipeline(&self.raster_pipeline);
-        self.frame_data[curr_frame]
-            .command_recorder
-            .set_viewport_and_scissor(size.width, size.height);
-        self.frame_data[curr_frame]
-            .command_recorder
-            .bind_vertex_buffer(self.vertex_buffer, 0);
-        self.frame_data[curr_frame]
-            .command_recorder
-            .draw(3, 1, 0, 0);
-
-        self.frame_data[curr_frame].command_recorder.end_rendering();
-        self.frame_data[curr_frame]
-            .command_recorder
-            .pipeline_barrier(&[Barrier::Image(ImageBarrier {
-                image: img,
-                old_layout: ImageLayout::ColorAttachment,
-                new_layout: ImageLayout::PresentSrc,
-                src_stage: PipelineStage::ColorAttachmentOutput,
-                dst_stage: PipelineStage::BottomOfPipe,
-                src_access: AccessType::ColorAttachmentWrite,
-                dst_access: AccessType::None,
+            },
+            rendering_flags: RenderingFlags::None,
+            view_mask: 0,
+            layer_count: 1,
+            color_attachments: vec![RenderingAttachment {
+                image_view: img_view,
+                image_layout: ImageLayout::ColorAttachment,
+                clear_value: ClearValue::ColorFloat([0.2, 0.2, 0.4, 1.0]),
                 ..Default::default()
-            })]);
-        let exec_buffer = self.frame_data[curr_frame].command_recorder.end_recording();
+            }],
+            depth_attachment: None,
+            stencil_attachment: None,
+        });
+
+        recorder.bind_pipeline(&self.raster_pipeline);
+        recorder.set_viewport_and_scissor(size.width, size.height);
+        recorder.bind_vertex_buffers(0, &[(self.vertex_buffer, 0), (self.instance_buffer, 0)]);
+        recorder.draw(3, 1, 0, 0);
 
-        self.device.submit(&QueueSubmitInfo {
-            fence: Some(self.frame_data[curr_frame].fence),
-            command_buffers: vec![exec_buffer],
+        recorder.end_rendering();
+        recorder.pipeline_barrier(&[Barrier::image_transition(
+            img,
+            ImageLayout::ColorAttachment,
+            ImageLayout::PresentSrc,
+        )]);
+
+        self.frames.end_frame(QueueSubmitInfo {
+            fence: None,
+            command_buffers: vec![],
             wait_semaphores: vec![SemaphoreInfo {
                 semaphore: image_semaphore,
                 pipeline_stage: PipelineStage::ColorAttachmentOutput,
@@ -294,10 +220,6 @@ impl VulkanApp {
         });
 
         self.swapchain.present();
-
-        unsafe {
-            curr_frame = (curr_frame + 1) % FRAME_IN_FLIGHT;
-        }
     }
 }
 
@@ -305,11 +227,7 @@ impl Drop for VulkanApp {
     fn drop(&mut self) {
         self.device.wait_idle();
         self.device.destroy_buffer(self.vertex_buffer);
-        self.device.destroy_buffer(self.color_buffer);
-
-        for i in 0..FRAME_IN_FLIGHT {
-            self.device.destroy_fence(self.frame_data[i].fence);
-        }
+        self.device.destroy_buffer(self.instance_buffer);
     }
 }
 
@@ -327,13 +245,11 @@ impl ApplicationHandler for VulkanApp {
             WindowEvent::CloseRequested => event_loop.exit(),
             WindowEvent::Resized(size) => self.resize(size.width, size.height),
             WindowEvent::RedrawRequested => {
-                unsafe {
-                    let start = Instant::now();
-                    self.render();
-                    let duration = start.elapsed();
-                    self.time += duration.as_secs_f32()
-                    //println!("{}", duration.as_millis());
-                }
+                let start = Instant::now();
+                self.render();
+                let duration = start.elapsed();
+                self.time += duration.as_secs_f32();
+                //println!("{}", duration.as_millis());
                 self.window.request_redraw();
             }
             _ => {}